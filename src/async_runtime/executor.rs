@@ -24,7 +24,7 @@ pub enum TaskState {
 pub struct Task {
     pub id: TaskId,
     pub name: String,
-    pub body: Vec<Stmt>,
+    pub body: Arc<Vec<Stmt>>,
     pub state: TaskState,
     pub function: Option<FunctionValue>,
     pub arguments: Vec<Value>,
@@ -35,7 +35,7 @@ impl Task {
         Self {
             id,
             name,
-            body,
+            body: Arc::new(body),
             state: TaskState::Pending,
             function: None,
             arguments: Vec::new(),
@@ -61,6 +61,12 @@ pub struct AsyncExecutor {
     next_task_id: Arc<Mutex<TaskId>>,
 }
 
+impl Default for AsyncExecutor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl AsyncExecutor {
     pub fn new() -> Self {
         Self {
@@ -0,0 +1,86 @@
+//! Bytecode representation
+//!
+//! This module defines the instructions produced by the compiler and the
+//! chunks and functions that hold them.
+
+use crate::error::SourceLocation;
+use crate::parser::ast::{BinaryOp, UnaryOp};
+use crate::runtime::Value;
+
+/// A single stack machine instruction
+///
+/// Jump targets are absolute instruction indices within the same chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpCode {
+    /// Push `constants[index]`
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    /// Discard the top of the stack
+    Pop,
+    /// Push the local in `slot`, counted from the current frame's base
+    GetLocal(usize),
+    /// Store the top of the stack in a local, leaving it on the stack
+    SetLocal(usize),
+    /// Pop a value into a new global named by `constants[index]`
+    DefineGlobal(usize),
+    /// Push a global (or builtin) named by `constants[index]`
+    GetGlobal(usize),
+    /// Store the top of the stack in an existing global, leaving it on the stack
+    SetGlobal(usize),
+    /// Pop two operands and push the result
+    Binary(BinaryOp),
+    /// Pop an operand and push the result
+    Unary(UnaryOp),
+    Jump(usize),
+    /// Jump when the top of the stack is falsy; the condition stays on the stack
+    JumpIfFalse(usize),
+    /// Jump when the top of the stack is truthy; the condition stays on the stack
+    JumpIfTrue(usize),
+    /// Call `functions[function]` with the given number of arguments
+    Call { function: usize, argc: usize },
+    /// Call the global or builtin named by `constants[name]`
+    CallGlobal { name: usize, argc: usize },
+    /// Return the top of the stack from the current function
+    Return,
+}
+
+/// A sequence of instructions with their constants
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<OpCode>,
+    pub constants: Vec<Value>,
+    /// Source location of each instruction, for error messages
+    pub locations: Vec<SourceLocation>,
+}
+
+impl Chunk {
+    /// Append an instruction, returning its index
+    pub fn emit(&mut self, op: OpCode, location: &SourceLocation) -> usize {
+        self.code.push(op);
+        self.locations.push(location.clone());
+        self.code.len() - 1
+    }
+
+    /// Add a constant, returning its index
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+}
+
+/// A compiled function
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub arity: usize,
+    pub chunk: Chunk,
+}
+
+/// A compiled program: the top-level code and every function it declares
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub main: Function,
+    pub functions: Vec<Function>,
+}
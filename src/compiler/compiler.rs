@@ -0,0 +1,454 @@
+//! Bytecode compiler implementation
+//!
+//! This module lowers an AST to a [`Program`]. Top-level variables become
+//! globals looked up by name; variables declared in blocks and functions live
+//! in stack slots resolved at compile time.
+//!
+//! The bytecode backend covers a subset of the language: literals,
+//! arithmetic, variables, `if`, `while`, `for`, `break`/`continue`, and calls
+//! to top-level functions and builtins. Anything else is reported as an error
+//! rather than silently miscompiled.
+
+use std::collections::HashMap;
+use crate::error::{LuxError, LuxResult, SourceLocation};
+use crate::lexer::Symbol;
+use crate::parser::ast::{Ast, Expr, Literal, LogicalOp, Stmt, UnaryOp};
+use crate::runtime::Value;
+use super::chunk::{Function, OpCode, Program};
+
+/// Compile a checked AST to bytecode
+pub fn compile(ast: &Ast) -> LuxResult<Program> {
+    // Number the top-level functions up front so calls can come before the
+    // declaration, matching the interpreter's hoisting
+    let mut function_indices = HashMap::new();
+    let mut declarations = Vec::new();
+    for stmt in &ast.statements {
+        if let Stmt::FunctionDecl { name, params, body, is_async, location, .. } = stmt {
+            if *is_async {
+                return Err(unsupported("Async functions", location));
+            }
+            function_indices.insert(*name, declarations.len());
+            declarations.push((name, params, body, location));
+        }
+    }
+
+    let mut functions = Vec::new();
+    for (name, params, body, location) in declarations {
+        let mut compiler = Compiler::new(name, params.len(), &function_indices);
+        // Parameters and the body share the function's scope
+        compiler.scope_depth = 1;
+        for (param, _) in params {
+            compiler.locals.push(Local { name: *param, depth: 1 });
+        }
+        for stmt in body.iter() {
+            compiler.statement(stmt)?;
+        }
+        compiler.emit(OpCode::Nil, location);
+        compiler.emit(OpCode::Return, location);
+        functions.push(compiler.function);
+    }
+
+    // The program's value is that of its final expression statement, as in the interpreter
+    let mut main = Compiler::new("<main>", 0, &function_indices);
+    match ast.statements.split_last() {
+        Some((Stmt::Expression { expr, location }, rest)) => {
+            for stmt in rest {
+                main.statement(stmt)?;
+            }
+            main.expression(expr)?;
+            main.emit(OpCode::Return, location);
+        }
+        Some((last, _)) => {
+            for stmt in &ast.statements {
+                main.statement(stmt)?;
+            }
+            main.emit(OpCode::Nil, last.location());
+            main.emit(OpCode::Return, last.location());
+        }
+        None => {
+            let start = SourceLocation::at(1, 1);
+            main.emit(OpCode::Nil, &start);
+            main.emit(OpCode::Return, &start);
+        }
+    }
+
+    Ok(Program { main: main.function, functions })
+}
+
+fn unsupported(what: &str, location: &SourceLocation) -> LuxError {
+    LuxError::semantic_error(
+        format!("{} are not supported by the bytecode backend yet", what),
+        location.clone(),
+    )
+}
+
+/// A variable living in a stack slot of the current function
+struct Local {
+    name: Symbol,
+    depth: usize,
+}
+
+/// Jumps out of the innermost loop that are waiting for their target
+struct Loop {
+    /// Number of locals in scope when the loop started
+    locals: usize,
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct Compiler<'a> {
+    function: Function,
+    function_indices: &'a HashMap<Symbol, usize>,
+    /// Locals in slot order
+    locals: Vec<Local>,
+    /// 0 at the top level of the program, where variables are globals
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(name: &str, arity: usize, function_indices: &'a HashMap<Symbol, usize>) -> Self {
+        Self {
+            function: Function {
+                name: name.to_string(),
+                arity,
+                chunk: Default::default(),
+            },
+            function_indices,
+            locals: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+
+    fn emit(&mut self, op: OpCode, location: &SourceLocation) -> usize {
+        self.function.chunk.emit(op, location)
+    }
+
+    /// Point the jump at `index` to the next instruction
+    fn patch(&mut self, index: usize) {
+        let target = self.function.chunk.code.len();
+        self.patch_to(index, target);
+    }
+
+    fn patch_to(&mut self, index: usize, target: usize) {
+        match &mut self.function.chunk.code[index] {
+            OpCode::Jump(to) | OpCode::JumpIfFalse(to) | OpCode::JumpIfTrue(to) => *to = target,
+            other => unreachable!("patching a non-jump instruction {:?}", other),
+        }
+    }
+
+    fn name_constant(&mut self, name: &str) -> usize {
+        self.function.chunk.add_constant(Value::String(name.to_string()))
+    }
+
+    fn resolve_local(&self, name: Symbol) -> Option<usize> {
+        self.locals.iter().rposition(|local| local.name == name)
+    }
+
+    fn begin_scope(&mut self) {
+        self.scope_depth += 1;
+    }
+
+    /// Leave a scope, popping the locals declared in it
+    fn end_scope(&mut self, location: &SourceLocation) {
+        self.scope_depth -= 1;
+        while self.locals.last().is_some_and(|local| local.depth > self.scope_depth) {
+            self.locals.pop();
+            self.emit(OpCode::Pop, location);
+        }
+    }
+
+    fn block(&mut self, statements: &[Stmt], location: &SourceLocation) -> LuxResult<()> {
+        self.begin_scope();
+        for stmt in statements {
+            self.statement(stmt)?;
+        }
+        self.end_scope(location);
+        Ok(())
+    }
+
+    fn statement(&mut self, stmt: &Stmt) -> LuxResult<()> {
+        match stmt {
+            Stmt::Expression { expr, location } => {
+                self.expression(expr)?;
+                self.emit(OpCode::Pop, location);
+            }
+
+            Stmt::VarDecl { name, initializer, location, .. } => {
+                match initializer {
+                    Some(init) => self.expression(init)?,
+                    None => {
+                        self.emit(OpCode::Nil, location);
+                    }
+                }
+                if self.scope_depth == 0 {
+                    let name = self.name_constant(name);
+                    self.emit(OpCode::DefineGlobal(name), location);
+                } else {
+                    // The initializer's value stays on the stack as the local's slot
+                    self.locals.push(Local { name: *name, depth: self.scope_depth });
+                }
+            }
+
+            // Top-level functions are compiled separately by `compile`
+            Stmt::FunctionDecl { location, .. } => {
+                if self.scope_depth > 0 {
+                    return Err(unsupported("Nested function declarations", location));
+                }
+            }
+
+            Stmt::If { condition, then_branch, else_branch, location } => {
+                self.expression(condition)?;
+                let to_else = self.emit(OpCode::JumpIfFalse(0), location);
+                self.emit(OpCode::Pop, location);
+                self.block(then_branch, location)?;
+                let to_end = self.emit(OpCode::Jump(0), location);
+                self.patch(to_else);
+                self.emit(OpCode::Pop, location);
+                if let Some(else_stmts) = else_branch {
+                    self.block(else_stmts, location)?;
+                }
+                self.patch(to_end);
+            }
+
+            Stmt::While { condition, body, location } => {
+                let start = self.function.chunk.code.len();
+                self.expression(condition)?;
+                let exit = self.emit(OpCode::JumpIfFalse(0), location);
+                self.emit(OpCode::Pop, location);
+                self.loop_body(body, location, |compiler| {
+                    compiler.emit(OpCode::Jump(start), location);
+                    Ok(start)
+                })?;
+                self.patch(exit);
+                self.emit(OpCode::Pop, location);
+                self.patch_breaks();
+            }
+
+            Stmt::For { initializer, condition, increment, body, location } => {
+                self.begin_scope();
+                if let Some(init) = initializer {
+                    self.statement(init)?;
+                }
+                let start = self.function.chunk.code.len();
+                let exit = match condition {
+                    Some(condition) => {
+                        self.expression(condition)?;
+                        let exit = self.emit(OpCode::JumpIfFalse(0), location);
+                        self.emit(OpCode::Pop, location);
+                        Some(exit)
+                    }
+                    None => None,
+                };
+                self.loop_body(body, location, |compiler| {
+                    let continue_target = compiler.function.chunk.code.len();
+                    if let Some(increment) = increment {
+                        compiler.expression(increment)?;
+                        compiler.emit(OpCode::Pop, location);
+                    }
+                    compiler.emit(OpCode::Jump(start), location);
+                    Ok(continue_target)
+                })?;
+                if let Some(exit) = exit {
+                    self.patch(exit);
+                    self.emit(OpCode::Pop, location);
+                }
+                self.patch_breaks();
+                self.end_scope(location);
+            }
+
+            Stmt::Return { value, location } => {
+                match value {
+                    Some(value) => self.expression(value)?,
+                    None => {
+                        self.emit(OpCode::Nil, location);
+                    }
+                }
+                self.emit(OpCode::Return, location);
+            }
+
+            Stmt::Break { location } | Stmt::Continue { location } => {
+                let Some(innermost) = self.loops.last() else {
+                    return Err(LuxError::semantic_error("'break' or 'continue' outside of a loop", location.clone()));
+                };
+                // Drop the locals declared inside the loop before jumping out of it
+                for _ in innermost.locals..self.locals.len() {
+                    self.emit(OpCode::Pop, location);
+                }
+                let jump = self.emit(OpCode::Jump(0), location);
+                let innermost = self.loops.last_mut().expect("checked above");
+                if matches!(stmt, Stmt::Break { .. }) {
+                    innermost.breaks.push(jump);
+                } else {
+                    innermost.continues.push(jump);
+                }
+            }
+
+            Stmt::Block { statements, location } => self.block(statements, location)?,
+
+            other => return Err(unsupported("These statements", other.location())),
+        }
+        Ok(())
+    }
+
+    /// Compile a loop body followed by `tail`, which jumps back to the start
+    /// and returns where `continue` should go. Breaks are left pending for
+    /// [`Self::patch_breaks`] once the loop's exit is emitted.
+    fn loop_body(
+        &mut self,
+        body: &[Stmt],
+        location: &SourceLocation,
+        tail: impl FnOnce(&mut Self) -> LuxResult<usize>,
+    ) -> LuxResult<()> {
+        self.loops.push(Loop { locals: self.locals.len(), breaks: Vec::new(), continues: Vec::new() });
+        self.block(body, location)?;
+        let continue_target = tail(self)?;
+        let continues = std::mem::take(&mut self.loops.last_mut().expect("pushed above").continues);
+        for jump in continues {
+            self.patch_to(jump, continue_target);
+        }
+        Ok(())
+    }
+
+    fn patch_breaks(&mut self) {
+        let finished = self.loops.pop().expect("called after loop_body");
+        for jump in finished.breaks {
+            self.patch(jump);
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) -> LuxResult<()> {
+        match expr {
+            Expr::Literal { value, location, .. } => {
+                let op = match value {
+                    Literal::Nil => OpCode::Nil,
+                    Literal::Boolean(true) => OpCode::True,
+                    Literal::Boolean(false) => OpCode::False,
+                    Literal::Integer(n) => OpCode::Constant(self.function.chunk.add_constant(Value::Int(*n))),
+                    Literal::Float(f) => OpCode::Constant(self.function.chunk.add_constant(Value::Float(*f))),
+                    Literal::String(s) => OpCode::Constant(self.function.chunk.add_constant(Value::String(s.clone()))),
+                };
+                self.emit(op, location);
+            }
+
+            Expr::Variable { name, location, .. } => {
+                if let Some(slot) = self.resolve_local(*name) {
+                    self.emit(OpCode::GetLocal(slot), location);
+                } else if self.function_indices.contains_key(name) {
+                    return Err(unsupported("Function values", location));
+                } else {
+                    let name = self.name_constant(name);
+                    self.emit(OpCode::GetGlobal(name), location);
+                }
+            }
+
+            Expr::Binary { left, operator, right, location, .. } => {
+                self.expression(left)?;
+                self.expression(right)?;
+                self.emit(OpCode::Binary(operator.clone()), location);
+            }
+
+            Expr::Unary { operator: operator @ (UnaryOp::Negate | UnaryOp::Not), operand, location, .. } => {
+                self.expression(operand)?;
+                self.emit(OpCode::Unary(operator.clone()), location);
+            }
+
+            // The left operand is the result when it decides the outcome
+            Expr::Logical { left, operator, right, location, .. } => {
+                self.expression(left)?;
+                let short_circuit = match operator {
+                    LogicalOp::And => OpCode::JumpIfFalse(0),
+                    LogicalOp::Or => OpCode::JumpIfTrue(0),
+                };
+                let jump = self.emit(short_circuit, location);
+                self.emit(OpCode::Pop, location);
+                self.expression(right)?;
+                self.patch(jump);
+            }
+
+            Expr::Assign { target, value, location, .. } => {
+                let name = match target.as_ref() {
+                    Expr::Variable { name, .. } => name,
+                    Expr::TableAccess { .. } => return Err(unsupported("Assignments to table fields", location)),
+                    _ => return Err(unsupported("Assignments through pointers", location)),
+                };
+                self.expression(value)?;
+                if let Some(slot) = self.resolve_local(*name) {
+                    self.emit(OpCode::SetLocal(slot), location);
+                } else {
+                    let name = self.name_constant(name);
+                    self.emit(OpCode::SetGlobal(name), location);
+                }
+            }
+
+            Expr::Call { callee, arguments, location, .. } => {
+                let Expr::Variable { name, .. } = callee.as_ref() else {
+                    return Err(unsupported("Calls of computed functions", location));
+                };
+                if self.resolve_local(*name).is_some() {
+                    return Err(unsupported("Calls through local variables", location));
+                }
+                for arg in arguments {
+                    self.expression(arg)?;
+                }
+                let argc = arguments.len();
+                match self.function_indices.get(name) {
+                    Some(&function) => self.emit(OpCode::Call { function, argc }, location),
+                    None => {
+                        let name = self.name_constant(name);
+                        self.emit(OpCode::CallGlobal { name, argc }, location)
+                    }
+                };
+            }
+
+            other => return Err(unsupported("These expressions", other.location())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::ast::BinaryOp;
+    use crate::{Lexer, Parser};
+
+    fn compile_source(source: &str) -> LuxResult<Program> {
+        let tokens = Lexer::new(source, None).tokenize()?;
+        let ast = Parser::new(tokens).parse()?;
+        compile(&ast)
+    }
+
+    #[test]
+    fn test_compile_expression() {
+        let program = compile_source("1 + 2 * x").unwrap();
+        assert_eq!(
+            program.main.chunk.code,
+            vec![
+                OpCode::Constant(0),
+                OpCode::Constant(1),
+                OpCode::GetGlobal(2),
+                OpCode::Binary(BinaryOp::Multiply),
+                OpCode::Binary(BinaryOp::Add),
+                OpCode::Return,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_locals_use_stack_slots() {
+        let program = compile_source("fn f(a: int) -> int { local b := a\nreturn b }").unwrap();
+        assert_eq!(
+            program.functions[0].chunk.code,
+            vec![OpCode::GetLocal(0), OpCode::GetLocal(1), OpCode::Return, OpCode::Nil, OpCode::Return]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_constructs_are_errors() {
+        let err = compile_source("local t := {1, 2}").unwrap_err();
+        assert!(err.to_string().contains("not supported by the bytecode backend"));
+        assert!(compile_source("fn f() { fn g() {} }").is_err());
+    }
+}
@@ -0,0 +1,11 @@
+//! Bytecode compiler module
+//!
+//! This module lowers a checked AST to the stack-based bytecode executed by
+//! the `vm` backend, an alternative to the tree-walking interpreter.
+
+pub mod chunk;
+#[allow(clippy::module_inception)]
+pub mod compiler;
+
+pub use chunk::{Chunk, Function, OpCode, Program};
+pub use compiler::compile;
@@ -3,12 +3,57 @@
 //! This module provides utilities for formatting error messages with
 //! source code context and helpful suggestions.
 
-use super::{LuxError, SourceLocation};
-use colored::Colorize;
+use super::{LuxError, LuxResult, SourceLocation, Span};
+use colored::{ColoredString, Colorize};
+use std::io::IsTerminal;
 
-/// Diagnostic information for displaying errors with context
+/// Color diagnostics only when stderr is a terminal and `NO_COLOR` is unset
+///
+/// Diagnostics are written to stderr, so a redirected stderr gets plain text.
+/// Following <https://no-color.org>, an empty `NO_COLOR` doesn't count.
+pub fn configure_color() {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    colored::control::set_override(!no_color && std::io::stderr().is_terminal());
+}
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// Stops compilation or execution
+    Error,
+    /// Suspicious code that still runs
+    Warning,
+    /// Additional information, such as a hint
+    Note,
+}
+
+impl Severity {
+    /// Color text in this severity's color
+    fn paint(&self, text: &str) -> ColoredString {
+        match self {
+            Self::Error => text.red(),
+            Self::Warning => text.yellow(),
+            Self::Note => text.cyan(),
+        }
+    }
+
+    /// Lowercase name, as used in JSON output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Note => "note",
+        }
+    }
+}
+
+/// Diagnostic information for displaying errors and warnings with context
+#[derive(Debug, Clone)]
 pub struct Diagnostic {
-    error: LuxError,
+    severity: Severity,
+    kind: String,
+    message: String,
+    span: Option<Span>,
     source: Option<String>,
 }
 
@@ -16,7 +61,10 @@ impl Diagnostic {
     /// Create a new diagnostic from an error
     pub fn new(error: LuxError) -> Self {
         Self {
-            error,
+            severity: Severity::Error,
+            kind: error.kind().to_string(),
+            message: error.message().to_string(),
+            span: error.span().cloned(),
             source: None,
         }
     }
@@ -24,35 +72,100 @@ impl Diagnostic {
     /// Create a diagnostic with source code context
     pub fn with_source(error: LuxError, source: &str) -> Self {
         Self {
-            error,
             source: Some(source.to_string()),
+            ..Self::new(error)
+        }
+    }
+
+    /// Create a warning pointing at `location`
+    pub fn warning(message: impl Into<String>, location: SourceLocation) -> Self {
+        Self::at(Severity::Warning, "Warning", message, location)
+    }
+
+    /// Create a note pointing at `location`
+    pub fn note(message: impl Into<String>, location: SourceLocation) -> Self {
+        Self::at(Severity::Note, "Note", message, location)
+    }
+
+    fn at(severity: Severity, kind: &str, message: impl Into<String>, location: SourceLocation) -> Self {
+        Self {
+            severity,
+            kind: kind.to_string(),
+            message: message.into(),
+            span: Some(Span::point(location)),
+            source: None,
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    /// One-line form, e.g. `Warning: Unused variable 'x' at main.lux:3:5`
+    pub fn summary(&self) -> String {
+        match &self.span {
+            Some(span) => format!("{}: {} at {}", self.kind, self.message, span.start),
+            None => format!("{}: {}", self.kind, self.message),
         }
     }
 
+    /// Machine-readable form for editors, e.g.
+    /// `{"kind":"Parse Error","message":"...","file":"main.lux","line":3,"column":5,"severity":"error"}`
+    ///
+    /// `file`, `line` and `column` are null when the diagnostic has no location.
+    pub fn to_json(&self) -> String {
+        let location = self.span.as_ref().map(|span| &span.start);
+        let file = location
+            .and_then(|location| location.filename.as_deref())
+            .map_or_else(|| "null".to_string(), json_string);
+        let (line, column) = location.map_or_else(
+            || ("null".to_string(), "null".to_string()),
+            |location| (location.line.to_string(), location.column.to_string()),
+        );
+        format!(
+            "{{\"kind\":{},\"message\":{},\"file\":{},\"line\":{},\"column\":{},\"severity\":{}}}",
+            json_string(&self.kind),
+            json_string(&self.message),
+            file,
+            line,
+            column,
+            json_string(self.severity.name())
+        )
+    }
+
     /// Format the diagnostic with color and context
     pub fn format(&self) -> String {
         let mut output = String::new();
 
-        // Error header
-        let kind = self.error.kind().red().bold();
+        // Header
+        let kind = self.severity.paint(&self.kind).bold();
         output.push_str(&format!("{}: ", kind));
-        output.push_str(self.error.message());
+        output.push_str(&self.message);
         output.push('\n');
 
         // Location and source context
-        if let Some(location) = self.error.location() {
-            output.push_str(&format!("  {} {}\n", "-->".blue().bold(), location));
+        if let Some(span) = &self.span {
+            output.push_str(&format!("  {} {}\n", "-->".blue().bold(), span.start));
 
             if let Some(ref source) = self.source {
-                output.push_str(&self.format_source_context(source, location));
+                output.push_str(&self.format_source_context(source, span));
             }
         }
 
         output
     }
 
-    /// Format source code context around the error location
-    fn format_source_context(&self, source: &str, location: &SourceLocation) -> String {
+    /// Format source code context around the error span
+    fn format_source_context(&self, source: &str, span: &Span) -> String {
+        let location = &span.start;
         let mut output = String::new();
         let lines: Vec<&str> = source.lines().collect();
 
@@ -81,9 +194,27 @@ impl Diagnostic {
             lines[line_idx]
         ));
 
-        // Show error indicator
-        let indicator_padding = " ".repeat(line_num_width + 2 + location.column - 1);
-        output.push_str(&format!("{}{}\n", indicator_padding, "^".red().bold()));
+        // Show error indicator, underlining the span on the error line.
+        // Spans that continue onto later lines are underlined to the end of this one.
+        let line_width = lines[line_idx].chars().count() + 1;
+        let end_column = if span.end.line > location.line {
+            line_width
+        } else {
+            span.end.column.min(line_width)
+        };
+        let underline_width = end_column.saturating_sub(location.column).max(1);
+        // Copy tabs from the source line so the caret lines up however wide
+        // the terminal draws them
+        let indicator_padding: String = " "
+            .repeat(line_num_width + 3)
+            .chars()
+            .chain(lines[line_idx].chars().take(location.column - 1).map(|c| if c == '\t' { '\t' } else { ' ' }))
+            .collect();
+        output.push_str(&format!(
+            "{}{}\n",
+            indicator_padding,
+            self.severity.paint(&"^".repeat(underline_width)).bold()
+        ));
 
         // Show next line if available
         if line_idx + 1 < lines.len() {
@@ -98,15 +229,112 @@ impl Diagnostic {
     }
 }
 
+/// Quote `text` as a JSON string
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 impl std::fmt::Display for Diagnostic {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.format())
     }
 }
 
+/// Collects the diagnostics produced while compiling a program
+///
+/// Warnings and notes are recorded here instead of aborting the pipeline,
+/// so a program with warnings still runs.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    items: Vec<Diagnostic>,
+    /// Whether `check` fails on warnings
+    deny_warnings: bool,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Treat warnings as errors: [`Diagnostics::check`] fails on the first one
+    pub fn deny_warnings(mut self) -> Self {
+        self.deny_warnings = true;
+        self
+    }
+
+    /// Fail with the first recorded warning if warnings are denied
+    pub fn check(&self) -> LuxResult<()> {
+        let Some(warning) = self.warnings().next().filter(|_| self.deny_warnings) else {
+            return Ok(());
+        };
+        let span = warning.span().cloned().unwrap_or_else(|| Span::point(SourceLocation::at(1, 1)));
+        Err(LuxError::semantic_error(
+            format!("{} (warnings are treated as errors)", warning.message()),
+            span.start.clone(),
+        )
+        .with_span(span))
+    }
+
+    /// Record a diagnostic
+    pub fn push(&mut self, diagnostic: Diagnostic) {
+        self.items.push(diagnostic);
+    }
+
+    /// Record a warning pointing at `location`
+    pub fn warn(&mut self, message: impl Into<String>, location: SourceLocation) {
+        self.push(Diagnostic::warning(message, location));
+    }
+
+    /// All recorded diagnostics, in the order they were reported
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.items.iter()
+    }
+
+    /// Recorded diagnostics with `Severity::Warning`
+    pub fn warnings(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.items.iter().filter(|d| d.severity == Severity::Warning)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Print every recorded diagnostic to stderr, one per line
+    pub fn report(&self) {
+        for diagnostic in &self.items {
+            eprintln!("{}", diagnostic.summary());
+        }
+    }
+
+    /// Every recorded diagnostic as a JSON array (see [`Diagnostic::to_json`])
+    pub fn to_json(&self) -> String {
+        let items: Vec<String> = self.items.iter().map(Diagnostic::to_json).collect();
+        format!("[{}]", items.join(","))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::SourceLocation;
 
     #[test]
     fn test_diagnostic_without_source() {
@@ -130,5 +358,97 @@ mod tests {
         assert!(formatted.contains("Lexer Error"));
         assert!(formatted.contains("let y = @"));
     }
+
+    #[test]
+    fn test_diagnostic_underlines_binary_expression() {
+        use crate::{Lexer, Parser};
+        use crate::types::TypeChecker;
+
+        let source = "local a := 1\nlocal b := \"x\"\nlocal c := a + b";
+        let tokens = Lexer::new(source, None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        let err = TypeChecker::new().check(&ast).unwrap_err();
+
+        let span = err.span().unwrap();
+        assert_eq!((span.start.line, span.start.column), (3, 12));
+        assert_eq!((span.end.line, span.end.column), (3, 17));
+
+        let formatted = Diagnostic::with_source(err, source).format();
+        assert!(formatted.contains("^^^^^"));
+        assert!(!formatted.contains("^^^^^^"));
+    }
+
+    #[test]
+    fn test_warning_diagnostic() {
+        let loc = SourceLocation::new(3, 5, Some("main.lux".to_string()));
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.warn("Unused variable 'x'", loc.clone());
+        diagnostics.push(Diagnostic::note("declared here", loc));
+
+        assert_eq!(diagnostics.len(), 2);
+        let warning = diagnostics.warnings().next().unwrap();
+        assert_eq!(warning.severity(), Severity::Warning);
+        assert_eq!(warning.summary(), "Warning: Unused variable 'x' at main.lux:3:5");
+        assert!(warning.format().contains("Warning"));
+    }
+
+    #[test]
+    fn test_caret_under_tab_indented_code() {
+        colored::control::set_override(false);
+        let source = "fn f() {\n\t\tlocal x := @\n}";
+        let err = LuxError::lexer_error("Unexpected character '@'", SourceLocation::at(2, 14));
+        let formatted = Diagnostic::with_source(err, source).format();
+
+        assert!(formatted.contains("\n    \t\t           ^\n"), "{:?}", formatted);
+    }
+
+    #[test]
+    fn test_caret_after_multibyte_characters() {
+        use crate::Lexer;
+
+        colored::control::set_override(false);
+        let source = "local s := \"h\u{e9}llo\" @";
+        let err = Lexer::new(source, None).tokenize().unwrap_err();
+        assert_eq!(err.span().unwrap().start.column, 20);
+
+        let formatted = Diagnostic::with_source(err, source).format();
+        let source_line = formatted.lines().find(|line| line.contains("llo")).unwrap();
+        let caret_line = formatted.lines().find(|line| line.trim() == "^").unwrap();
+        let caret = caret_line.chars().count() - 1;
+        assert_eq!(source_line.chars().nth(caret), Some('@'), "{:?}", formatted);
+    }
+
+    #[test]
+    fn test_format_without_color() {
+        colored::control::set_override(false);
+        let source = "local x := 1\nlocal y := x +\n";
+        let err = LuxError::parse_error("Expected expression", SourceLocation::at(2, 13));
+        let formatted = Diagnostic::with_source(err, source).format();
+        let warning = Diagnostic::warning("Unused variable 'y'", SourceLocation::at(2, 7)).format();
+
+        assert!(formatted.contains("Parse Error: Expected expression"));
+        assert!(formatted.contains("^"));
+        assert!(!formatted.contains('\x1b'), "{:?}", formatted);
+        assert!(!warning.contains('\x1b'), "{:?}", warning);
+    }
+
+    #[test]
+    fn test_parse_error_json() {
+        use crate::{Lexer, Parser};
+
+        let tokens = Lexer::new("local x = (1 + \"a\"", Some("main.lux")).tokenize().unwrap();
+        let err = Parser::new(tokens).parse().unwrap_err();
+        let mut diagnostics = Diagnostics::new();
+        diagnostics.warn("Unused variable 'x'", SourceLocation::new(1, 7, Some("main.lux".to_string())));
+        diagnostics.push(Diagnostic::new(err));
+
+        assert_eq!(
+            diagnostics.to_json(),
+            "[{\"kind\":\"Warning\",\"message\":\"Unused variable 'x'\",\"file\":\"main.lux\",\"line\":1,\"column\":7,\"severity\":\"warning\"},\
+             {\"kind\":\"Parse Error\",\"message\":\"Expected ')' after expression\",\"file\":\"main.lux\",\"line\":1,\"column\":19,\"severity\":\"error\"}]"
+        );
+        let internal = Diagnostic::new(LuxError::internal_error("tab\there"));
+        assert!(internal.to_json().contains("\"message\":\"tab\\there\",\"file\":null,\"line\":null,\"column\":null"));
+    }
 }
 
@@ -7,7 +7,7 @@ use std::fmt;
 
 pub mod diagnostic;
 
-pub use diagnostic::Diagnostic;
+pub use diagnostic::{configure_color, Diagnostic, Diagnostics, Severity};
 
 /// Result type alias for Lux operations
 pub type LuxResult<T> = Result<T, LuxError>;
@@ -17,7 +17,7 @@ pub type LuxResult<T> = Result<T, LuxError>;
 pub struct SourceLocation {
     /// Line number (1-based)
     pub line: usize,
-    /// Column number (1-based)
+    /// Column number (1-based), counted in characters rather than bytes
     pub column: usize,
     /// Optional filename
     pub filename: Option<String>,
@@ -49,33 +49,75 @@ impl fmt::Display for SourceLocation {
     }
 }
 
+/// A range of source code, from `start` up to (but not including) `end`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// First character covered by the span
+    pub start: SourceLocation,
+    /// Position just past the last character covered by the span
+    pub end: SourceLocation,
+}
+
+impl Span {
+    /// Create a new span
+    pub fn new(start: SourceLocation, end: SourceLocation) -> Self {
+        Self { start, end }
+    }
+
+    /// Create a span covering the single character at `location`
+    pub fn point(location: SourceLocation) -> Self {
+        let end = SourceLocation::new(location.line, location.column + 1, location.filename.clone());
+        Self::new(location, end)
+    }
+}
+
+/// A function call that was active when a runtime error occurred
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// Name of the called function
+    pub function: String,
+    /// Where the function was defined
+    pub definition: SourceLocation,
+    /// Where the function was called from
+    pub call_site: SourceLocation,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (defined at {}, called at {})", self.function, self.definition, self.call_site)
+    }
+}
+
 /// Main error type for the Lux language
 #[derive(Debug, Clone)]
 pub enum LuxError {
     /// Lexical analysis error
     LexerError {
         message: String,
-        location: SourceLocation,
+        span: Span,
     },
     /// Parsing error
     ParseError {
         message: String,
-        location: SourceLocation,
+        span: Span,
     },
     /// Type checking error
     TypeError {
         message: String,
-        location: SourceLocation,
+        span: Span,
     },
     /// Semantic analysis error
     SemanticError {
         message: String,
-        location: SourceLocation,
+        span: Span,
     },
     /// Runtime error
     RuntimeError {
         message: String,
-        location: Option<SourceLocation>,
+        span: Option<Span>,
+        /// Calls active when the error occurred, innermost first
+        /// (boxed to keep `LuxError` small)
+        backtrace: Box<[StackFrame]>,
     },
     /// Internal compiler error (should not happen in normal operation)
     InternalError {
@@ -88,7 +130,7 @@ impl LuxError {
     pub fn lexer_error(message: impl Into<String>, location: SourceLocation) -> Self {
         Self::LexerError {
             message: message.into(),
-            location,
+            span: Span::point(location),
         }
     }
 
@@ -96,7 +138,7 @@ impl LuxError {
     pub fn parse_error(message: impl Into<String>, location: SourceLocation) -> Self {
         Self::ParseError {
             message: message.into(),
-            location,
+            span: Span::point(location),
         }
     }
 
@@ -104,7 +146,7 @@ impl LuxError {
     pub fn type_error(message: impl Into<String>, location: SourceLocation) -> Self {
         Self::TypeError {
             message: message.into(),
-            location,
+            span: Span::point(location),
         }
     }
 
@@ -112,7 +154,7 @@ impl LuxError {
     pub fn semantic_error(message: impl Into<String>, location: SourceLocation) -> Self {
         Self::SemanticError {
             message: message.into(),
-            location,
+            span: Span::point(location),
         }
     }
 
@@ -120,7 +162,8 @@ impl LuxError {
     pub fn runtime_error(message: impl Into<String>, location: Option<SourceLocation>) -> Self {
         Self::RuntimeError {
             message: message.into(),
-            location,
+            span: location.map(Span::point),
+            backtrace: Box::new([]),
         }
     }
 
@@ -157,24 +200,75 @@ impl LuxError {
 
     /// Get the source location if available
     pub fn location(&self) -> Option<&SourceLocation> {
+        self.span().map(|span| &span.start)
+    }
+
+    /// Get the source span if available
+    pub fn span(&self) -> Option<&Span> {
         match self {
-            Self::LexerError { location, .. }
-            | Self::ParseError { location, .. }
-            | Self::TypeError { location, .. }
-            | Self::SemanticError { location, .. } => Some(location),
-            Self::RuntimeError { location, .. } => location.as_ref(),
+            Self::LexerError { span, .. }
+            | Self::ParseError { span, .. }
+            | Self::TypeError { span, .. }
+            | Self::SemanticError { span, .. } => Some(span),
+            Self::RuntimeError { span, .. } => span.as_ref(),
             Self::InternalError { .. } => None,
         }
     }
+
+    /// Get the call stack recorded for a runtime error, innermost call first
+    pub fn backtrace(&self) -> &[StackFrame] {
+        match self {
+            Self::RuntimeError { backtrace, .. } => backtrace,
+            _ => &[],
+        }
+    }
+
+    /// Record the call stack of a runtime error, unless one was already
+    /// recorded closer to where the error happened
+    pub fn with_backtrace(mut self, frames: impl FnOnce() -> Vec<StackFrame>) -> Self {
+        if let Self::RuntimeError { backtrace, .. } = &mut self {
+            if backtrace.is_empty() {
+                *backtrace = frames().into_boxed_slice();
+            }
+        }
+        self
+    }
+
+    /// Widen the error to cover `new_span` instead of a single point
+    pub fn with_span(mut self, new_span: Span) -> Self {
+        match &mut self {
+            Self::LexerError { span, .. }
+            | Self::ParseError { span, .. }
+            | Self::TypeError { span, .. }
+            | Self::SemanticError { span, .. } => *span = new_span,
+            Self::RuntimeError { span, .. } => *span = Some(new_span),
+            Self::InternalError { .. } => {}
+        }
+        self
+    }
 }
 
+/// Backtraces longer than this (e.g. from runaway recursion) are cut short
+const MAX_BACKTRACE_FRAMES: usize = 16;
+
 impl fmt::Display for LuxError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(location) = self.location() {
-            write!(f, "{}: {} at {}", self.kind(), self.message(), location)
+            write!(f, "{}: {} at {}", self.kind(), self.message(), location)?;
         } else {
-            write!(f, "{}: {}", self.kind(), self.message())
+            write!(f, "{}: {}", self.kind(), self.message())?;
         }
+        let backtrace = self.backtrace();
+        if !backtrace.is_empty() {
+            write!(f, "\nBacktrace (most recent call first):")?;
+            for frame in backtrace.iter().take(MAX_BACKTRACE_FRAMES) {
+                write!(f, "\n  in {}", frame)?;
+            }
+            if backtrace.len() > MAX_BACKTRACE_FRAMES {
+                write!(f, "\n  ... {} more", backtrace.len() - MAX_BACKTRACE_FRAMES)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -210,5 +304,34 @@ mod tests {
         
         assert_eq!(err.to_string(), "Parse Error: expected ';' at 5:10");
     }
+
+    #[test]
+    fn test_runtime_error_backtrace_display() {
+        let frame = StackFrame {
+            function: "inner".to_string(),
+            definition: SourceLocation::at(1, 1),
+            call_site: SourceLocation::at(5, 3),
+        };
+        let err = LuxError::runtime_error("boom", Some(SourceLocation::at(2, 5)))
+            .with_backtrace(|| vec![frame.clone()])
+            .with_backtrace(Vec::new);
+
+        assert_eq!(err.backtrace(), std::slice::from_ref(&frame));
+        assert_eq!(
+            err.to_string(),
+            "Runtime Error: boom at 2:5\nBacktrace (most recent call first):\n  in inner (defined at 1:1, called at 5:3)"
+        );
+    }
+
+    #[test]
+    fn test_error_with_span() {
+        let start = SourceLocation::at(3, 4);
+        let end = SourceLocation::at(3, 9);
+        let err = LuxError::type_error("bad operands", start.clone())
+            .with_span(Span::new(start.clone(), end.clone()));
+
+        assert_eq!(err.location(), Some(&start));
+        assert_eq!(err.span().map(|s| &s.end), Some(&end));
+    }
 }
 
@@ -4,7 +4,9 @@
 
 pub mod token;
 pub mod scanner;
+pub mod symbol;
 
 pub use token::{Token, TokenType, Keyword, Literal};
 pub use scanner::Lexer;
+pub use symbol::Symbol;
 
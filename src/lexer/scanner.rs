@@ -2,49 +2,153 @@
 //!
 //! This module implements lexical analysis, converting source code into tokens.
 
+use std::borrow::Cow;
+use std::io::{self, Read};
 use crate::error::{LuxError, LuxResult, SourceLocation};
+use super::symbol::Symbol;
 use super::token::{Token, TokenType, Keyword, Literal};
 
+/// Bytes read at a time by [`Lexer::from_reader`]
+const READ_CHUNK: usize = 64 * 1024;
+
 /// Lexer for Lux source code
 pub struct Lexer {
     source: Vec<char>,
     tokens: Vec<Token>,
     start: usize,
+    /// Line and column of `start`; a string token can end on a later line
+    start_line: usize,
+    start_column: usize,
     current: usize,
     line: usize,
     column: usize,
     filename: Option<String>,
+    /// Emit a `Newline` token at each line break that follows a token
+    emit_newlines: bool,
+    /// Text of the identifier being scanned, reused so that scanning a
+    /// name that is already interned doesn't allocate
+    name: String,
 }
 
 impl Lexer {
     /// Create a new lexer
     pub fn new(source: &str, filename: Option<&str>) -> Self {
+        Self::from_chars(source.chars().collect(), filename)
+    }
+
+    /// Create a lexer over UTF-8 source read from `reader`
+    ///
+    /// The input is decoded a chunk at a time, so a large file is never held
+    /// as a `String` alongside the decoded characters. Fails with
+    /// `InvalidData` if the input is not valid UTF-8.
+    pub fn from_reader(mut reader: impl Read, filename: Option<&str>) -> io::Result<Self> {
+        let mut source = Vec::new();
+        let mut buffer = vec![0; READ_CHUNK];
+        // Bytes of a character split across two reads, kept at the front of `buffer`
+        let mut pending = 0;
+        loop {
+            let read = match reader.read(&mut buffer[pending..]) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let filled = pending + read;
+            let valid = match std::str::from_utf8(&buffer[..filled]) {
+                Ok(_) => filled,
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+            };
+            let text = std::str::from_utf8(&buffer[..valid]).expect("prefix was validated");
+            source.extend(text.chars());
+            buffer.copy_within(valid..filled, 0);
+            pending = filled - valid;
+        }
+        if pending > 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "source ends in the middle of a UTF-8 character"));
+        }
+        Ok(Self::from_chars(source, filename))
+    }
+
+    fn from_chars(source: Vec<char>, filename: Option<&str>) -> Self {
         Self {
-            source: source.chars().collect(),
+            source,
             tokens: Vec::new(),
             start: 0,
+            start_line: 1,
+            start_column: 1,
             current: 0,
             line: 1,
             column: 1,
             filename: filename.map(|s| s.to_string()),
+            emit_newlines: false,
+            name: String::new(),
         }
     }
 
+    /// Emit `Newline` tokens so the parser can tell where lines end
+    ///
+    /// Blank lines and comment-only lines produce no extra tokens: at most
+    /// one `Newline` separates two other tokens.
+    pub fn with_newlines(mut self) -> Self {
+        self.emit_newlines = true;
+        self
+    }
+
     /// Tokenize the source code
+    ///
+    /// The tokens are moved out rather than copied, so the lexer holds none
+    /// of them afterwards.
     pub fn tokenize(&mut self) -> LuxResult<Vec<Token>> {
         while !self.is_at_end() {
-            self.start = self.current;
+            self.begin_token();
             self.scan_token()?;
         }
+        Ok(self.finish())
+    }
 
-        // Add EOF token
+    /// Tokenize the source code, reporting every error instead of stopping
+    /// at the first
+    ///
+    /// After an invalid character or an unterminated construct, scanning
+    /// resumes at the next whitespace, so later mistakes are found too.
+    pub fn tokenize_all(&mut self) -> Result<Vec<Token>, Vec<LuxError>> {
+        let mut errors = Vec::new();
+        while !self.is_at_end() {
+            self.begin_token();
+            if let Err(error) = self.scan_token() {
+                errors.push(error);
+                self.skip_to_whitespace();
+            }
+        }
+        let tokens = self.finish();
+        if errors.is_empty() { Ok(tokens) } else { Err(errors) }
+    }
+
+    /// Mark the current position as the start of the next token
+    fn begin_token(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column;
+    }
+
+    /// Add the EOF token and move the tokens out
+    fn finish(&mut self) -> Vec<Token> {
         self.tokens.push(Token::new(
             TokenType::Eof,
             String::new(),
             self.current_location(),
+            self.current_location(),
         ));
+        std::mem::take(&mut self.tokens)
+    }
 
-        Ok(self.tokens.clone())
+    /// Skip to the next whitespace character, leaving it to be scanned so
+    /// line breaks are still counted
+    fn skip_to_whitespace(&mut self) {
+        while !self.is_at_end() && !self.peek().is_whitespace() {
+            self.advance();
+        }
     }
 
     /// Scan a single token
@@ -57,10 +161,15 @@ impl Lexer {
 
             // Newline
             '\n' => {
+                let follows_token = self
+                    .tokens
+                    .last()
+                    .is_some_and(|token| token.token_type != TokenType::Newline);
+                if self.emit_newlines && follows_token {
+                    self.add_token(TokenType::Newline)?;
+                }
                 self.line += 1;
                 self.column = 1;
-                // Optionally emit newline tokens for statement separation
-                // self.add_token(TokenType::Newline);
                 Ok(())
             }
 
@@ -77,8 +186,10 @@ impl Lexer {
             '+' => self.add_token(TokenType::Plus),
             '*' => self.add_token(TokenType::Star),
             '%' => self.add_token(TokenType::Percent),
+            '^' => self.add_token(TokenType::Caret),
             '#' => self.add_token(TokenType::Hash),
             '&' => self.add_token(TokenType::Ampersand),
+            '?' => self.add_token(TokenType::Question),
 
             // Two-character tokens
             '-' => {
@@ -101,7 +212,7 @@ impl Lexer {
                 if self.match_char('=') {
                     self.add_token(TokenType::NotEqual)
                 } else {
-                    Err(self.error("Unexpected character '!'. Did you mean '!='?"))
+                    Err(self.error_at_start("Unexpected character '!'. Did you mean '!='?"))
                 }
             }
 
@@ -129,6 +240,14 @@ impl Lexer {
                 }
             }
 
+            '~' => {
+                if self.match_char('/') {
+                    self.add_token(TokenType::TildeSlash)
+                } else {
+                    Err(self.error_at_start("Unexpected character '~'. Did you mean '~/'?"))
+                }
+            }
+
             // Comments
             '/' => {
                 if self.match_char('/') {
@@ -155,7 +274,7 @@ impl Lexer {
             c if c.is_alphabetic() || c == '_' => self.scan_identifier(),
 
             // Unexpected character
-            _ => Err(self.error(&format!("Unexpected character '{}'", c))),
+            _ => Err(self.error_at_start(&format!("Unexpected character '{}'", c))),
         }
     }
 
@@ -164,11 +283,6 @@ impl Lexer {
         let mut value = String::new();
 
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-                self.column = 1;
-            }
-
             // Handle escape sequences
             if self.peek() == '\\' {
                 self.advance(); // consume backslash
@@ -182,7 +296,7 @@ impl Lexer {
                     _ => return Err(self.error(&format!("Invalid escape sequence '\\{}'", escaped))),
                 }
             } else {
-                value.push(self.advance());
+                value.push(self.advance_counting_lines());
             }
         }
 
@@ -232,13 +346,14 @@ impl Lexer {
             self.advance();
         }
 
-        let lexeme: String = self.source[self.start..self.current].iter().collect();
+        self.name.clear();
+        self.name.extend(&self.source[self.start..self.current]);
 
         // Check if it's a keyword
-        let token_type = if let Some(keyword) = Keyword::from_str(&lexeme) {
+        let token_type = if let Some(keyword) = Keyword::from_str(&self.name) {
             TokenType::Keyword(keyword)
         } else {
-            TokenType::Identifier
+            TokenType::Identifier(Symbol::intern(&self.name))
         };
 
         self.add_token(token_type)
@@ -258,11 +373,7 @@ impl Lexer {
                 self.advance();
                 depth -= 1;
             } else {
-                if self.peek() == '\n' {
-                    self.line += 1;
-                    self.column = 1;
-                }
-                self.advance();
+                self.advance_counting_lines();
             }
         }
 
@@ -275,13 +386,17 @@ impl Lexer {
 
     /// Add a token to the token list
     fn add_token(&mut self, token_type: TokenType) -> LuxResult<()> {
-        let lexeme: String = self.source[self.start..self.current].iter().collect();
-        let location = SourceLocation::new(
-            self.line,
-            self.column - (self.current - self.start),
-            self.filename.clone(),
-        );
-        self.tokens.push(Token::new(token_type, lexeme, location));
+        let lexeme = match &token_type {
+            TokenType::Identifier(name) => Cow::Borrowed(name.as_str()),
+            TokenType::Keyword(keyword) => Cow::Borrowed(keyword.as_str()),
+            fixed => match fixed.text() {
+                Some(text) => Cow::Borrowed(text),
+                None => Cow::Owned(self.source[self.start..self.current].iter().collect()),
+            },
+        };
+        let location = self.start_location();
+        let end = self.current_location();
+        self.tokens.push(Token::new(token_type, lexeme, location, end));
         Ok(())
     }
 
@@ -293,6 +408,17 @@ impl Lexer {
         c
     }
 
+    /// Consume the next character inside a token or comment that may span
+    /// lines, so that the character after a line break is at column 1
+    fn advance_counting_lines(&mut self) -> char {
+        let c = self.advance();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        }
+        c
+    }
+
     /// Check if the next character matches and consume it if so
     fn match_char(&mut self, expected: char) -> bool {
         if self.is_at_end() || self.source[self.current] != expected {
@@ -332,10 +458,20 @@ impl Lexer {
         SourceLocation::new(self.line, self.column, self.filename.clone())
     }
 
+    /// Get the location of the start of the current token
+    fn start_location(&self) -> SourceLocation {
+        SourceLocation::new(self.start_line, self.start_column, self.filename.clone())
+    }
+
     /// Create an error at the current location
     fn error(&self, message: &str) -> LuxError {
         LuxError::lexer_error(message, self.current_location())
     }
+
+    /// Create an error pointing at the start of the current token
+    fn error_at_start(&self, message: &str) -> LuxError {
+        LuxError::lexer_error(message, self.start_location())
+    }
 }
 
 #[cfg(test)]
@@ -375,13 +511,14 @@ mod tests {
 
     #[test]
     fn test_two_character_tokens() {
-        let tokens = tokenize_source("== != <= >= := ->").unwrap();
+        let tokens = tokenize_source("== != <= >= := -> ~/").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Equal);
         assert_eq!(tokens[1].token_type, TokenType::NotEqual);
         assert_eq!(tokens[2].token_type, TokenType::LessEqual);
         assert_eq!(tokens[3].token_type, TokenType::GreaterEqual);
         assert_eq!(tokens[4].token_type, TokenType::ColonAssign);
         assert_eq!(tokens[5].token_type, TokenType::Arrow);
+        assert_eq!(tokens[6].token_type, TokenType::TildeSlash);
     }
 
     #[test]
@@ -402,9 +539,9 @@ mod tests {
         let tokens = tokenize_source("table setmetatable getmetatable").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Keyword(Keyword::Table));
         // setmetatable and getmetatable are now regular identifiers, not keywords
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("setmetatable")));
         assert_eq!(tokens[1].lexeme, "setmetatable");
-        assert_eq!(tokens[2].token_type, TokenType::Identifier);
+        assert_eq!(tokens[2].token_type, TokenType::Identifier(Symbol::intern("getmetatable")));
         assert_eq!(tokens[2].lexeme, "getmetatable");
     }
 
@@ -419,13 +556,13 @@ mod tests {
     #[test]
     fn test_identifiers() {
         let tokens = tokenize_source("foo bar_baz _private myVar123").unwrap();
-        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(tokens[0].token_type, TokenType::Identifier(Symbol::intern("foo")));
         assert_eq!(tokens[0].lexeme, "foo");
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("bar_baz")));
         assert_eq!(tokens[1].lexeme, "bar_baz");
-        assert_eq!(tokens[2].token_type, TokenType::Identifier);
+        assert_eq!(tokens[2].token_type, TokenType::Identifier(Symbol::intern("_private")));
         assert_eq!(tokens[2].lexeme, "_private");
-        assert_eq!(tokens[3].token_type, TokenType::Identifier);
+        assert_eq!(tokens[3].token_type, TokenType::Identifier(Symbol::intern("myVar123")));
         assert_eq!(tokens[3].lexeme, "myVar123");
     }
 
@@ -438,6 +575,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_float_literals() {
         let tokens = tokenize_source("3.14 0.5 123.456").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Literal(Literal::Float(3.14)));
@@ -465,7 +603,7 @@ mod tests {
     fn test_single_line_comment() {
         let tokens = tokenize_source("local x = 42 // this is a comment\nlocal y = 10").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Keyword(Keyword::Local));
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("x")));
         assert_eq!(tokens[2].token_type, TokenType::Assign);
         assert_eq!(tokens[3].token_type, TokenType::Literal(Literal::Integer(42)));
         assert_eq!(tokens[4].token_type, TokenType::Keyword(Keyword::Local));
@@ -475,7 +613,7 @@ mod tests {
     fn test_multiline_comment() {
         let tokens = tokenize_source("local x /* comment */ = 42").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Keyword(Keyword::Local));
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("x")));
         assert_eq!(tokens[2].token_type, TokenType::Assign);
         assert_eq!(tokens[3].token_type, TokenType::Literal(Literal::Integer(42)));
     }
@@ -484,7 +622,7 @@ mod tests {
     fn test_nested_multiline_comment() {
         let tokens = tokenize_source("local x /* outer /* inner */ outer */ = 42").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Keyword(Keyword::Local));
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("x")));
         assert_eq!(tokens[2].token_type, TokenType::Assign);
         assert_eq!(tokens[3].token_type, TokenType::Literal(Literal::Integer(42)));
     }
@@ -493,7 +631,7 @@ mod tests {
     fn test_complete_statement() {
         let tokens = tokenize_source("local x: int = 42").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Keyword(Keyword::Local));
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("x")));
         assert_eq!(tokens[1].lexeme, "x");
         assert_eq!(tokens[2].token_type, TokenType::Colon);
         assert_eq!(tokens[3].token_type, TokenType::Keyword(Keyword::Int));
@@ -505,18 +643,18 @@ mod tests {
     fn test_hash_operator() {
         let tokens = tokenize_source("#myTable").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Hash);
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("myTable")));
         assert_eq!(tokens[1].lexeme, "myTable");
     }
 
     #[test]
     fn test_metamethod_identifiers() {
         let tokens = tokenize_source("__index __add __call").unwrap();
-        assert_eq!(tokens[0].token_type, TokenType::Identifier);
+        assert_eq!(tokens[0].token_type, TokenType::Identifier(Symbol::intern("__index")));
         assert_eq!(tokens[0].lexeme, "__index");
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("__add")));
         assert_eq!(tokens[1].lexeme, "__add");
-        assert_eq!(tokens[2].token_type, TokenType::Identifier);
+        assert_eq!(tokens[2].token_type, TokenType::Identifier(Symbol::intern("__call")));
         assert_eq!(tokens[2].lexeme, "__call");
     }
 
@@ -524,7 +662,7 @@ mod tests {
     fn test_function_declaration() {
         let tokens = tokenize_source("fn add(a: int, b: int) -> int { return a + b }").unwrap();
         assert_eq!(tokens[0].token_type, TokenType::Keyword(Keyword::Fn));
-        assert_eq!(tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("add")));
         assert_eq!(tokens[1].lexeme, "add");
         assert_eq!(tokens[2].token_type, TokenType::LeftParen);
     }
@@ -547,11 +685,135 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_newline_tokens_are_opt_in() {
+        let source = "x\n\n// comment\n(1)\n";
+        let types = |tokens: Vec<Token>| tokens.into_iter().map(|t| t.token_type).collect::<Vec<_>>();
+        assert!(!types(tokenize_source(source).unwrap()).contains(&TokenType::Newline));
+
+        let tokens = Lexer::new(source, None).with_newlines().tokenize().unwrap();
+        assert_eq!(tokens[1].location.line, 1);
+        assert_eq!(
+            types(tokens),
+            vec![
+                TokenType::Identifier(Symbol::intern("x")),
+                TokenType::Newline,
+                TokenType::LeftParen,
+                TokenType::Literal(Literal::Integer(1)),
+                TokenType::RightParen,
+                TokenType::Newline,
+                TokenType::Eof,
+            ]
+        );
+    }
+
     #[test]
     fn test_source_location() {
         let tokens = tokenize_source("let\nx").unwrap();
         assert_eq!(tokens[0].location.line, 1);
         assert_eq!(tokens[1].location.line, 2);
     }
+
+    #[test]
+    fn test_token_end_location() {
+        let tokens = tokenize_source("local total := 42").unwrap();
+        let span = tokens[1].span();
+        assert_eq!(span.start.column, 7);
+        assert_eq!(span.end.column, 12);
+        assert_eq!(tokens[2].end.column, 15);
+    }
+
+    #[test]
+    fn test_tokenize_moves_tokens_out() {
+        let source = "local x := 1\n".repeat(1000);
+        let mut lexer = Lexer::new(&source, None);
+        let tokens = lexer.tokenize().unwrap();
+
+        assert_eq!(tokens, tokenize_source(&source).unwrap());
+        assert_eq!(tokens.len(), 4001);
+        // A clone would have left the original behind
+        assert!(lexer.tokens.is_empty());
+    }
+
+    #[test]
+    fn test_locations_after_multiline_tokens() {
+        let tokens = tokenize_source("x := \"one\ntwo\" y\n/* a\nb */ z").unwrap();
+        let positions: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.location.line, token.location.column))
+            .collect();
+        // The string starts on line 1 and ends on line 2, where `y` follows it
+        assert_eq!(positions, vec![(1, 1), (1, 3), (1, 6), (2, 6), (4, 6), (4, 7)]);
+        assert_eq!((tokens[2].end.line, tokens[2].end.column), (2, 5));
+    }
+
+    #[test]
+    fn test_tokenize_all_reports_every_error() {
+        let source = "local a := 1 @\nlocal b := $x + 2\nprint(\"unterminated";
+        let errors = Lexer::new(source, None).tokenize_all().unwrap_err();
+        let reported: Vec<(String, usize, usize)> = errors
+            .iter()
+            .map(|error| {
+                let location = error.location().expect("lexer errors have a location");
+                (error.message().to_string(), location.line, location.column)
+            })
+            .collect();
+        assert_eq!(
+            reported,
+            vec![
+                ("Unexpected character '@'".to_string(), 1, 14),
+                ("Unexpected character '$'".to_string(), 2, 12),
+                ("Unterminated string".to_string(), 3, 20),
+            ]
+        );
+
+        // Without errors it matches tokenize
+        let tokens = Lexer::new("local a := 1", None).tokenize_all().unwrap();
+        assert_eq!(tokens, tokenize_source("local a := 1").unwrap());
+    }
+
+    /// Hands out at most `step` bytes per read, splitting multibyte characters
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        step: usize,
+    }
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.step.min(buf.len()).min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_from_reader_matches_str() {
+        let source = "local caf\u{e9} := \"\u{1f600} \u{4e16}\u{754c}\"\n-- comment \u{e9}\nprint(caf\u{e9} + \"!\")\n";
+        let expected = Lexer::new(source, Some("a.lux")).tokenize().unwrap();
+        for step in [1, 2, 3, 5, 64] {
+            let reader = Trickle { bytes: source.as_bytes(), step };
+            let tokens = Lexer::from_reader(reader, Some("a.lux")).unwrap().tokenize().unwrap();
+            assert_eq!(tokens, expected, "step {}", step);
+        }
+
+        let invalid = Lexer::from_reader(&b"local x := \"\xff\""[..], None);
+        assert_eq!(invalid.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let truncated = Lexer::from_reader(&"\u{e9}".as_bytes()[..1], None);
+        assert_eq!(truncated.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_from_reader_large_input() {
+        let line = "local value := compute(12, 3.5) + \"caf\u{e9}\" // trailing comment\n";
+        let lines = 4 * 1024 * 1024 / line.len();
+        let source = line.repeat(lines);
+
+        let tokens = Lexer::from_reader(source.as_bytes(), None).unwrap().tokenize().unwrap();
+        // 11 tokens per line, plus Eof
+        assert_eq!(tokens.len(), lines * 11 + 1);
+        let last = &tokens[tokens.len() - 2];
+        assert_eq!((last.location.line, last.location.column), (lines, 35));
+    }
 }
 
@@ -0,0 +1,216 @@
+//! Interned identifiers
+//!
+//! Variable and function names are stored as [`Symbol`]s: small copyable
+//! handles into a process-wide table of names. Copying a symbol doesn't
+//! allocate, and comparing or hashing one is as cheap as for a `u32`, which
+//! keeps variable lookups in the interpreter's environment fast.
+//!
+//! The lexer interns identifiers as it scans them, so a name that appears
+//! many times in a program is allocated once.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{LazyLock, OnceLock, RwLock};
+
+/// An interned identifier
+///
+/// Equal names always intern to the same symbol. Symbols deref to the name
+/// they stand for, so they can be used wherever a `&str` is expected.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Slots in the first chunk of [`NAMES`]; each later chunk is twice as big
+const FIRST_CHUNK: usize = 64;
+
+/// Chunks needed for [`NAMES`] to hold a slot for every `u32` symbol
+const CHUNKS: usize = 27;
+
+/// Every interned name, indexed by symbol
+///
+/// This is an append-only arena: chunks are allocated while interning and
+/// never moved or freed, and each slot is written once, so resolving a
+/// symbol reads its slot without taking a lock. Names live as long as the
+/// process, which costs one allocation per distinct identifier.
+static NAMES: [AtomicPtr<OnceLock<&'static str>>; CHUNKS] = [const { AtomicPtr::new(ptr::null_mut()) }; CHUNKS];
+
+/// The symbol of every interned name; only needed to intern, never to resolve
+static SYMBOLS: LazyLock<RwLock<HashMap<&'static str, Symbol>>> = LazyLock::new(Default::default);
+
+/// The chunk of [`NAMES`] that holds the slot of symbol `index`, its
+/// length, and the slot's position in it
+fn slot_position(index: u32) -> (usize, usize, usize) {
+    let position = index as usize + FIRST_CHUNK;
+    let chunk = (position.ilog2() - FIRST_CHUNK.ilog2()) as usize;
+    let start = FIRST_CHUNK << chunk;
+    (chunk, start, position - start)
+}
+
+impl Symbol {
+    /// The symbol for `name`, interning it if this is its first use
+    pub fn intern(name: &str) -> Self {
+        if let Some(symbol) = SYMBOLS.read().unwrap().get(name) {
+            return *symbol;
+        }
+        let mut symbols = SYMBOLS.write().unwrap();
+        // Another thread may have interned the name since the read lock was released
+        if let Some(symbol) = symbols.get(name) {
+            return *symbol;
+        }
+        let index = u32::try_from(symbols.len()).expect("too many distinct identifiers");
+        let (chunk, len, offset) = slot_position(index);
+        // Chunks are only allocated here, under the write lock
+        let mut slots = NAMES[chunk].load(Ordering::Acquire);
+        if slots.is_null() {
+            let chunk_slots: Box<[OnceLock<&'static str>]> = (0..len).map(|_| OnceLock::new()).collect();
+            slots = Box::leak(chunk_slots).as_mut_ptr();
+            NAMES[chunk].store(slots, Ordering::Release);
+        }
+        let name: &'static str = Box::leak(name.into());
+        // SAFETY: the chunk holds `len` slots and `offset < len`
+        let slot = unsafe { &*slots.add(offset) };
+        slot.set(name).expect("symbol slots are written once");
+        let symbol = Symbol(index);
+        symbols.insert(name, symbol);
+        symbol
+    }
+
+    /// The name this symbol stands for
+    pub fn as_str(&self) -> &'static str {
+        let (chunk, _, offset) = slot_position(self.0);
+        let slots = NAMES[chunk].load(Ordering::Acquire);
+        assert!(!slots.is_null(), "symbol {} was never interned", self.0);
+        // SAFETY: a symbol is only handed out after its chunk was allocated
+        // with room for its slot, and chunks are never freed
+        let slot = unsafe { &*slots.add(offset) };
+        slot.get().expect("symbol slots are written before the symbol is handed out")
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Shows the name, so AST dumps read the same as with plain strings
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Self::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Self::intern(&name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Self::intern(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::TokenType;
+
+    #[test]
+    fn test_equal_names_share_a_symbol() {
+        let first = Symbol::intern("counter");
+        let second = Symbol::from("counter".to_string());
+        assert_eq!(first, second);
+        assert_ne!(first, Symbol::intern("count"));
+        assert_eq!(first.as_str(), "counter");
+        assert_eq!(first, "counter");
+        // Copying a name is copying a u32
+        assert_eq!(std::mem::size_of::<Symbol>(), 4);
+        assert_eq!(format!("{} {:?}", first, first), "counter \"counter\"");
+
+        let threads: Vec<_> = (0..4).map(|_| std::thread::spawn(|| Symbol::intern("shared_name"))).collect();
+        let symbols: Vec<Symbol> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+        assert!(symbols.iter().all(|symbol| *symbol == symbols[0]));
+    }
+
+    /// Counts the allocations made by the current thread, so that tests
+    /// running in parallel don't disturb each other's counts
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations_during(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(|count| count.get());
+        f();
+        ALLOCATIONS.with(|count| count.get()) - before
+    }
+
+    #[test]
+    fn test_symbols_resolve_across_arena_chunks() {
+        let names: Vec<String> = (0..1000).map(|i| format!("arena_name_{}", i)).collect();
+        let symbols: Vec<Symbol> = names.iter().map(Symbol::from).collect();
+        for (name, symbol) in names.iter().zip(&symbols) {
+            assert_eq!(symbol.as_str(), name);
+        }
+    }
+
+    #[test]
+    fn test_repeated_identifiers_do_not_allocate() {
+        let source = "local total := count + offset\n".repeat(5_000);
+        // Intern the names up front, as an earlier part of the program would
+        crate::Lexer::new(&source[..30], None).tokenize().unwrap();
+        let allocations = allocations_during(|| {
+            let tokens = crate::Lexer::new(&source, None).tokenize().unwrap();
+            assert_eq!(tokens.len(), 30_001);
+            assert_eq!(tokens[1].token_type, TokenType::Identifier(Symbol::intern("total")));
+        });
+        // Only the source and the token vector are allocated, not a
+        // string per token; copying each lexeme took over 50,000
+        assert!(allocations < 100, "{} allocations", allocations);
+    }
+}
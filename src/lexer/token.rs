@@ -2,26 +2,43 @@
 //!
 //! This module defines all token types used in lexical analysis.
 
-use crate::error::SourceLocation;
+use crate::error::{SourceLocation, Span};
+use crate::lexer::Symbol;
+use std::borrow::Cow;
 use std::fmt;
 
 /// A token in the Lux language
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
-    pub lexeme: String,
+    /// The token's source text; identifiers and keywords borrow it instead
+    /// of allocating a copy
+    pub lexeme: Cow<'static, str>,
     pub location: SourceLocation,
+    /// Position just past the last character of the token
+    pub end: SourceLocation,
 }
 
 impl Token {
     /// Create a new token
-    pub fn new(token_type: TokenType, lexeme: String, location: SourceLocation) -> Self {
+    pub fn new(
+        token_type: TokenType,
+        lexeme: impl Into<Cow<'static, str>>,
+        location: SourceLocation,
+        end: SourceLocation,
+    ) -> Self {
         Self {
             token_type,
-            lexeme,
+            lexeme: lexeme.into(),
             location,
+            end,
         }
     }
+
+    /// Get the source span covered by this token
+    pub fn span(&self) -> Span {
+        Span::new(self.location.clone(), self.end.clone())
+    }
 }
 
 /// Token types in the Lux language
@@ -31,7 +48,7 @@ pub enum TokenType {
     Literal(Literal),
 
     // Identifiers and keywords
-    Identifier,
+    Identifier(Symbol),
     Keyword(Keyword),
 
     // Operators
@@ -40,7 +57,9 @@ pub enum TokenType {
     Minus,      // -
     Star,       // *
     Slash,      // /
+    TildeSlash, // ~/ (floor division; `//` starts a comment)
     Percent,    // %
+    Caret,      // ^
 
     // Comparison
     Equal,          // ==
@@ -62,6 +81,7 @@ pub enum TokenType {
     // Unary operators
     Hash,           // # (length operator, Lua-style)
     Ampersand,      // & (address-of operator)
+    Question,       // ? (optional type suffix)
 
     // Delimiters
     LeftParen,      // (
@@ -99,6 +119,8 @@ pub enum Keyword {
     For,
     Break,
     Continue,
+    Do,
+    Defer,
 
     // Types
     Int,
@@ -107,6 +129,7 @@ pub enum Keyword {
     Bool,
     Nil,
     Table,
+    Any,
 
     // Boolean literals
     True,
@@ -124,10 +147,16 @@ pub enum Keyword {
 
     // Modules
     Import,
+
+    // Type declarations
+    Type,
+    Enum,
+    Struct,
 }
 
 impl Keyword {
     /// Get keyword from string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "local" => Some(Self::Local),
@@ -140,12 +169,15 @@ impl Keyword {
             "for" => Some(Self::For),
             "break" => Some(Self::Break),
             "continue" => Some(Self::Continue),
+            "do" => Some(Self::Do),
+            "defer" => Some(Self::Defer),
             "int" => Some(Self::Int),
             "float" => Some(Self::Float),
             "string" => Some(Self::String),
             "bool" => Some(Self::Bool),
             "nil" => Some(Self::Nil),
             "table" => Some(Self::Table),
+            "any" => Some(Self::Any),
             "true" => Some(Self::True),
             "false" => Some(Self::False),
             "async" => Some(Self::Async),
@@ -155,6 +187,9 @@ impl Keyword {
             "or" => Some(Self::Or),
             "not" => Some(Self::Not),
             "import" => Some(Self::Import),
+            "type" => Some(Self::Type),
+            "enum" => Some(Self::Enum),
+            "struct" => Some(Self::Struct),
             _ => None,
         }
     }
@@ -172,12 +207,15 @@ impl Keyword {
             Self::For => "for",
             Self::Break => "break",
             Self::Continue => "continue",
+            Self::Do => "do",
+            Self::Defer => "defer",
             Self::Int => "int",
             Self::Float => "float",
             Self::String => "string",
             Self::Bool => "bool",
             Self::Nil => "nil",
             Self::Table => "table",
+            Self::Any => "any",
             Self::True => "true",
             Self::False => "false",
             Self::Async => "async",
@@ -187,6 +225,9 @@ impl Keyword {
             Self::Or => "or",
             Self::Not => "not",
             Self::Import => "import",
+            Self::Type => "type",
+            Self::Enum => "enum",
+            Self::Struct => "struct",
         }
     }
 }
@@ -205,43 +246,58 @@ pub enum Literal {
     String(String),
 }
 
+impl TokenType {
+    /// The source text of a token that is always spelled the same way,
+    /// such as an operator or delimiter
+    pub fn text(&self) -> Option<&'static str> {
+        let text = match self {
+            Self::Plus => "+",
+            Self::Minus => "-",
+            Self::Star => "*",
+            Self::Slash => "/",
+            Self::TildeSlash => "~/",
+            Self::Percent => "%",
+            Self::Caret => "^",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterEqual => ">=",
+            Self::And => "and",
+            Self::Or => "or",
+            Self::Not => "not",
+            Self::Assign => "=",
+            Self::ColonAssign => ":=",
+            Self::Hash => "#",
+            Self::LeftParen => "(",
+            Self::RightParen => ")",
+            Self::LeftBrace => "{",
+            Self::RightBrace => "}",
+            Self::LeftBracket => "[",
+            Self::RightBracket => "]",
+            Self::Comma => ",",
+            Self::Dot => ".",
+            Self::Colon => ":",
+            Self::Semicolon => ";",
+            Self::Arrow => "->",
+            Self::Ampersand => "&",
+            Self::Question => "?",
+            Self::Literal(_) | Self::Identifier(_) | Self::Keyword(_) | Self::Newline | Self::Eof => return None,
+        };
+        Some(text)
+    }
+}
+
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Literal(lit) => write!(f, "{:?}", lit),
-            Self::Identifier => write!(f, "identifier"),
+            Self::Identifier(_) => write!(f, "identifier"),
             Self::Keyword(kw) => write!(f, "keyword '{}'", kw),
-            Self::Plus => write!(f, "+"),
-            Self::Minus => write!(f, "-"),
-            Self::Star => write!(f, "*"),
-            Self::Slash => write!(f, "/"),
-            Self::Percent => write!(f, "%"),
-            Self::Equal => write!(f, "=="),
-            Self::NotEqual => write!(f, "!="),
-            Self::Less => write!(f, "<"),
-            Self::LessEqual => write!(f, "<="),
-            Self::Greater => write!(f, ">"),
-            Self::GreaterEqual => write!(f, ">="),
-            Self::And => write!(f, "and"),
-            Self::Or => write!(f, "or"),
-            Self::Not => write!(f, "not"),
-            Self::Assign => write!(f, "="),
-            Self::ColonAssign => write!(f, ":="),
-            Self::Hash => write!(f, "#"),
-            Self::LeftParen => write!(f, "("),
-            Self::RightParen => write!(f, ")"),
-            Self::LeftBrace => write!(f, "{{"),
-            Self::RightBrace => write!(f, "}}"),
-            Self::LeftBracket => write!(f, "["),
-            Self::RightBracket => write!(f, "]"),
-            Self::Comma => write!(f, ","),
-            Self::Dot => write!(f, "."),
-            Self::Colon => write!(f, ":"),
-            Self::Semicolon => write!(f, ";"),
-            Self::Arrow => write!(f, "->"),
-            Self::Ampersand => write!(f, "&"),
             Self::Newline => write!(f, "newline"),
             Self::Eof => write!(f, "EOF"),
+            other => f.write_str(other.text().unwrap_or_default()),
         }
     }
 }
@@ -12,25 +12,92 @@
 //! - `parser`: Parsing tokens into an Abstract Syntax Tree (AST)
 //! - `types`: Type system and type checking
 //! - `semantic`: Semantic analysis and validation
+//! - `optimize`: Optional AST optimizations such as constant folding
 //! - `runtime`: Interpreter/execution engine
+//! - `compiler`: Lowering of the AST to bytecode for the `vm` backend
+//! - `vm`: Bytecode virtual machine, an alternative to the interpreter
 //! - `async_runtime`: Async task execution (future)
+//! - `module`: Module loading state shared between phases
 //! - `error`: Error handling and diagnostics
 
 pub mod error;
 pub mod lexer;
 pub mod parser;
 pub mod types;
+pub mod semantic;
+pub mod optimize;
 pub mod runtime;
+pub mod compiler;
+pub mod vm;
 pub mod async_runtime;
+pub mod module;
 
 // Re-export commonly used types
-pub use error::{LuxError, LuxResult, SourceLocation};
-pub use lexer::{Token, TokenType, Lexer};
+pub use error::{Diagnostics, LuxError, LuxResult, SourceLocation, Span};
+pub use lexer::{Token, TokenType, Lexer, Symbol};
 pub use parser::{Parser, Ast};
+pub use module::ModuleCache;
 
 /// Version of the Lux language
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Settings for [`run_with`]
+///
+/// By default the program gets no script arguments, warnings are printed to
+/// stderr before it starts, imported modules are parsed into a fresh cache
+/// and constant expressions are folded.
+pub struct RunOptions<'a> {
+    script_args: Vec<String>,
+    diagnostics: Option<&'a mut Diagnostics>,
+    modules: ModuleCache,
+    fold_constants: bool,
+}
+
+impl<'a> RunOptions<'a> {
+    pub fn new() -> Self {
+        RunOptions {
+            script_args: Vec::new(),
+            diagnostics: None,
+            modules: ModuleCache::new(),
+            fold_constants: true,
+        }
+    }
+
+    /// Make `script_args` available to the program through the `args()` builtin
+    pub fn with_args(mut self, script_args: Vec<String>) -> Self {
+        self.script_args = script_args;
+        self
+    }
+
+    /// Record warnings in `diagnostics` for the caller to inspect instead of
+    /// printing them. Warnings don't stop the program from running unless
+    /// `diagnostics` was created with [`Diagnostics::deny_warnings`].
+    pub fn with_diagnostics(mut self, diagnostics: &'a mut Diagnostics) -> Self {
+        self.diagnostics = Some(diagnostics);
+        self
+    }
+
+    /// Parse imported modules through `modules`, so modules parsed for one
+    /// program are reused by the next program run with the same cache
+    pub fn with_modules(mut self, modules: &ModuleCache) -> Self {
+        self.modules = modules.clone();
+        self
+    }
+
+    /// Leave constant expressions as written instead of folding them.
+    /// `const` declarations are still evaluated at compile time.
+    pub fn without_folding(mut self) -> Self {
+        self.fold_constants = false;
+        self
+    }
+}
+
+impl Default for RunOptions<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Compile and run a Lux program from source code
 ///
 /// This is the main entry point for executing Lux programs.
@@ -47,25 +114,154 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 /// Returns `Ok(())` if the program executes successfully, or a `LuxError` if
 /// any stage of compilation or execution fails.
 pub fn run(source: &str, filename: Option<&str>) -> LuxResult<()> {
+    run_with(source, filename, RunOptions::new())?;
+    Ok(())
+}
+
+/// Compile and run a Lux program, returning its final value
+///
+/// Runs the same pipeline as [`run`], but hands back the value of a top-level
+/// `return` or of the program's final expression statement (nil otherwise),
+/// so host programs can compute with Lux.
+///
+/// # Arguments
+///
+/// * `source` - The source code to compile and run
+/// * `filename` - Optional filename for error reporting
+pub fn eval(source: &str, filename: Option<&str>) -> LuxResult<runtime::Value> {
+    run_with(source, filename, RunOptions::new())
+}
+
+/// Compile and run a Lux program with the given [`RunOptions`], returning
+/// its final value as [`eval`] does
+///
+/// Both the type checker and the interpreter load imports through the
+/// options' module cache, so each module is parsed once.
+pub fn run_with(source: &str, filename: Option<&str>, options: RunOptions) -> LuxResult<runtime::Value> {
+    let tokens = Lexer::new(source, filename).with_newlines().tokenize()?;
+    run_ast_with(Parser::new(tokens).parse()?, filename, options)
+}
+
+/// Check and run a program that has already been parsed, returning its
+/// final value as [`eval`] does
+///
+/// Lets a caller that inspects the syntax tree first, such as the REPL,
+/// avoid parsing the source twice.
+pub fn eval_ast(ast: Ast, filename: Option<&str>) -> LuxResult<runtime::Value> {
+    run_ast_with(ast, filename, RunOptions::new())
+}
+
+/// The part of [`run_with`] after parsing
+fn run_ast_with(ast: Ast, filename: Option<&str>, options: RunOptions) -> LuxResult<runtime::Value> {
+    let RunOptions { script_args, diagnostics, modules, fold_constants } = options;
+    let mut printed = Diagnostics::new();
+    let report = diagnostics.is_none();
+    let diagnostics = match diagnostics {
+        Some(diagnostics) => diagnostics,
+        None => &mut printed,
+    };
+    let ast = analyze(ast, filename, diagnostics, &modules, fold_constants)?;
+    if report {
+        diagnostics.report();
+    }
+
+    // Phase 5: Interpretation
+    let interpreter = runtime::Interpreter::new().with_args(script_args);
+    interpret(with_current_file(interpreter, filename).with_modules(modules), &ast)
+}
+
+/// Check a Lux program without running it
+///
+/// Runs lexing, parsing, type checking and semantic analysis, recording
+/// warnings in `diagnostics`. Imported modules are checked too, but no code
+/// is executed.
+pub fn check(source: &str, filename: Option<&str>, diagnostics: &mut Diagnostics) -> LuxResult<()> {
+    compile(source, filename, diagnostics, &ModuleCache::new(), true)?;
+    Ok(())
+}
+
+/// Compile and run a Lux program on the bytecode virtual machine
+///
+/// Runs the same front end as [`run`], then compiles the checked program to
+/// bytecode instead of interpreting the AST. The bytecode backend supports a
+/// subset of the language; anything outside it is reported as an error
+/// before the program starts.
+pub fn run_vm(source: &str, filename: Option<&str>) -> LuxResult<()> {
+    let mut diagnostics = Diagnostics::new();
+    let ast = compile(source, filename, &mut diagnostics, &ModuleCache::new(), true)?;
+    diagnostics.report();
+
+    let program = compiler::compile(&ast)?;
+    vm::Vm::new().run(&program)?;
+    Ok(())
+}
+
+/// Resolve the program's imports relative to `filename`, when there is one
+fn with_current_file(interpreter: runtime::Interpreter, filename: Option<&str>) -> runtime::Interpreter {
+    match filename {
+        Some(file) => interpreter.with_current_file(file),
+        None => interpreter,
+    }
+}
+
+/// Interpret `ast` on a thread whose stack is large enough for the
+/// interpreter's call depth limit
+fn interpret(mut interpreter: runtime::Interpreter, ast: &Ast) -> LuxResult<runtime::Value> {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(runtime::interpreter::STACK_SIZE)
+            .spawn_scoped(scope, move || interpreter.eval(ast))
+            .map_err(|e| LuxError::internal_error(format!("Failed to start interpreter thread: {}", e)))?
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Lex, parse and check `source`, recording warnings in `diagnostics`
+fn compile(
+    source: &str,
+    filename: Option<&str>,
+    diagnostics: &mut Diagnostics,
+    modules: &ModuleCache,
+    fold_constants: bool,
+) -> LuxResult<Ast> {
     // Phase 1: Lexical Analysis
-    let mut lexer = Lexer::new(source, filename);
+    let mut lexer = Lexer::new(source, filename).with_newlines();
     let tokens = lexer.tokenize()?;
 
     // Phase 2: Parsing
     let ast = Parser::new(tokens).parse()?;
+    analyze(ast, filename, diagnostics, modules, fold_constants)
+}
 
+/// Check and optimize a parsed program, recording warnings in `diagnostics`
+fn analyze(
+    mut ast: Ast,
+    filename: Option<&str>,
+    diagnostics: &mut Diagnostics,
+    modules: &ModuleCache,
+    fold_constants: bool,
+) -> LuxResult<Ast> {
     // Phase 3: Type Checking
-    let mut type_checker = types::TypeChecker::new();
+    let mut type_checker = types::TypeChecker::new().with_modules(modules.clone());
+    if let Some(file) = filename {
+        type_checker = type_checker.with_current_file(file);
+    }
     type_checker.check(&ast)?;
 
-    // Phase 4: Semantic Analysis (to be implemented)
-    // let validated_ast = SemanticAnalyzer::analyze(typed_ast)?;
+    // Phase 4: Semantic Analysis (warnings only)
+    semantic::SemanticAnalyzer::new(diagnostics).analyze(&ast);
+    diagnostics.check()?;
 
-    // Phase 5: Interpretation
-    let mut interpreter = runtime::Interpreter::new();
-    interpreter.interpret(&ast)?;
+    // Consts are always evaluated here; other constant expressions are
+    // folded once instead of on every run, unless folding is turned off
+    if fold_constants {
+        optimize::fold_constants(&mut ast)?;
+    } else {
+        optimize::evaluate_consts(&mut ast)?;
+    }
 
-    Ok(())
+    Ok(ast)
 }
 
 #[cfg(test)]
@@ -76,5 +272,117 @@ mod tests {
     fn test_version() {
         assert!(!VERSION.is_empty());
     }
+
+    #[test]
+    fn test_eval_returns_final_value() {
+        assert!(matches!(eval("6 * 7", None), Ok(runtime::Value::Int(42))));
+        assert!(matches!(eval("return 40 + 2", None), Ok(runtime::Value::Int(42))));
+        assert!(matches!(eval("local x := 1", None), Ok(runtime::Value::Nil)));
+
+        let tokens = Lexer::new("6 * 7", None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        assert!(matches!(eval_ast(ast, None), Ok(runtime::Value::Int(42))));
+    }
+
+    #[test]
+    fn test_check_does_not_run_the_program() {
+        let mut diagnostics = Diagnostics::new();
+        assert!(check("local unused := missing_function()", None, &mut diagnostics).is_err());
+        assert!(check("fn f() { local unused := 1 }\nprint(1 ~/ 0)", None, &mut diagnostics).is_ok());
+        assert_eq!(diagnostics.warnings().count(), 1);
+    }
+
+    #[test]
+    fn test_warnings_as_errors() {
+        let source = "fn f() -> int {\n    local unused := 1\n    return 2\n}\nf()";
+        fn recording(diagnostics: &mut Diagnostics) -> RunOptions<'_> {
+            RunOptions::new().with_diagnostics(diagnostics)
+        }
+        assert!(run_with(source, None, recording(&mut Diagnostics::new())).is_ok());
+
+        let err = run_with(source, None, recording(&mut Diagnostics::new().deny_warnings())).unwrap_err();
+        assert_eq!(err.message(), "Unused variable 'unused' (warnings are treated as errors)");
+        assert_eq!(err.location().map(|l| (l.line, l.column)), Some((2, 5)));
+        let clean = "fn f() -> int { return 2 }\nf()";
+        assert!(run_with(clean, None, recording(&mut Diagnostics::new().deny_warnings())).is_ok());
+    }
+
+    #[test]
+    fn test_consts_are_evaluated_without_folding() {
+        let options = || RunOptions::new().without_folding();
+        let source = "const limit := 6 * 7\nreturn limit == 2 * 21";
+        assert!(matches!(run_with(source, None, options()), Ok(runtime::Value::Bool(true))));
+
+        let err = run_with("local n := 1\nconst m := n", None, options()).unwrap_err();
+        assert_eq!(err.message(), "Const 'm' can't read 'n', which is not a const");
+    }
+
+    #[test]
+    fn test_script_args() {
+        let options = RunOptions::new().with_args(vec!["a".to_string(), "b".to_string()]);
+        assert!(matches!(run_with("#args()", None, options), Ok(runtime::Value::Int(2))));
+    }
+
+    #[test]
+    fn test_warnings_do_not_stop_the_program() {
+        let mut diagnostics = Diagnostics::new();
+        let source = "fn f() -> int {\n    local unused := 1\n    return 2\n}\nf()";
+        assert!(run_with(source, None, RunOptions::new().with_diagnostics(&mut diagnostics)).is_ok());
+
+        let warnings: Vec<_> = diagnostics.warnings().map(|w| w.summary()).collect();
+        assert_eq!(warnings, vec!["Warning: Unused variable 'unused' at 2:5"]);
+    }
+
+    #[test]
+    fn test_diamond_import_parses_shared_module_once() {
+        // a imports b and c, which both import d
+        let dir = std::env::temp_dir().join("lux_diamond_import");
+        std::fs::create_dir_all(&dir).unwrap();
+        let module = |name: &str| dir.join(name).to_string_lossy().to_string();
+        std::fs::write(dir.join("d.lux"), "fn shared() -> int { return 1 }").unwrap();
+        for name in ["b", "c"] {
+            let source = format!("import \"{}\"\nfn from_{}() -> int {{ return shared() }}", module("d"), name);
+            std::fs::write(dir.join(format!("{}.lux", name)), source).unwrap();
+        }
+
+        let modules = ModuleCache::new();
+        let source = format!("import \"{}\"\nimport \"{}\"\nfrom_b() + from_c()", module("b"), module("c"));
+        run_with(&source, None, RunOptions::new().with_modules(&modules)).unwrap();
+        // b, c and d are each parsed once, shared by the checker and the interpreter
+        assert_eq!(modules.parse_count(), 3);
+    }
+
+    #[test]
+    fn test_script_imports_sibling_module() {
+        let dir = std::env::temp_dir().join("lux_sibling_import").join("scripts");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("helper.lux"), "fn helped() -> int { return 7 }").unwrap();
+        let script = dir.join("main.lux").to_string_lossy().to_string();
+
+        let source = "import \"helper\"\nhelped() * 6";
+        assert!(matches!(eval(source, Some(&script)), Ok(runtime::Value::Int(42))));
+        assert!(run(source, Some(&script)).is_ok());
+        assert!(eval(source, None).unwrap_err().message().contains("Module 'helper' not found"));
+    }
+
+    #[test]
+    fn test_circular_import_is_an_error() {
+        let dir = std::env::temp_dir().join("lux_circular_import");
+        std::fs::create_dir_all(&dir).unwrap();
+        let ping = dir.join("ping").to_string_lossy().to_string();
+        let pong = dir.join("pong").to_string_lossy().to_string();
+        std::fs::write(dir.join("ping.lux"), format!("import \"{}\"", pong)).unwrap();
+        std::fs::write(dir.join("pong.lux"), format!("import \"{}\"", ping)).unwrap();
+
+        let expected = format!("Circular import: {} -> {} -> {}", ping, pong, ping);
+        let err = eval(&format!("import \"{}\"", ping), None).unwrap_err();
+        assert_eq!(err.message(), expected);
+
+        // The interpreter detects the cycle on its own too
+        let tokens = Lexer::new(&format!("import \"{}\"", ping), None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        let err = runtime::Interpreter::new().eval(&ast).unwrap_err();
+        assert_eq!(err.message(), expected);
+    }
 }
 
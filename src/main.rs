@@ -7,10 +7,26 @@ use std::fs;
 use std::io::{self, Write};
 use std::process;
 
-use lux_lang::{run, Lexer, VERSION};
+use lux_lang::error::Diagnostic;
+use lux_lang::lexer::{Literal, Token, TokenType};
+use lux_lang::parser::Stmt;
+use lux_lang::runtime::Value;
+use lux_lang::{
+    check, eval_ast, run_with, Diagnostics, Lexer, LuxResult, Parser, RunOptions, VERSION,
+};
+
+/// How errors and warnings are written to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DiagnosticFormat {
+    /// Human-readable text
+    Human,
+    /// One JSON array of diagnostics, for editors (see `Diagnostic::to_json`)
+    Json,
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    lux_lang::error::configure_color();
 
     if args.len() == 1 {
         // No arguments: start REPL
@@ -22,19 +38,49 @@ fn main() {
 
     // Check for flags
     let mut show_tokens = false;
+    let mut show_ast = false;
     let mut show_help = false;
+    let mut check_only = false;
+    let mut deny_warnings = false;
+    let mut fold_constants = true;
+    let mut format = DiagnosticFormat::Human;
     let mut filename: Option<&String> = None;
+    let mut script_args: &[String] = &[];
 
-    for arg in &args[1..] {
+    let mut flags = args.iter().enumerate().skip(1);
+    while let Some((i, arg)) = flags.next() {
         match arg.as_str() {
             "--tokens" | "-t" => show_tokens = true,
+            "--ast" | "-a" => show_ast = true,
             "--help" | "-h" => show_help = true,
+            "--check" | "-c" => check_only = true,
+            "--no-fold" => fold_constants = false,
+            "-W" => match flags.next() {
+                Some((_, level)) if level == "error" => deny_warnings = true,
+                _ => {
+                    eprintln!("-W expects 'error' (treat warnings as errors)");
+                    print_usage();
+                    process::exit(1);
+                }
+            },
+            "--diagnostics=human" => format = DiagnosticFormat::Human,
+            "--diagnostics=json" => format = DiagnosticFormat::Json,
+            _ if arg.starts_with("--diagnostics=") => {
+                eprintln!("--diagnostics expects 'human' or 'json'");
+                print_usage();
+                process::exit(1);
+            }
             _ if arg.starts_with('-') => {
                 eprintln!("Unknown flag: {}", arg);
                 print_usage();
                 process::exit(1);
             }
-            _ => filename = Some(arg),
+            _ => {
+                // Everything after the script name belongs to the script
+                filename = Some(arg);
+                script_args = &args[i + 1..];
+                break;
+            }
         }
     }
 
@@ -49,8 +95,18 @@ fn main() {
                 eprintln!("{}", e);
                 process::exit(1);
             }
+        } else if show_ast {
+            if let Err(e) = show_file_ast(file) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        } else if check_only {
+            if let Err(e) = check_file(file, deny_warnings, format) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
         } else {
-            if let Err(e) = run_file(file) {
+            if let Err(e) = run_file(file, script_args, deny_warnings, format, fold_constants) {
                 eprintln!("{}", e);
                 process::exit(1);
             }
@@ -63,7 +119,7 @@ fn main() {
 }
 
 fn print_usage() {
-    eprintln!("Usage: lux [OPTIONS] [script]");
+    eprintln!("Usage: lux [OPTIONS] [script] [args...]");
     eprintln!("       lux --help");
 }
 
@@ -71,15 +127,27 @@ fn print_help() {
     println!("Lux v{} - A custom programming language", VERSION);
     println!();
     println!("USAGE:");
-    println!("    lux [OPTIONS] [script]");
+    println!("    lux [OPTIONS] [script] [args...]");
     println!();
     println!("OPTIONS:");
     println!("    -t, --tokens    Show tokenization output (lexer only)");
+    println!("    -a, --ast       Show the parsed syntax tree");
+    println!("    -c, --check     Check the script for errors without running it");
+    println!("    -W error        Treat warnings as errors");
+    println!("    --no-fold       Don't fold constant expressions before running");
+    println!("    --diagnostics=json");
+    println!("                    Report errors and warnings as a JSON array on stderr");
     println!("    -h, --help      Show this help message");
     println!();
     println!("EXAMPLES:");
     println!("    lux script.lux           Run a Lux script");
+    println!("    lux script.lux a b       Run a script with arguments (see args())");
     println!("    lux --tokens script.lux  Show tokens from lexer");
+    println!("    lux --ast script.lux     Show the parsed syntax tree");
+    println!("    lux --check script.lux   Type-check a script without running it");
+    println!("    lux -W error script.lux  Fail if the script has any warnings");
+    println!("    lux --check --diagnostics=json script.lux");
+    println!("                             Check a script, reporting errors for an editor");
     println!("    lux                      Start interactive REPL");
     println!();
     println!("IMPLEMENTATION STATUS:");
@@ -92,13 +160,78 @@ fn print_help() {
     println!("    ⏳ Phase 7: Async Runtime");
 }
 
-/// Run a Lux script from a file
-fn run_file(filename: &str) -> Result<(), String> {
+/// Run a Lux script from a file, passing `script_args` through to `args()`
+///
+/// With `deny_warnings`, any warning aborts the script before it starts.
+/// In JSON format, warnings are reported together with any error once the
+/// script finishes.
+fn run_file(
+    filename: &str,
+    script_args: &[String],
+    deny_warnings: bool,
+    format: DiagnosticFormat,
+    fold_constants: bool,
+) -> Result<(), String> {
+    let source = fs::read_to_string(filename)
+        .map_err(|e| format!("Failed to read file '{}': {}", filename, e))?;
+
+    let mut diagnostics = new_diagnostics(deny_warnings);
+    let mut options = RunOptions::new().with_args(script_args.to_vec());
+    if !fold_constants {
+        options = options.without_folding();
+    }
+
+    if format == DiagnosticFormat::Json {
+        let result = run_with(&source, Some(filename), options.with_diagnostics(&mut diagnostics));
+        return report_json(diagnostics, deny_warnings, result.map(|_| ()));
+    }
+
+    // Otherwise warnings are printed before the script starts
+    if deny_warnings {
+        options = options.with_diagnostics(&mut diagnostics);
+    }
+    run_with(&source, Some(filename), options).map(|_| ()).map_err(|e| format!("{}", e))
+}
+
+/// Check a Lux script without running it, printing any warnings
+fn check_file(filename: &str, deny_warnings: bool, format: DiagnosticFormat) -> Result<(), String> {
     let source = fs::read_to_string(filename)
         .map_err(|e| format!("Failed to read file '{}': {}", filename, e))?;
 
-    run(&source, Some(filename))
-        .map_err(|e| format!("{}", e))
+    let mut diagnostics = new_diagnostics(deny_warnings);
+    let result = check(&source, Some(filename), &mut diagnostics);
+    if format == DiagnosticFormat::Json {
+        return report_json(diagnostics, deny_warnings, result);
+    }
+    if !deny_warnings {
+        diagnostics.report();
+    }
+    result.map_err(|e| format!("{}", e))
+}
+
+fn new_diagnostics(deny_warnings: bool) -> Diagnostics {
+    if deny_warnings { Diagnostics::new().deny_warnings() } else { Diagnostics::new() }
+}
+
+/// Report warnings and any error as a single JSON array
+///
+/// On failure the array is returned as the error for the caller to print;
+/// otherwise it is printed here, if there is anything to report. Denied
+/// warnings only appear as the error they caused.
+fn report_json(diagnostics: Diagnostics, deny_warnings: bool, result: LuxResult<()>) -> Result<(), String> {
+    let mut report = if deny_warnings { Diagnostics::new() } else { diagnostics };
+    match result {
+        Ok(()) => {
+            if !report.is_empty() {
+                eprintln!("{}", report.to_json());
+            }
+            Ok(())
+        }
+        Err(e) => {
+            report.push(Diagnostic::new(e));
+            Err(report.to_json())
+        }
+    }
 }
 
 /// Show tokens from lexing a file
@@ -114,7 +247,7 @@ fn show_file_tokens(filename: &str) -> Result<(), String> {
     println!("{}", "=".repeat(60));
 
     for (i, token) in tokens.iter().enumerate() {
-        println!("{:4}: {:20} | {:?}", i, format!("{:?}", token.token_type), token.lexeme);
+        println!("{}", format_token(i, token));
     }
 
     println!("{}", "=".repeat(60));
@@ -123,6 +256,71 @@ fn show_file_tokens(filename: &str) -> Result<(), String> {
     Ok(())
 }
 
+/// One line of the `--tokens` listing: index, `line:column`, kind and text
+///
+/// String literals show their value with escapes (`\n`, `\t`, `\u{1b}`), so a
+/// string spanning several source lines stays on one line of the listing.
+fn format_token(index: usize, token: &Token) -> String {
+    let location = format!("{}:{}", token.location.line, token.location.column);
+    let (kind, text) = match &token.token_type {
+        TokenType::Literal(Literal::String(value)) => ("Literal(String)".to_string(), escape_string(value)),
+        TokenType::Identifier(_) => ("Identifier".to_string(), token.lexeme.to_string()),
+        other => (format!("{:?}", other), token.lexeme.to_string()),
+    };
+    format!("{:4}: {:8} {:20} | {}", index, location, kind, text)
+}
+
+/// `value` as a quoted Lux string literal, with control characters escaped
+fn escape_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Show the AST from parsing a file
+fn show_file_ast(filename: &str) -> Result<(), String> {
+    let source = fs::read_to_string(filename)
+        .map_err(|e| format!("Failed to read file '{}': {}", filename, e))?;
+
+    let ast = Lexer::new(&source, Some(filename))
+        .with_newlines()
+        .tokenize()
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .map_err(|e| Diagnostic::with_source(e, &source).format())?;
+
+    println!("AST for '{}':", filename);
+    println!("{}", "=".repeat(60));
+    print!("{}", ast);
+    println!("{}", "=".repeat(60));
+    println!("Total statements: {}", ast.statements.len());
+
+    Ok(())
+}
+
+/// Run one line of REPL input
+///
+/// Returns the value of the input when it is a single expression statement
+/// that evaluates to something other than nil. Anything else, such as a
+/// declaration, a `return` or several statements, runs silently.
+fn eval_repl_line(input: &str) -> LuxResult<Option<Value>> {
+    let tokens = Lexer::new(input, Some("<repl>")).with_newlines().tokenize()?;
+    let ast = Parser::new(tokens).parse()?;
+    let echoes = matches!(ast.statements.as_slice(), [Stmt::Expression { .. }]);
+    let value = eval_ast(ast, Some("<repl>"))?;
+    Ok(if echoes && !matches!(value, Value::Nil) { Some(value) } else { None })
+}
+
 /// Start an interactive REPL (Read-Eval-Print Loop)
 fn repl() {
     let mut line_number = 1;
@@ -145,9 +343,11 @@ fn repl() {
                     continue;
                 }
 
-                // Run the input
-                if let Err(e) = run(input, Some("<repl>")) {
-                    eprintln!("{}", e);
+                // Run the input, echoing the value of bare expressions
+                match eval_repl_line(input) {
+                    Ok(Some(value)) => println!("=> {}", value),
+                    Ok(None) => {}
+                    Err(e) => eprintln!("{}", e),
                 }
 
                 line_number += 1;
@@ -162,3 +362,108 @@ fn repl() {
     println!("\nGoodbye!");
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_listing_shows_locations_and_escapes() {
+        let source = "local s := \"tab\\there\nnext \u{1b}é\"\nprint(s)";
+        let tokens = Lexer::new(source, None).tokenize().unwrap();
+        let lines: Vec<String> = tokens.iter().enumerate().map(|(i, token)| format_token(i, token)).collect();
+        assert_eq!(lines[0], "   0: 1:1      Keyword(Local)       | local");
+        assert_eq!(lines[3], "   3: 1:12     Literal(String)      | \"tab\\there\\nnext \\u{1b}é\"");
+        // The string spans two lines, so print starts on line 3
+        assert_eq!(lines[4], "   4: 3:1      Identifier           | print");
+        assert_eq!(lines[8], "   8: 3:9      Eof                  | ");
+    }
+
+    #[test]
+    fn test_repl_echoes_expression_value() {
+        let value = eval_repl_line("2 * 21").unwrap();
+        assert!(matches!(value, Some(Value::Int(42))));
+    }
+
+    #[test]
+    fn test_repl_statements_are_silent() {
+        assert!(eval_repl_line("local x = 1").unwrap().is_none());
+        assert!(eval_repl_line("nil").unwrap().is_none());
+        // Only a line that is one expression echoes
+        assert!(eval_repl_line("local y := 2; y * 21").unwrap().is_none());
+        assert!(eval_repl_line("return 42").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_check_file_reports_errors_without_running() {
+        let dir = env::temp_dir().join("lux_check_file");
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("written.txt");
+        fs::remove_file(&output).ok();
+        let script = dir.join("broken.lux");
+        let source = format!("write_file(\"{}\", \"ran\")\nlocal n: int = \"not a number\"\n", output.display());
+        fs::write(&script, &source).unwrap();
+
+        let err = check_file(script.to_str().unwrap(), false, DiagnosticFormat::Human).unwrap_err();
+        assert!(err.contains("declared as int but initialized with string"), "{}", err);
+        assert!(!output.exists(), "--check must not run the script");
+
+        fs::write(&script, format!("write_file(\"{}\", \"ran\")\n", output.display())).unwrap();
+        assert!(check_file(script.to_str().unwrap(), false, DiagnosticFormat::Human).is_ok());
+        assert!(!output.exists(), "--check must not run the script");
+    }
+
+    #[test]
+    fn test_json_diagnostics() {
+        let path = env::temp_dir().join("lux_json_diagnostics.lux");
+        fs::write(&path, "local x := 1\nlocal y := (x + \n").unwrap();
+        let filename = path.to_str().unwrap();
+
+        let parse_error = check_file(filename, false, DiagnosticFormat::Json).unwrap_err();
+        let runtime_error = {
+            fs::write(&path, "local x := 0\nprint(1 % x)\n").unwrap();
+            run_file(filename, &[], false, DiagnosticFormat::Json, true).unwrap_err()
+        };
+        fs::remove_file(&path).ok();
+
+        // Temporary paths need no JSON escaping
+        let file = format!("\"{}\"", filename);
+        assert!(parse_error.starts_with("[{\"kind\":\"Parse Error\",\"message\":"), "{}", parse_error);
+        assert!(parse_error.ends_with(&format!(",\"file\":{},\"line\":3,\"column\":1,\"severity\":\"error\"}}]", file)), "{}", parse_error);
+        assert!(runtime_error.starts_with("[{\"kind\":\"Runtime Error\""), "{}", runtime_error);
+        assert!(runtime_error.contains("\"line\":2,\"column\":"), "{}", runtime_error);
+    }
+
+    #[test]
+    fn test_run_file_forwards_script_args() {
+        let path = env::temp_dir().join("lux_run_file_args.lux");
+        // Fails at runtime with a modulo by zero unless the arguments arrive
+        fs::write(&path, "local a := args()\nif #a != 2 or a[2] != \"--verbose\" { local boom := 1 % 0 }\n").unwrap();
+
+        let script_args = vec!["input.txt".to_string(), "--verbose".to_string()];
+        let result = run_file(path.to_str().unwrap(), &script_args, false, DiagnosticFormat::Human, true);
+        let without_args = run_file(path.to_str().unwrap(), &[], false, DiagnosticFormat::Human, true);
+        fs::remove_file(&path).ok();
+        assert!(result.is_ok(), "{:?}", result);
+        assert!(without_args.is_err());
+    }
+
+    #[test]
+    fn test_show_file_ast() {
+        let path = env::temp_dir().join("lux_show_file_ast.lux");
+        fs::write(&path, "local x := 1 + 2\nprint(x)\n").unwrap();
+
+        let result = show_file_ast(path.to_str().unwrap());
+        fs::remove_file(&path).ok();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_show_file_ast_parse_error() {
+        let path = env::temp_dir().join("lux_show_file_ast_error.lux");
+        fs::write(&path, "local x = (1 + \n").unwrap();
+
+        let result = show_file_ast(path.to_str().unwrap());
+        fs::remove_file(&path).ok();
+        assert!(result.unwrap_err().contains("Parse Error"));
+    }
+}
@@ -0,0 +1,59 @@
+//! Module cache
+//!
+//! This module stores parsed modules so that a module imported by several
+//! files, or by both the type checker and the interpreter, is read and
+//! parsed only once.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use crate::error::{LuxError, LuxResult};
+use crate::lexer::Lexer;
+use crate::parser::{Ast, Parser};
+
+/// Parsed modules keyed by resolved path
+///
+/// Clones share the same cache, so one cache can be handed to the type
+/// checker, the interpreter and any task interpreters it spawns.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    modules: HashMap<String, Arc<Ast>>,
+    /// Number of files parsed so far
+    parses: usize,
+}
+
+impl ModuleCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the module at `resolved_path`, reading and parsing it on first
+    /// use. `read_error` builds the error reported when the file can't be read.
+    pub fn load(
+        &self,
+        resolved_path: &str,
+        read_error: impl FnOnce(std::io::Error) -> LuxError,
+    ) -> LuxResult<Arc<Ast>> {
+        if let Some(ast) = self.state.lock().unwrap().modules.get(resolved_path) {
+            return Ok(ast.clone());
+        }
+
+        let source = std::fs::read_to_string(resolved_path).map_err(read_error)?;
+        let tokens = Lexer::new(&source, Some(resolved_path)).with_newlines().tokenize()?;
+        let ast = Arc::new(Parser::new(tokens).parse()?);
+
+        let mut state = self.state.lock().unwrap();
+        state.parses += 1;
+        state.modules.insert(resolved_path.to_string(), ast.clone());
+        Ok(ast)
+    }
+
+    /// How many module files have been parsed through this cache
+    pub fn parse_count(&self) -> usize {
+        self.state.lock().unwrap().parses
+    }
+}
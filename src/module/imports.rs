@@ -0,0 +1,51 @@
+//! Import tracking
+//!
+//! This module tracks which modules are in the middle of being loaded, so
+//! that a module importing itself, directly or through other modules, is
+//! reported instead of recursing forever.
+
+/// Modules currently being loaded, outermost first
+#[derive(Debug, Clone, Default)]
+pub struct ImportStack {
+    paths: Vec<String>,
+}
+
+impl ImportStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start loading `path`. If it is already being loaded, return the
+    /// import cycle instead, written as `a -> b -> a`.
+    pub fn enter(&mut self, path: &str) -> Result<(), String> {
+        if let Some(start) = self.paths.iter().position(|p| p == path) {
+            let mut cycle = self.paths[start..].to_vec();
+            cycle.push(path.to_string());
+            return Err(cycle.join(" -> "));
+        }
+        self.paths.push(path.to_string());
+        Ok(())
+    }
+
+    /// Finish loading the most recently entered module
+    pub fn exit(&mut self) {
+        self.paths.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enter_reports_cycle() {
+        let mut imports = ImportStack::new();
+        imports.enter("main").unwrap();
+        imports.enter("a").unwrap();
+        imports.enter("b").unwrap();
+        assert_eq!(imports.enter("a").unwrap_err(), "a -> b -> a");
+        imports.exit();
+        imports.exit();
+        assert!(imports.enter("a").is_ok());
+    }
+}
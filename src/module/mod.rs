@@ -0,0 +1,12 @@
+//! Module system
+//!
+//! This module holds state shared by the compilation phases when a program
+//! imports other files.
+
+pub mod cache;
+pub mod imports;
+pub mod search;
+
+pub use cache::ModuleCache;
+pub use imports::ImportStack;
+pub use search::SearchPath;
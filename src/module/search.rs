@@ -0,0 +1,101 @@
+//! Module search path
+//!
+//! This module decides which file an `import` refers to. A module named
+//! `name` is the file `name.lux`, looked up first next to the importing
+//! file, then in each search directory in order, and finally relative to the
+//! working directory (which also covers absolute paths).
+
+use std::path::{Path, PathBuf};
+
+/// Environment variable listing extra search directories, separated like `PATH`
+pub const LUX_PATH: &str = "LUX_PATH";
+
+/// The directory containing `file`, used to resolve the imports it makes;
+/// `None` for a bare file name, which resolves against the working directory
+pub fn file_dir(file: &str) -> Option<String> {
+    Path::new(file)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| dir.to_string_lossy().to_string())
+}
+
+/// Ordered list of directories searched for imported modules
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchPath {
+    dirs: Vec<PathBuf>,
+}
+
+impl Default for SearchPath {
+    fn default() -> Self {
+        Self::from_env()
+    }
+}
+
+impl SearchPath {
+    /// A search path holding exactly `dirs`
+    pub fn new(dirs: Vec<PathBuf>) -> Self {
+        Self { dirs }
+    }
+
+    /// The directories listed in `LUX_PATH`, followed by `lib` and `tools`
+    pub fn from_env() -> Self {
+        let mut dirs: Vec<PathBuf> = std::env::var_os(LUX_PATH)
+            .map(|value| std::env::split_paths(&value).collect())
+            .unwrap_or_default();
+        dirs.extend([PathBuf::from("lib"), PathBuf::from("tools")]);
+        Self::new(dirs)
+    }
+
+    /// Search `dir` after the directories already listed
+    pub fn push(&mut self, dir: impl Into<PathBuf>) {
+        self.dirs.push(dir.into());
+    }
+
+    pub fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Find the file for module `name`, trying `current_dir` (the importing
+    /// file's directory) before the search directories
+    pub fn resolve(&self, name: &str, current_dir: Option<&Path>) -> Option<PathBuf> {
+        let file = format!("{}.lux", name);
+        current_dir
+            .into_iter()
+            .chain(self.dirs.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(&file))
+            .chain(std::iter::once(PathBuf::from(&file)))
+            .find(|candidate| candidate.exists())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_dir() {
+        assert_eq!(file_dir("scripts/main.lux"), Some("scripts".to_string()));
+        assert_eq!(file_dir("main.lux"), None);
+        assert_eq!(file_dir("<repl>"), None);
+    }
+
+    #[test]
+    fn test_resolve_prefers_current_dir_then_search_order() {
+        let root = std::env::temp_dir().join("lux_search_path");
+        let (here, first, second) = (root.join("here"), root.join("first"), root.join("second"));
+        for dir in [&here, &first, &second] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(first.join("util.lux"), "").unwrap();
+        std::fs::write(second.join("util.lux"), "").unwrap();
+        std::fs::write(second.join("extra.lux"), "").unwrap();
+
+        let search = SearchPath::new(vec![first.clone(), second.clone()]);
+        assert_eq!(search.resolve("util", None), Some(first.join("util.lux")));
+        assert_eq!(search.resolve("extra", Some(&here)), Some(second.join("extra.lux")));
+        assert_eq!(search.resolve("missing", Some(&here)), None);
+
+        std::fs::write(here.join("util.lux"), "").unwrap();
+        assert_eq!(search.resolve("util", Some(&here)), Some(here.join("util.lux")));
+    }
+}
@@ -0,0 +1,575 @@
+//! Constant folding
+//!
+//! This module evaluates operators whose operands are all literals at
+//! compile time, so `2 * 60 * 60` becomes the single literal `7200`.
+//! Anything that could fail at runtime (division by zero, integer overflow)
+//! is left in place so the interpreter still reports it.
+//!
+//! `const` declarations are stricter: their initializer must be a constant
+//! expression built from literals, operators and other consts, and it is
+//! always replaced by the literal it evaluates to. Consts can't be assigned
+//! to, so reads of them can be folded too. Every call is rejected in a
+//! const initializer, including pure builtins such as `len`: the pass runs
+//! before the interpreter exists and doesn't evaluate functions.
+//!
+//! Folding is optional; [`evaluate_consts`] does only the `const` part,
+//! which every program needs, and leaves other expressions as written.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use crate::error::{LuxError, LuxResult, SourceLocation};
+use crate::lexer::Symbol;
+use crate::parser::ast::{Ast, BinaryOp, Expr, Literal, LogicalOp, Stmt, TableKey, UnaryOp};
+
+/// Fold constant expressions throughout the program, reporting `const`
+/// declarations that can't be evaluated at compile time
+pub fn fold_constants(ast: &mut Ast) -> LuxResult<()> {
+    Folder::new(true).fold_block(&mut ast.statements)
+}
+
+/// Evaluate `const` declarations and check that no const is assigned to,
+/// without folding any other expression
+pub fn evaluate_consts(ast: &mut Ast) -> LuxResult<()> {
+    Folder::new(false).fold_block(&mut ast.statements)
+}
+
+/// Tracks the bindings in scope, mirroring where the interpreter opens
+/// scopes: `if` and `while` bodies share their enclosing scope
+struct Folder {
+    /// The value of each const, or `None` for a binding that shadows one
+    scopes: Vec<HashMap<Symbol, Option<Literal>>>,
+    /// Whether expressions outside const initializers are folded
+    fold: bool,
+}
+
+impl Folder {
+    fn new(fold: bool) -> Self {
+        Folder { scopes: vec![HashMap::new()], fold }
+    }
+
+    fn fold_block(&mut self, stmts: &mut [Stmt]) -> LuxResult<()> {
+        for stmt in stmts {
+            self.fold_stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Fold `stmts` in a new scope, after declaring `params` in it
+    fn fold_scoped(&mut self, params: &[Symbol], stmts: &mut [Stmt]) -> LuxResult<()> {
+        self.scopes.push(params.iter().map(|param| (*param, None)).collect());
+        let result = self.fold_block(stmts);
+        self.scopes.pop();
+        result
+    }
+
+    fn declare(&mut self, name: Symbol, value: Option<Literal>) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name, value);
+        }
+    }
+
+    /// The value of the const `name` refers to, if it refers to one
+    fn const_value(&self, name: &Symbol) -> Option<&Literal> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .and_then(Option::as_ref)
+    }
+
+    fn fold_stmt(&mut self, stmt: &mut Stmt) -> LuxResult<()> {
+        match stmt {
+            Stmt::VarDecl { name, initializer, is_const: true, location, .. } => {
+                let Some(init) = initializer else {
+                    return Err(LuxError::semantic_error(
+                        format!("Const '{}' must be initialized", name),
+                        location.clone(),
+                    ));
+                };
+                let value = self.fold_const(*name, init)?;
+                self.declare(*name, Some(value));
+            }
+            Stmt::VarDecl { name, initializer, .. } => {
+                if let Some(init) = initializer {
+                    self.fold_expr(init)?;
+                }
+                self.declare(*name, None);
+            }
+            Stmt::Destructure { names, initializer, .. } => {
+                // Tuples aren't literals, so destructured consts are
+                // folded but not tracked
+                self.fold_expr(initializer)?;
+                for name in names {
+                    self.declare(*name, None);
+                }
+            }
+            // The AST is folded before any function value shares a body
+            Stmt::FunctionDecl { name, params, body, .. } => {
+                self.declare(*name, None);
+                let params: Vec<Symbol> = params.iter().map(|(param, _)| *param).collect();
+                self.fold_scoped(&params, Arc::make_mut(body).as_mut_slice())?;
+            }
+            Stmt::Expression { expr, .. } => self.fold_expr(expr)?,
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.fold_expr(condition)?;
+                self.fold_block(then_branch)?;
+                if let Some(else_stmts) = else_branch {
+                    self.fold_block(else_stmts)?;
+                }
+            }
+            Stmt::While { condition, body, .. } => {
+                self.fold_expr(condition)?;
+                self.fold_block(body)?;
+            }
+            Stmt::For { initializer, condition, increment, body, .. } => {
+                self.scopes.push(HashMap::new());
+                let result = self.fold_for(initializer, condition, increment, body);
+                self.scopes.pop();
+                result?;
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.fold_expr(value)?;
+                }
+            }
+            Stmt::Block { statements, .. } => self.fold_scoped(&[], statements)?,
+            Stmt::Defer { expr, .. } => self.fold_expr(expr)?,
+            Stmt::EnumDecl { name, .. } | Stmt::StructDecl { name, .. } => {
+                self.declare(Symbol::from(name.as_str()), None);
+            }
+            Stmt::Import { .. }
+            | Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::TypeAlias { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn fold_for(
+        &mut self,
+        initializer: &mut Option<Box<Stmt>>,
+        condition: &mut Option<Expr>,
+        increment: &mut Option<Expr>,
+        body: &mut [Stmt],
+    ) -> LuxResult<()> {
+        if let Some(init) = initializer {
+            self.fold_stmt(init)?;
+        }
+        if let Some(cond) = condition {
+            self.fold_expr(cond)?;
+        }
+        if let Some(inc) = increment {
+            self.fold_expr(inc)?;
+        }
+        self.fold_block(body)
+    }
+
+    /// Evaluate the initializer of the const `name`, replacing it with
+    /// the resulting literal
+    fn fold_const(&mut self, name: Symbol, init: &mut Expr) -> LuxResult<Literal> {
+        self.inline_consts(name, init)?;
+        fold_expr_tree(init).map_err(|(why, location)| {
+            LuxError::semantic_error(
+                format!("Const '{}' can't be evaluated at compile time: {}", name, why.reason()),
+                location,
+            )
+        })?;
+        match init {
+            Expr::Literal { value, .. } => Ok(value.clone()),
+            other => Err(LuxError::semantic_error(
+                format!("Const '{}' can't be evaluated at compile time", name),
+                other.span().start,
+            )),
+        }
+    }
+
+    /// Replace reads of consts in a const initializer with their values,
+    /// rejecting anything that isn't a constant expression, including calls
+    fn inline_consts(&self, name: Symbol, expr: &mut Expr) -> LuxResult<()> {
+        match expr {
+            Expr::Literal { .. } => Ok(()),
+            Expr::Variable { name: read, location, span } => match self.const_value(read) {
+                Some(value) => {
+                    *expr = Expr::Literal { value: value.clone(), location: location.clone(), span: span.clone() };
+                    Ok(())
+                }
+                None => Err(LuxError::semantic_error(
+                    format!("Const '{}' can't read '{}', which is not a const", name, read),
+                    location.clone(),
+                )),
+            },
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.inline_consts(name, left)?;
+                self.inline_consts(name, right)
+            }
+            Expr::Unary { operand, .. } => self.inline_consts(name, operand),
+            Expr::Call { .. } | Expr::MethodCall { .. } | Expr::Spawn { .. } | Expr::Await { .. } => {
+                Err(LuxError::semantic_error(
+                    format!("Const '{}' can't call a function in its initializer", name),
+                    expr.span().start,
+                ))
+            }
+            other => Err(LuxError::semantic_error(
+                format!("Const '{}' must be initialized with a constant expression", name),
+                other.span().start,
+            )),
+        }
+    }
+
+    fn fold_expr(&mut self, expr: &mut Expr) -> LuxResult<()> {
+        if let Expr::Assign { target, .. } = expr {
+            if let Expr::Variable { name, location, .. } = target.as_ref() {
+                if self.const_value(name).is_some() {
+                    return Err(LuxError::semantic_error(
+                        format!("Cannot assign to const '{}'", name),
+                        location.clone(),
+                    ));
+                }
+            }
+        }
+        // Fold children first so nested constants collapse bottom-up
+        self.fold_children(expr)?;
+        if self.fold {
+            // Anything that can't be folded is left for the interpreter to report
+            let _ = fold_operator(expr);
+        }
+        Ok(())
+    }
+
+    /// Fold the subexpressions of `expr`
+    fn fold_children(&mut self, expr: &mut Expr) -> LuxResult<()> {
+        match expr {
+            Expr::Literal { .. } | Expr::Variable { .. } => {}
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.fold_expr(left)?;
+                self.fold_expr(right)?;
+            }
+            Expr::Unary { operand, .. } => self.fold_expr(operand)?,
+            Expr::Assign { target, value, .. } => {
+                self.fold_expr(target)?;
+                self.fold_expr(value)?;
+            }
+            Expr::Call { callee, arguments, .. } => {
+                self.fold_expr(callee)?;
+                for arg in arguments {
+                    self.fold_expr(arg)?;
+                }
+            }
+            Expr::MethodCall { receiver, arguments, .. } => {
+                self.fold_expr(receiver)?;
+                for arg in arguments {
+                    self.fold_expr(arg)?;
+                }
+            }
+            Expr::Tuple { elements, .. } => {
+                for element in elements {
+                    self.fold_expr(element)?;
+                }
+            }
+            Expr::Table { fields, .. } | Expr::StructInit { fields, .. } => {
+                for (key, value) in fields {
+                    if let TableKey::Expression(key_expr) = key {
+                        self.fold_expr(key_expr)?;
+                    }
+                    self.fold_expr(value)?;
+                }
+            }
+            Expr::TableAccess { table, key, .. } => {
+                self.fold_expr(table)?;
+                self.fold_expr(key)?;
+            }
+            Expr::Function { params, body, .. } => {
+                let params: Vec<Symbol> = params.iter().map(|(param, _)| *param).collect();
+                self.fold_scoped(&params, Arc::make_mut(body).as_mut_slice())?;
+            }
+            Expr::DoBlock { body, .. } => self.fold_scoped(&[], body)?,
+            Expr::Spawn { call, .. } => self.fold_expr(call)?,
+            Expr::Await { task, .. } => self.fold_expr(task)?,
+        }
+        Ok(())
+    }
+}
+
+/// Why an operator on literals is left for the interpreter
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unfoldable {
+    Overflow,
+    DivisionByZero,
+    /// The result is inf or NaN, which have no literal syntax
+    NotFinite,
+    /// The operator doesn't apply to operands of these types
+    Operands,
+}
+
+impl Unfoldable {
+    fn reason(self) -> &'static str {
+        match self {
+            Unfoldable::Overflow => "integer overflow",
+            Unfoldable::DivisionByZero => "division by zero",
+            Unfoldable::NotFinite => "the result is not a finite number",
+            Unfoldable::Operands => "the operator doesn't apply to these operands",
+        }
+    }
+}
+
+/// Fold an expression made only of literals and operators, bottom-up,
+/// returning why and where an operator couldn't be folded
+fn fold_expr_tree(expr: &mut Expr) -> Result<(), (Unfoldable, SourceLocation)> {
+    match expr {
+        Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+            fold_expr_tree(left)?;
+            fold_expr_tree(right)?;
+        }
+        Expr::Unary { operand, .. } => fold_expr_tree(operand)?,
+        _ => {}
+    }
+    match fold_operator(expr) {
+        Some(Err(why)) => Err((why, expr.location().clone())),
+        _ => Ok(()),
+    }
+}
+
+/// Replace an operator whose operands are literals by its result. Returns
+/// `None` when `expr` isn't such an operator, or why it couldn't be folded.
+fn fold_operator(expr: &mut Expr) -> Option<Result<(), Unfoldable>> {
+    let folded = match expr {
+        Expr::Binary { left, operator, right, .. } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Literal { value: a, .. }, Expr::Literal { value: b, .. }) => fold_binary(a, operator, b),
+            _ => return None,
+        },
+        Expr::Logical { left, operator, right, .. } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Literal { value: a, .. }, Expr::Literal { value: b, .. }) => Ok(fold_logical(a, operator, b)),
+            _ => return None,
+        },
+        Expr::Unary { operator, operand, .. } => match operand.as_ref() {
+            Expr::Literal { value, .. } => fold_unary(operator, value),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(folded.map(|value| {
+        // The literal covers the whole folded expression
+        let span = expr.span();
+        *expr = Expr::Literal { value, location: span.start.clone(), span };
+    }))
+}
+
+/// Evaluate a binary operator on two literals, or say why the result must
+/// be left to the interpreter
+fn fold_binary(left: &Literal, op: &BinaryOp, right: &Literal) -> Result<Literal, Unfoldable> {
+    match (left, right) {
+        (Literal::Integer(a), Literal::Integer(b)) => fold_int(*a, op, *b),
+        (Literal::Float(a), Literal::Float(b)) => fold_float(*a, op, *b),
+        // Mixed int/float operands promote the int to float
+        (Literal::Integer(a), Literal::Float(b)) => fold_float(*a as f64, op, *b),
+        (Literal::Float(a), Literal::Integer(b)) => fold_float(*a, op, *b as f64),
+        (Literal::String(a), Literal::String(b)) => match op {
+            BinaryOp::Add => Ok(Literal::String(format!("{}{}", a, b))),
+            BinaryOp::Equal => Ok(Literal::Boolean(a == b)),
+            BinaryOp::NotEqual => Ok(Literal::Boolean(a != b)),
+            _ => Err(Unfoldable::Operands),
+        },
+        _ => Err(Unfoldable::Operands),
+    }
+}
+
+fn fold_int(a: i64, op: &BinaryOp, b: i64) -> Result<Literal, Unfoldable> {
+    // Checked arithmetic: anything that would overflow or divide by zero
+    // stays in the AST
+    let int = |result: Option<i64>| result.map(Literal::Integer).ok_or(Unfoldable::Overflow);
+    match op {
+        BinaryOp::Add => int(a.checked_add(b)),
+        BinaryOp::Subtract => int(a.checked_sub(b)),
+        BinaryOp::Multiply => int(a.checked_mul(b)),
+        BinaryOp::Divide | BinaryOp::FloorDivide | BinaryOp::Modulo if b == 0 => Err(Unfoldable::DivisionByZero),
+        BinaryOp::Divide => Ok(Literal::Float(a as f64 / b as f64)),
+        BinaryOp::FloorDivide => int(a.checked_div(b).map(|quotient| {
+            if a % b != 0 && (a < 0) != (b < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        })),
+        BinaryOp::Modulo => int(a.checked_rem(b)),
+        BinaryOp::Power => fold_float(a as f64, op, b as f64),
+        BinaryOp::Equal => Ok(Literal::Boolean(a == b)),
+        BinaryOp::NotEqual => Ok(Literal::Boolean(a != b)),
+        BinaryOp::Less => Ok(Literal::Boolean(a < b)),
+        BinaryOp::LessEqual => Ok(Literal::Boolean(a <= b)),
+        BinaryOp::Greater => Ok(Literal::Boolean(a > b)),
+        BinaryOp::GreaterEqual => Ok(Literal::Boolean(a >= b)),
+    }
+}
+
+fn fold_float(a: f64, op: &BinaryOp, b: f64) -> Result<Literal, Unfoldable> {
+    let result = match op {
+        BinaryOp::Add => a + b,
+        BinaryOp::Subtract => a - b,
+        BinaryOp::Multiply => a * b,
+        BinaryOp::Divide | BinaryOp::FloorDivide | BinaryOp::Modulo if b == 0.0 => {
+            return Err(Unfoldable::DivisionByZero)
+        }
+        BinaryOp::Divide => a / b,
+        BinaryOp::FloorDivide => (a / b).floor(),
+        BinaryOp::Modulo => a % b,
+        BinaryOp::Power => a.powf(b),
+        BinaryOp::Equal => return Ok(Literal::Boolean(a == b)),
+        BinaryOp::NotEqual => return Ok(Literal::Boolean(a != b)),
+        BinaryOp::Less => return Ok(Literal::Boolean(a < b)),
+        BinaryOp::LessEqual => return Ok(Literal::Boolean(a <= b)),
+        BinaryOp::Greater => return Ok(Literal::Boolean(a > b)),
+        BinaryOp::GreaterEqual => return Ok(Literal::Boolean(a >= b)),
+    };
+    // inf and NaN have no literal syntax, so keep the original expression
+    if result.is_finite() {
+        Ok(Literal::Float(result))
+    } else {
+        Err(Unfoldable::NotFinite)
+    }
+}
+
+/// Evaluate `and`/`or` on two literals, which returns one of its operands
+fn fold_logical(left: &Literal, op: &LogicalOp, right: &Literal) -> Literal {
+    let truthy = !matches!(left, Literal::Nil | Literal::Boolean(false));
+    match (op, truthy) {
+        (LogicalOp::And, true) | (LogicalOp::Or, false) => right.clone(),
+        _ => left.clone(),
+    }
+}
+
+fn fold_unary(op: &UnaryOp, operand: &Literal) -> Result<Literal, Unfoldable> {
+    match (op, operand) {
+        (UnaryOp::Negate, Literal::Integer(n)) => n.checked_neg().map(Literal::Integer).ok_or(Unfoldable::Overflow),
+        (UnaryOp::Negate, Literal::Float(f)) => Ok(Literal::Float(-f)),
+        (UnaryOp::Not, Literal::Boolean(b)) => Ok(Literal::Boolean(!b)),
+        (UnaryOp::Not, Literal::Nil) => Ok(Literal::Boolean(true)),
+        (UnaryOp::Not, _) => Ok(Literal::Boolean(false)),
+        (UnaryOp::Length, Literal::String(s)) => Ok(Literal::Integer(s.chars().count() as i64)),
+        _ => Err(Unfoldable::Operands),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Parser};
+
+    /// Fold `source` and return the initializer of its first declaration
+    fn folded(source: &str) -> Expr {
+        let tokens = Lexer::new(source, None).tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        fold_constants(&mut ast).unwrap();
+        match ast.statements.remove(0) {
+            Stmt::VarDecl { initializer: Some(init), .. } => init,
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+    }
+
+    /// Fold `source`, returning the error it reports
+    fn fold_error(source: &str) -> String {
+        let tokens = Lexer::new(source, None).with_newlines().tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        fold_constants(&mut ast).unwrap_err().message().to_string()
+    }
+
+    fn literal(expr: &Expr) -> Option<&Literal> {
+        match expr {
+            Expr::Literal { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_constant_tree_collapses_to_literal() {
+        assert_eq!(literal(&folded("local x := 2 * 60 * 60 + (10 - 4) ~/ 2")), Some(&Literal::Integer(7203)));
+        assert_eq!(literal(&folded("local x := 7 / 2")), Some(&Literal::Float(3.5)));
+        assert_eq!(literal(&folded("local x := -7 ~/ 2")), Some(&Literal::Integer(-4)));
+        assert_eq!(literal(&folded("local x := -1.5 * 2")), Some(&Literal::Float(-3.0)));
+        assert_eq!(literal(&folded("local x := \"a\" + \"b\" == \"ab\"")), Some(&Literal::Boolean(true)));
+        assert_eq!(literal(&folded("local x := not (1 < 2)")), Some(&Literal::Boolean(false)));
+    }
+
+    #[test]
+    fn test_division_by_zero_is_left_intact() {
+        assert!(matches!(folded("local x := 1 / 0"), Expr::Binary { operator: BinaryOp::Divide, .. }));
+        assert!(matches!(folded("local x := 1.0 % 0.0"), Expr::Binary { operator: BinaryOp::Modulo, .. }));
+        // The divisor is folded even though the division itself is not
+        match folded("local x := 1 / (2 - 2)") {
+            Expr::Binary { right, .. } => assert_eq!(literal(&right), Some(&Literal::Integer(0))),
+            other => panic!("expected a division, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_overflow_is_left_intact() {
+        assert!(matches!(folded("local x := 9223372036854775807 + 1"), Expr::Binary { .. }));
+        assert!(literal(&folded("local x := 9223372036854775806 + 1")).is_some());
+    }
+
+    #[test]
+    fn test_non_constant_operands_are_kept() {
+        assert!(matches!(folded("local x := y * (2 + 3)"), Expr::Binary { .. }));
+    }
+
+    #[test]
+    fn test_const_initializer_is_evaluated() {
+        let tokens = Lexer::new("const a := 6\nconst b := a * 7 > 40 and \"big\"", None)
+            .with_newlines()
+            .tokenize()
+            .unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        fold_constants(&mut ast).unwrap();
+        match &ast.statements[1] {
+            Stmt::VarDecl { initializer: Some(init), .. } => {
+                assert_eq!(literal(init), Some(&Literal::String("big".to_string())));
+            }
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_non_constant_const_initializer_is_rejected() {
+        assert_eq!(fold_error("local x := 1\nconst y := x + 1"), "Const 'y' can't read 'x', which is not a const");
+        assert_eq!(fold_error("const y := len(\"abc\")"), "Const 'y' can't call a function in its initializer");
+        assert_eq!(fold_error("const t := {1, 2}"), "Const 't' must be initialized with a constant expression");
+        assert_eq!(fold_error("const z := 1 / 0"), "Const 'z' can't be evaluated at compile time: division by zero");
+        assert_eq!(
+            fold_error("const big := 9223372036854775807 + 1"),
+            "Const 'big' can't be evaluated at compile time: integer overflow"
+        );
+        assert_eq!(
+            fold_error("const s := \"a\" - 1"),
+            "Const 's' can't be evaluated at compile time: the operator doesn't apply to these operands"
+        );
+        // A parameter shadows the const of the same name
+        assert_eq!(
+            fold_error("const n := 1\nfn f(n) { const m := n }"),
+            "Const 'm' can't read 'n', which is not a const"
+        );
+        assert_eq!(fold_error("const n := 1\nfn f() { n = 2 }"), "Cannot assign to const 'n'");
+
+        // The error points at the operator that couldn't be evaluated
+        let tokens = Lexer::new("const big := 9223372036854775807 - 1 + 2", None).tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        let err = fold_constants(&mut ast).unwrap_err();
+        assert_eq!(err.location().map(|l| (l.line, l.column)), Some((1, 38)));
+    }
+
+    #[test]
+    fn test_evaluate_consts_leaves_other_expressions() {
+        let tokens = Lexer::new("const a := 2 * 3\nlocal b := 2 * 3", None).with_newlines().tokenize().unwrap();
+        let mut ast = Parser::new(tokens).parse().unwrap();
+        evaluate_consts(&mut ast).unwrap();
+        let initializers: Vec<&Expr> = ast
+            .statements
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Stmt::VarDecl { initializer: Some(init), .. } => Some(init),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(literal(initializers[0]), Some(&Literal::Integer(6)));
+        assert!(matches!(initializers[1], Expr::Binary { operator: BinaryOp::Multiply, .. }));
+    }
+}
@@ -0,0 +1,8 @@
+//! AST optimization module
+//!
+//! This module holds optional passes that rewrite a checked AST into an
+//! equivalent, cheaper one before it is interpreted.
+
+pub mod fold;
+
+pub use fold::{evaluate_consts, fold_constants};
@@ -2,7 +2,9 @@
 //!
 //! This module defines the AST node types for the Lux language.
 
-use crate::error::SourceLocation;
+use std::sync::Arc;
+use crate::error::{SourceLocation, Span};
+use crate::lexer::Symbol;
 
 /// Root AST node representing a complete program
 #[derive(Debug, Clone, PartialEq)]
@@ -13,27 +15,38 @@ pub struct Ast {
 /// Statement node
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
-    /// Import statement: import "path"
+    /// Import statement: import "path", import "path" as name,
+    /// or import { a, b } from "path"
     Import {
         path: String,
+        kind: ImportKind,
         location: SourceLocation,
     },
 
     /// Variable declaration: local x: int = 42
     VarDecl {
-        name: String,
+        name: Symbol,
         type_annotation: Option<Type>,
         initializer: Option<Expr>,
         is_const: bool,
         location: SourceLocation,
     },
 
+    /// Destructuring declaration: local a, b = <tuple>
+    Destructure {
+        names: Vec<Symbol>,
+        initializer: Expr,
+        is_const: bool,
+        location: SourceLocation,
+    },
+
     /// Function declaration
     FunctionDecl {
-        name: String,
-        params: Vec<(String, Type)>,
+        name: Symbol,
+        params: Vec<(Symbol, Type)>,
         return_type: Option<Type>,
-        body: Vec<Stmt>,
+        /// Shared with the function values made from this declaration
+        body: Arc<Vec<Stmt>>,
         is_async: bool,
         location: SourceLocation,
     },
@@ -84,26 +97,66 @@ pub enum Stmt {
         location: SourceLocation,
     },
 
+    /// Defer statement: `defer expr` evaluates `expr` when the enclosing
+    /// function or block exits; deferred expressions run in reverse order
+    Defer {
+        expr: Expr,
+        location: SourceLocation,
+    },
+
     /// Block statement
     Block {
         statements: Vec<Stmt>,
         location: SourceLocation,
     },
+
+    /// Type alias declaration: type Name = <type>
+    TypeAlias {
+        name: String,
+        target: Type,
+        location: SourceLocation,
+    },
+
+    /// Enum declaration: enum Name { A, B = 5, C }
+    ///
+    /// Member values are resolved by the parser: each member without an
+    /// explicit value is one more than the member before it, starting at 0.
+    EnumDecl {
+        name: String,
+        members: Vec<(String, i64)>,
+        location: SourceLocation,
+    },
+
+    /// Struct declaration: struct Name { field: T, ... }
+    ///
+    /// Declares `Name` as a shape type and enables `Name { field = value }`
+    /// constructors, which the type checker holds to exactly these fields.
+    StructDecl {
+        name: String,
+        fields: Vec<(String, Type)>,
+        location: SourceLocation,
+    },
 }
 
 /// Expression node
+///
+/// Each expression has a `location` that errors about it point at, such as
+/// its operator, and a `span` covering its source text from its first token
+/// to its last, parentheses included.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     /// Literal value
     Literal {
         value: Literal,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Variable reference
     Variable {
-        name: String,
+        name: Symbol,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Binary operation
@@ -112,6 +165,7 @@ pub enum Expr {
         operator: BinaryOp,
         right: Box<Expr>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Unary operation
@@ -119,6 +173,7 @@ pub enum Expr {
         operator: UnaryOp,
         operand: Box<Expr>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Assignment (variable or table element)
@@ -126,6 +181,7 @@ pub enum Expr {
         target: Box<Expr>,  // Can be Variable or TableAccess
         value: Box<Expr>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Function call
@@ -133,12 +189,28 @@ pub enum Expr {
         callee: Box<Expr>,
         arguments: Vec<Expr>,
         location: SourceLocation,
+        span: Span,
+    },
+
+    /// Method call: receiver:method(args)
+    ///
+    /// Sugar for `receiver.method(receiver, args)`, except that the receiver
+    /// is only evaluated once. It stays a node of its own because the AST
+    /// has no way to bind a temporary inside an expression, which a
+    /// desugared `Call` would need to avoid evaluating the receiver twice.
+    MethodCall {
+        receiver: Box<Expr>,
+        method: String,
+        arguments: Vec<Expr>,
+        location: SourceLocation,
+        span: Span,
     },
 
     /// Table literal
     Table {
         fields: Vec<(TableKey, Expr)>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Table access: table.field or table[key]
@@ -146,6 +218,7 @@ pub enum Expr {
         table: Box<Expr>,
         key: Box<Expr>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Logical operation (and, or)
@@ -154,29 +227,68 @@ pub enum Expr {
         operator: LogicalOp,
         right: Box<Expr>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Function expression (anonymous function)
     Function {
-        params: Vec<(String, Type)>,
+        params: Vec<(Symbol, Type)>,
         return_type: Option<Type>,
+        /// Shared with the function values made from this expression
+        body: Arc<Vec<Stmt>>,
+        location: SourceLocation,
+        span: Span,
+    },
+
+    /// Tuple literal: `(a, b)`; a single element needs a trailing comma: `(a,)`
+    Tuple {
+        elements: Vec<Expr>,
+        location: SourceLocation,
+        span: Span,
+    },
+
+    /// Struct constructor: `Name { field = value, ... }`; a plain table at runtime
+    StructInit {
+        name: String,
+        fields: Vec<(TableKey, Expr)>,
+        location: SourceLocation,
+        span: Span,
+    },
+
+    /// Do block: `do { ... }` runs its statements in a new scope and
+    /// evaluates to the value of its final expression statement, or nil
+    DoBlock {
         body: Vec<Stmt>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Spawn expression: spawn function_call()
     Spawn {
         call: Box<Expr>,
         location: SourceLocation,
+        span: Span,
     },
 
     /// Await expression: await task_id
     Await {
         task: Box<Expr>,
         location: SourceLocation,
+        span: Span,
     },
 }
 
+/// Which of a module's top-level bindings an import brings into scope
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportKind {
+    /// Every binding, directly in the importing scope
+    All,
+    /// Every binding, as fields of a table with this name
+    Alias(String),
+    /// Only the named bindings
+    Names(Vec<String>),
+}
+
 /// Table key (for table literals)
 #[derive(Debug, Clone, PartialEq)]
 pub enum TableKey {
@@ -190,8 +302,13 @@ pub enum BinaryOp {
     Add,
     Subtract,
     Multiply,
+    /// `/`: always produces a float
     Divide,
+    /// `~/`: division rounded down; int for two ints
+    FloorDivide,
     Modulo,
+    /// `^`: exponentiation, always produces a float
+    Power,
     Equal,
     NotEqual,
     Less,
@@ -236,10 +353,32 @@ pub enum Type {
     Bool,
     Nil,
     Table,
+    /// Any value; compatible with every other type
+    Any,
     Function {
         params: Vec<Type>,
         return_type: Box<Type>,
     },
     Pointer(Box<Type>),
+    /// Array of a single element type: `[]T`
+    Array(Box<Type>),
+    /// Table with typed keys and values: `map[K]V`
+    Map {
+        key: Box<Type>,
+        value: Box<Type>,
+    },
+    /// Table with a fixed set of typed fields: `{ name: string, age: int }`
+    Shape(Vec<(String, Type)>),
+    /// Reference to a declared type such as an alias, resolved by the type checker
+    Named(String),
+    /// Member of the named enum; an int at runtime
+    Enum(String),
+    /// Fixed-length sequence of typed elements: `(int, string)`
+    Tuple(Vec<Type>),
+    /// The inner type or nil: `int?`
+    Optional(Box<Type>),
+    /// Any number of further arguments of the inner type; only valid as the
+    /// last parameter of a builtin's function type
+    Variadic(Box<Type>),
 }
 
@@ -3,8 +3,11 @@
 //! This module handles parsing tokens into an Abstract Syntax Tree (AST).
 
 pub mod ast;
+#[allow(clippy::module_inception)]
 pub mod parser;
+pub mod printer;
 
 pub use ast::{Ast, Expr, Stmt, Type};
 pub use parser::Parser;
+pub use printer::AstPrinter;
 
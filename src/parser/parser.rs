@@ -2,22 +2,46 @@
 //!
 //! This module implements the parser for the Lux language.
 
-use crate::error::{LuxError, LuxResult, SourceLocation};
-use crate::lexer::{Token, TokenType, Keyword, Literal as TokenLiteral};
+use std::collections::HashSet;
+use std::sync::Arc;
+use crate::error::{LuxError, LuxResult, SourceLocation, Span};
+use crate::lexer::{Token, TokenType, Keyword, Literal as TokenLiteral, Symbol};
 use super::ast::*;
 
 /// Parser for Lux source code
 pub struct Parser {
     tokens: Vec<Token>,
+    /// Whether each token is the first on its line, known when the lexer
+    /// emitted `Newline` tokens; `(` there starts a new statement, not a call
+    line_starts: Vec<bool>,
     current: usize,
+    /// Structs declared so far; `Name {` starts a constructor only for these,
+    /// so `if ready { ... }` still parses as an if statement
+    structs: HashSet<String>,
 }
 
 impl Parser {
     /// Create a new parser from tokens
     pub fn new(tokens: Vec<Token>) -> Self {
+        let mut line_starts = Vec::with_capacity(tokens.len());
+        let mut after_newline = false;
+        let tokens = tokens
+            .into_iter()
+            .filter(|token| {
+                if token.token_type == TokenType::Newline {
+                    after_newline = true;
+                    return false;
+                }
+                line_starts.push(std::mem::take(&mut after_newline));
+                true
+            })
+            .collect();
+
         Self {
             tokens,
+            line_starts,
             current: 0,
+            structs: HashSet::new(),
         }
     }
 
@@ -27,22 +51,93 @@ impl Parser {
 
         while !self.is_at_end() {
             statements.push(self.declaration()?);
+            // Statements may optionally be separated by ';'
+            self.match_token(TokenType::Semicolon);
         }
 
         Ok(Ast { statements })
     }
 
+    /// Parse tokens into an AST, reporting every syntax error instead of
+    /// stopping at the first
+    ///
+    /// After an error the parser skips ahead to the next statement boundary:
+    /// a token that starts a line (when the lexer emitted newlines), a
+    /// statement keyword such as `local`, `fn` or `if`, or a brace. Returns
+    /// the statements that parsed along with the errors, in source order.
+    pub fn parse_all(&mut self) -> (Ast, Vec<LuxError>) {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+
+        while !self.is_at_end() {
+            let start = self.current;
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => {
+                    errors.push(error);
+                    self.synchronize(start);
+                }
+            }
+            self.match_token(TokenType::Semicolon);
+        }
+
+        (Ast { statements }, errors)
+    }
+
+    /// Skip to the next statement boundary after a parse error in the
+    /// statement that began at token `start`
+    fn synchronize(&mut self, start: usize) {
+        // Always make progress, even if the statement failed on its first token
+        if self.current == start {
+            self.advance();
+        }
+        while !self.is_at_end() {
+            if self.line_starts[self.current] || self.previous().token_type == TokenType::Semicolon {
+                return;
+            }
+            match &self.peek().token_type {
+                TokenType::Keyword(
+                    Keyword::Local
+                    | Keyword::Const
+                    | Keyword::Fn
+                    | Keyword::If
+                    | Keyword::While
+                    | Keyword::For
+                    | Keyword::Return
+                    | Keyword::Import,
+                )
+                | TokenType::LeftBrace
+                | TokenType::RightBrace => return,
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+    }
+
     // ===== Declarations =====
 
     fn declaration(&mut self) -> LuxResult<Stmt> {
         if self.match_keyword(Keyword::Import) {
             self.import_declaration()
         } else if self.match_keyword(Keyword::Local) {
-            self.var_declaration(false)
+            // `local fn name() {}` declares a function in the current scope,
+            // just like `local name := fn() {}`
+            if self.check_keyword(Keyword::Fn) || self.check_keyword(Keyword::Async) {
+                self.function_declaration()
+            } else {
+                self.var_declaration(false)
+            }
         } else if self.match_keyword(Keyword::Const) {
             self.var_declaration(true)
         } else if self.check_keyword(Keyword::Fn) || self.check_keyword(Keyword::Async) {
             self.function_declaration()
+        } else if self.match_keyword(Keyword::Type) {
+            self.type_alias_declaration()
+        } else if self.match_keyword(Keyword::Enum) {
+            self.enum_declaration()
+        } else if self.match_keyword(Keyword::Struct) {
+            self.struct_declaration()
         } else {
             self.statement()
         }
@@ -51,17 +146,41 @@ impl Parser {
     fn import_declaration(&mut self) -> LuxResult<Stmt> {
         let location = self.previous().location.clone();
 
-        // Expect a string literal for the path
-        if let TokenType::Literal(TokenLiteral::String(_)) = &self.peek().token_type {
-            let token = self.advance();
-            if let TokenType::Literal(TokenLiteral::String(path)) = &token.token_type {
-                Ok(Stmt::Import {
-                    path: path.clone(),
-                    location
-                })
-            } else {
-                unreachable!()
+        // import { a, b } from "path"
+        if self.match_token(TokenType::LeftBrace) {
+            let mut names = Vec::new();
+            loop {
+                names.push(self.consume_identifier("Expected name to import")?);
+                if !self.match_token(TokenType::Comma) || self.check(TokenType::RightBrace) {
+                    break;
+                }
             }
+            self.consume(TokenType::RightBrace, "Expected '}' after imported names")?;
+            if !self.match_word("from") {
+                return Err(LuxError::parse_error(
+                    "Expected 'from' after imported names".to_string(),
+                    self.peek().location.clone(),
+                ));
+            }
+            let path = self.import_path()?;
+            return Ok(Stmt::Import { path, kind: ImportKind::Names(names), location });
+        }
+
+        let path = self.import_path()?;
+        // import "path" as name
+        let kind = if self.match_word("as") {
+            ImportKind::Alias(self.consume_identifier("Expected name after 'as'")?)
+        } else {
+            ImportKind::All
+        };
+        Ok(Stmt::Import { path, kind, location })
+    }
+
+    fn import_path(&mut self) -> LuxResult<String> {
+        if let TokenType::Literal(TokenLiteral::String(path)) = &self.peek().token_type {
+            let path = path.clone();
+            self.advance();
+            Ok(path)
         } else {
             Err(LuxError::parse_error(
                 "Expected string path after 'import'".to_string(),
@@ -72,7 +191,26 @@ impl Parser {
 
     fn var_declaration(&mut self, is_const: bool) -> LuxResult<Stmt> {
         let location = self.previous().location.clone();
-        let name = self.consume_identifier("Expected variable name")?;
+        let name = self.consume_symbol("Expected variable name")?;
+
+        // `local a, b = value` destructures a tuple
+        if self.match_token(TokenType::Comma) {
+            let mut names = vec![name];
+            loop {
+                names.push(self.consume_symbol("Expected variable name")?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            if !self.match_token(TokenType::Assign) && !self.match_token(TokenType::ColonAssign) {
+                return Err(LuxError::parse_error(
+                    "Expected '=' after variable names".to_string(),
+                    self.peek().location.clone(),
+                ));
+            }
+            let initializer = self.expression()?;
+            return Ok(Stmt::Destructure { names, initializer, is_const, location });
+        }
 
         let type_annotation = if self.match_token(TokenType::Colon) {
             Some(self.parse_type()?)
@@ -95,36 +233,103 @@ impl Parser {
         })
     }
 
-    fn function_declaration(&mut self) -> LuxResult<Stmt> {
-        let is_async = self.match_keyword(Keyword::Async);
-        self.consume_keyword(Keyword::Fn, "Expected 'fn'")?;
+    fn type_alias_declaration(&mut self) -> LuxResult<Stmt> {
+        let location = self.previous().location.clone();
+        let name = self.consume_identifier("Expected type name after 'type'")?;
+        self.consume(TokenType::Assign, "Expected '=' after type name")?;
+        let target = self.parse_type()?;
 
+        Ok(Stmt::TypeAlias { name, target, location })
+    }
+
+    fn enum_declaration(&mut self) -> LuxResult<Stmt> {
         let location = self.previous().location.clone();
-        let name = self.consume_identifier("Expected function name")?;
+        let name = self.consume_identifier("Expected enum name after 'enum'")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' after enum name")?;
+
+        let mut members: Vec<(String, i64)> = Vec::new();
+        let mut next_value = 0;
+        while !self.check(TokenType::RightBrace) {
+            let member_location = self.peek().location.clone();
+            let member = self.consume_identifier("Expected enum member name")?;
+            if members.iter().any(|(existing, _)| *existing == member) {
+                return Err(LuxError::parse_error(
+                    format!("Duplicate enum member '{}'", member),
+                    member_location,
+                ));
+            }
 
-        self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
+            let value = if self.match_token(TokenType::Assign) {
+                self.enum_value()?
+            } else {
+                next_value
+            };
+            members.push((member, value));
+            next_value = value.wrapping_add(1);
 
-        let mut params = Vec::new();
-        if !self.check(TokenType::RightParen) {
-            loop {
-                let param_name = self.consume_identifier("Expected parameter name")?;
+            if !self.match_token(TokenType::Comma) {
+                break;
+            }
+        }
 
-                // Type annotation is optional
-                let param_type = if self.match_token(TokenType::Colon) {
-                    self.parse_type()?
-                } else {
-                    Type::Nil // Use Nil to represent "any" type
-                };
+        self.consume(TokenType::RightBrace, "Expected '}' after enum members")?;
+        Ok(Stmt::EnumDecl { name, members, location })
+    }
 
-                params.push((param_name, param_type));
+    fn struct_declaration(&mut self) -> LuxResult<Stmt> {
+        let location = self.previous().location.clone();
+        let name = self.consume_identifier("Expected struct name after 'struct'")?;
+        self.consume(TokenType::LeftBrace, "Expected '{' after struct name")?;
+
+        let mut fields: Vec<(String, Type)> = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let field_location = self.peek().location.clone();
+            let field = self.consume_identifier("Expected field name in struct")?;
+            if fields.iter().any(|(existing, _)| *existing == field) {
+                return Err(LuxError::parse_error(
+                    format!("Duplicate struct field '{}'", field),
+                    field_location,
+                ));
+            }
+            self.consume(TokenType::Colon, "Expected ':' after struct field name")?;
+            fields.push((field, self.parse_type()?));
 
-                if !self.match_token(TokenType::Comma) {
-                    break;
-                }
+            if !self.match_token(TokenType::Comma) {
+                break;
             }
         }
 
-        self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+        self.consume(TokenType::RightBrace, "Expected '}' after struct fields")?;
+        self.structs.insert(name.clone());
+        Ok(Stmt::StructDecl { name, fields, location })
+    }
+
+    /// Parse the explicit value of an enum member: an optionally negated integer
+    fn enum_value(&mut self) -> LuxResult<i64> {
+        let negative = self.match_token(TokenType::Minus);
+        match &self.peek().token_type {
+            TokenType::Literal(TokenLiteral::Integer(n)) => {
+                let n = *n;
+                self.advance();
+                Ok(if negative { -n } else { n })
+            }
+            _ => Err(LuxError::parse_error(
+                "Expected an integer value for enum member".to_string(),
+                self.peek().location.clone(),
+            )),
+        }
+    }
+
+    fn function_declaration(&mut self) -> LuxResult<Stmt> {
+        let is_async = self.match_keyword(Keyword::Async);
+        self.consume_keyword(Keyword::Fn, "Expected 'fn'")?;
+
+        let location = self.previous().location.clone();
+        let name = self.consume_symbol("Expected function name")?;
+
+        self.consume(TokenType::LeftParen, "Expected '(' after function name")?;
+
+        let params = self.parameters()?;
 
         let return_type = if self.match_token(TokenType::Arrow) {
             Some(self.parse_type()?)
@@ -139,12 +344,39 @@ impl Parser {
             name,
             params,
             return_type,
-            body,
+            body: Arc::new(body),
             is_async,
             location,
         })
     }
 
+    /// Parse a parameter list after the opening '(' up to and including ')'
+    fn parameters(&mut self) -> LuxResult<Vec<(Symbol, Type)>> {
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                let param_name = self.consume_symbol("Expected parameter name")?;
+
+                // Type annotation is optional
+                let param_type = if self.match_token(TokenType::Colon) {
+                    self.parse_type()?
+                } else {
+                    Type::Any
+                };
+
+                params.push((param_name, param_type));
+
+                // A single trailing comma is allowed before ')'
+                if !self.match_token(TokenType::Comma) || self.check(TokenType::RightParen) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+        Ok(params)
+    }
+
     // ===== Statements =====
 
     fn statement(&mut self) -> LuxResult<Stmt> {
@@ -164,6 +396,10 @@ impl Parser {
             Ok(Stmt::Continue {
                 location: self.previous().location.clone(),
             })
+        } else if self.match_keyword(Keyword::Defer) {
+            let location = self.previous().location.clone();
+            let expr = self.expression()?;
+            Ok(Stmt::Defer { expr, location })
         } else if self.match_token(TokenType::LeftBrace) {
             let location = self.previous().location.clone();
             let statements = self.block_statements()?;
@@ -279,6 +515,7 @@ impl Parser {
 
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
             statements.push(self.declaration()?);
+            self.match_token(TokenType::Semicolon);
         }
 
         self.consume(TokenType::RightBrace, "Expected '}' after block")?;
@@ -297,14 +534,18 @@ impl Parser {
         if self.match_token(TokenType::Assign) {
             let location = self.previous().location.clone();
             let value = Box::new(self.assignment()?);
+            let span = self.span_from(&expr.span().start);
 
             // Check if the target is a valid assignment target
             match &expr {
-                Expr::Variable { .. } | Expr::TableAccess { .. } => {
+                Expr::Variable { .. }
+                | Expr::TableAccess { .. }
+                | Expr::Unary { operator: UnaryOp::Dereference, .. } => {
                     return Ok(Expr::Assign {
                         target: Box::new(expr),
                         value,
                         location,
+                        span,
                     });
                 }
                 _ => {
@@ -325,11 +566,13 @@ impl Parser {
         while self.match_keyword(Keyword::Or) {
             let location = self.previous().location.clone();
             let right = Box::new(self.logical_and()?);
+            let span = self.span_from(&expr.span().start);
             expr = Expr::Logical {
                 left: Box::new(expr),
                 operator: LogicalOp::Or,
                 right,
                 location,
+                span,
             };
         }
 
@@ -342,11 +585,13 @@ impl Parser {
         while self.match_keyword(Keyword::And) {
             let location = self.previous().location.clone();
             let right = Box::new(self.equality()?);
+            let span = self.span_from(&expr.span().start);
             expr = Expr::Logical {
                 left: Box::new(expr),
                 operator: LogicalOp::And,
                 right,
                 location,
+                span,
             };
         }
 
@@ -364,11 +609,13 @@ impl Parser {
                 _ => unreachable!(),
             };
             let right = Box::new(self.comparison()?);
+            let span = self.span_from(&expr.span().start);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
                 right,
                 location,
+                span,
             };
         }
 
@@ -393,11 +640,13 @@ impl Parser {
                 _ => unreachable!(),
             };
             let right = Box::new(self.term()?);
+            let span = self.span_from(&expr.span().start);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
                 right,
                 location,
+                span,
             };
         }
 
@@ -415,11 +664,13 @@ impl Parser {
                 _ => unreachable!(),
             };
             let right = Box::new(self.factor()?);
+            let span = self.span_from(&expr.span().start);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
                 right,
                 location,
+                span,
             };
         }
 
@@ -429,20 +680,25 @@ impl Parser {
     fn factor(&mut self) -> LuxResult<Expr> {
         let mut expr = self.unary()?;
 
-        while self.match_tokens(&[TokenType::Star, TokenType::Slash, TokenType::Percent]) {
+        while !self.starts_pointer_assignment()
+            && self.match_tokens(&[TokenType::Star, TokenType::Slash, TokenType::TildeSlash, TokenType::Percent])
+        {
             let location = self.previous().location.clone();
             let operator = match &self.previous().token_type {
                 TokenType::Star => BinaryOp::Multiply,
                 TokenType::Slash => BinaryOp::Divide,
+                TokenType::TildeSlash => BinaryOp::FloorDivide,
                 TokenType::Percent => BinaryOp::Modulo,
                 _ => unreachable!(),
             };
             let right = Box::new(self.unary()?);
+            let span = self.span_from(&expr.span().start);
             expr = Expr::Binary {
                 left: Box::new(expr),
                 operator,
                 right,
                 location,
+                span,
             };
         }
 
@@ -464,38 +720,79 @@ impl Parser {
             return Ok(Expr::Unary {
                 operator,
                 operand,
+                span: self.span_from(&location),
                 location,
             });
         }
 
-        self.call()
+        self.power()
+    }
+
+    /// `^` binds tighter than a unary operator on its left and is
+    /// right-associative, so `-2 ^ 2` is `-(2 ^ 2)` and `2 ^ -3` is `2 ^ (-3)`
+    fn power(&mut self) -> LuxResult<Expr> {
+        let base = self.call()?;
+
+        if self.match_token(TokenType::Caret) {
+            let location = self.previous().location.clone();
+            let exponent = Box::new(self.unary()?);
+            let span = self.span_from(&base.span().start);
+            return Ok(Expr::Binary {
+                left: Box::new(base),
+                operator: BinaryOp::Power,
+                right: exponent,
+                location,
+                span,
+            });
+        }
+
+        Ok(base)
     }
 
     fn call(&mut self) -> LuxResult<Expr> {
         let mut expr = self.primary()?;
 
         loop {
-            if self.match_token(TokenType::LeftParen) {
+            // Like Lua, `f` followed by `(...)` on the next line is two statements
+            if !self.line_starts[self.current] && self.match_token(TokenType::LeftParen) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_token(TokenType::Colon) {
+                let method = self.consume_identifier("Expected method name after ':'")?;
+                self.consume(TokenType::LeftParen, "Expected '(' after method name")?;
+                let location = self.previous().location.clone();
+                let arguments = self.arguments()?;
+                let span = self.span_from(&expr.span().start);
+                expr = Expr::MethodCall {
+                    receiver: Box::new(expr),
+                    method,
+                    arguments,
+                    location,
+                    span,
+                };
             } else if self.match_token(TokenType::Dot) {
                 let location = self.previous().location.clone();
-                let field = self.consume_identifier("Expected property name after '.'")?;
+                let field = self.consume_property_name()?;
+                let span = self.span_from(&expr.span().start);
                 expr = Expr::TableAccess {
                     table: Box::new(expr),
                     key: Box::new(Expr::Literal {
                         value: Literal::String(field),
                         location: location.clone(),
+                        span: self.previous().span(),
                     }),
                     location,
+                    span,
                 };
             } else if self.match_token(TokenType::LeftBracket) {
                 let location = self.previous().location.clone();
                 let key = Box::new(self.expression()?);
                 self.consume(TokenType::RightBracket, "Expected ']' after table index")?;
+                let span = self.span_from(&expr.span().start);
                 expr = Expr::TableAccess {
                     table: Box::new(expr),
                     key,
                     location,
+                    span,
                 };
             } else {
                 break;
@@ -507,24 +804,33 @@ impl Parser {
 
     fn finish_call(&mut self, callee: Expr) -> LuxResult<Expr> {
         let location = self.previous().location.clone();
+        let arguments = self.arguments()?;
+        let span = self.span_from(&callee.span().start);
+
+        Ok(Expr::Call {
+            callee: Box::new(callee),
+            arguments,
+            location,
+            span,
+        })
+    }
+
+    /// Parse call arguments after the opening '(' up to and including ')'
+    fn arguments(&mut self) -> LuxResult<Vec<Expr>> {
         let mut arguments = Vec::new();
 
         if !self.check(TokenType::RightParen) {
             loop {
                 arguments.push(self.expression()?);
-                if !self.match_token(TokenType::Comma) {
+                // A single trailing comma is allowed before ')'
+                if !self.match_token(TokenType::Comma) || self.check(TokenType::RightParen) {
                     break;
                 }
             }
         }
 
         self.consume(TokenType::RightParen, "Expected ')' after arguments")?;
-
-        Ok(Expr::Call {
-            callee: Box::new(callee),
-            arguments,
-            location,
-        })
+        Ok(arguments)
     }
 
     fn primary(&mut self) -> LuxResult<Expr> {
@@ -538,13 +844,14 @@ impl Parser {
                 TokenLiteral::String(s) => Literal::String(s.clone()),
             };
             self.advance();
-            return Ok(Expr::Literal { value, location });
+            return Ok(Expr::Literal { value, span: self.span_from(&location), location });
         }
 
         // Boolean literals
         if self.match_keyword(Keyword::True) {
             return Ok(Expr::Literal {
                 value: Literal::Boolean(true),
+                span: self.span_from(&location),
                 location,
             });
         }
@@ -552,6 +859,7 @@ impl Parser {
         if self.match_keyword(Keyword::False) {
             return Ok(Expr::Literal {
                 value: Literal::Boolean(false),
+                span: self.span_from(&location),
                 location,
             });
         }
@@ -560,20 +868,41 @@ impl Parser {
         if self.match_keyword(Keyword::Nil) {
             return Ok(Expr::Literal {
                 value: Literal::Nil,
+                span: self.span_from(&location),
                 location,
             });
         }
 
         // Identifiers
-        if self.check(TokenType::Identifier) {
-            let name = self.advance().lexeme.clone();
-            return Ok(Expr::Variable { name, location });
+        if let TokenType::Identifier(name) = self.peek().token_type {
+            self.advance();
+            if self.structs.contains(name.as_str()) && self.match_token(TokenType::LeftBrace) {
+                return match self.table_literal(location)? {
+                    Expr::Table { fields, location, span } => {
+                        Ok(Expr::StructInit { name: name.to_string(), fields, location, span })
+                    }
+                    _ => unreachable!(),
+                };
+            }
+            return Ok(Expr::Variable { name, span: self.span_from(&location), location });
         }
 
-        // Parenthesized expression
+        // Parenthesized expression, or a tuple when a comma follows
         if self.match_token(TokenType::LeftParen) {
-            let expr = self.expression()?;
+            let mut expr = self.expression()?;
+            if self.match_token(TokenType::Comma) {
+                let mut elements = vec![expr];
+                while !self.check(TokenType::RightParen) {
+                    elements.push(self.expression()?);
+                    if !self.match_token(TokenType::Comma) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::RightParen, "Expected ')' after tuple elements")?;
+                return Ok(Expr::Tuple { elements, span: self.span_from(&location), location });
+            }
             self.consume(TokenType::RightParen, "Expected ')' after expression")?;
+            expr.set_span(self.span_from(&location));
             return Ok(expr);
         }
 
@@ -587,16 +916,23 @@ impl Parser {
             return self.function_expression(location);
         }
 
+        // Do block expression
+        if self.match_keyword(Keyword::Do) {
+            self.consume(TokenType::LeftBrace, "Expected '{' after 'do'")?;
+            let body = self.block_statements()?;
+            return Ok(Expr::DoBlock { body, span: self.span_from(&location), location });
+        }
+
         // Spawn expression
         if self.match_keyword(Keyword::Spawn) {
             let call = Box::new(self.unary()?);
-            return Ok(Expr::Spawn { call, location });
+            return Ok(Expr::Spawn { call, span: self.span_from(&location), location });
         }
 
         // Await expression
         if self.match_keyword(Keyword::Await) {
             let task = Box::new(self.unary()?);
-            return Ok(Expr::Await { task, location });
+            return Ok(Expr::Await { task, span: self.span_from(&location), location });
         }
 
         Err(LuxError::parse_error(
@@ -608,27 +944,7 @@ impl Parser {
     fn function_expression(&mut self, location: SourceLocation) -> LuxResult<Expr> {
         self.consume(TokenType::LeftParen, "Expected '(' after 'fn'")?;
 
-        let mut params = Vec::new();
-        if !self.check(TokenType::RightParen) {
-            loop {
-                let param_name = self.consume_identifier("Expected parameter name")?;
-
-                // Type annotation is optional
-                let param_type = if self.match_token(TokenType::Colon) {
-                    self.parse_type()?
-                } else {
-                    Type::Nil // Use Nil to represent "any" type
-                };
-
-                params.push((param_name, param_type));
-
-                if !self.match_token(TokenType::Comma) {
-                    break;
-                }
-            }
-        }
-
-        self.consume(TokenType::RightParen, "Expected ')' after parameters")?;
+        let params = self.parameters()?;
 
         let return_type = if self.match_token(TokenType::Arrow) {
             Some(self.parse_type()?)
@@ -642,7 +958,8 @@ impl Parser {
         Ok(Expr::Function {
             params,
             return_type,
-            body,
+            body: Arc::new(body),
+            span: self.span_from(&location),
             location,
         })
     }
@@ -653,9 +970,9 @@ impl Parser {
         if !self.check(TokenType::RightBrace) {
             loop {
                 // Check for key = value or just value
-                if self.check(TokenType::Identifier) {
+                if self.check_identifier() {
                     let checkpoint = self.current;
-                    let name = self.advance().lexeme.clone();
+                    let name = self.advance().lexeme.to_string();
 
                     if self.match_token(TokenType::Assign) {
                         // key = value
@@ -668,6 +985,7 @@ impl Parser {
                         fields.push((TableKey::Expression(Box::new(Expr::Literal {
                             value: Literal::Integer(fields.len() as i64 + 1),
                             location: location.clone(),
+                            span: value.span(),
                         })), value));
                     }
                 } else if self.match_token(TokenType::LeftBracket) {
@@ -683,10 +1001,12 @@ impl Parser {
                     fields.push((TableKey::Expression(Box::new(Expr::Literal {
                         value: Literal::Integer(fields.len() as i64 + 1),
                         location: location.clone(),
+                        span: value.span(),
                     })), value));
                 }
 
-                if !self.match_token(TokenType::Comma) {
+                // A single trailing comma is allowed before '}'
+                if !self.match_token(TokenType::Comma) || self.check(TokenType::RightBrace) {
                     break;
                 }
             }
@@ -694,18 +1014,106 @@ impl Parser {
 
         self.consume(TokenType::RightBrace, "Expected '}' after table literal")?;
 
-        Ok(Expr::Table { fields, location })
+        Ok(Expr::Table { fields, span: self.span_from(&location), location })
     }
 
     // ===== Type Parsing =====
 
     fn parse_type(&mut self) -> LuxResult<Type> {
+        let typ = self.base_type()?;
+        // A trailing `?` also admits nil: `int?`
+        if self.match_token(TokenType::Question) {
+            return Ok(Type::Optional(Box::new(typ)));
+        }
+        Ok(typ)
+    }
+
+    fn base_type(&mut self) -> LuxResult<Type> {
         // Check for pointer type: *T
         if self.match_token(TokenType::Star) {
             let inner_type = self.parse_type()?;
             return Ok(Type::Pointer(Box::new(inner_type)));
         }
 
+        // Check for tuple type: (T, U); a single parenthesized type is just grouping
+        if self.match_token(TokenType::LeftParen) {
+            let first = self.parse_type()?;
+            if !self.match_token(TokenType::Comma) {
+                self.consume(TokenType::RightParen, "Expected ')' after type")?;
+                return Ok(first);
+            }
+            let mut elements = vec![first];
+            while !self.check(TokenType::RightParen) {
+                elements.push(self.parse_type()?);
+                if !self.match_token(TokenType::Comma) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after tuple type")?;
+            return Ok(Type::Tuple(elements));
+        }
+
+        // Check for array type: []T
+        if self.match_token(TokenType::LeftBracket) {
+            self.consume(TokenType::RightBracket, "Expected ']' in array type")?;
+            let element_type = self.parse_type()?;
+            return Ok(Type::Array(Box::new(element_type)));
+        }
+
+        // Check for shape type: { name: T, other: U }
+        if self.match_token(TokenType::LeftBrace) {
+            let mut fields = Vec::new();
+            if !self.check(TokenType::RightBrace) {
+                loop {
+                    let name = self.consume_identifier("Expected field name in shape type")?;
+                    self.consume(TokenType::Colon, "Expected ':' after field name in shape type")?;
+                    fields.push((name, self.parse_type()?));
+                    if !self.match_token(TokenType::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightBrace, "Expected '}' after shape type")?;
+            return Ok(Type::Shape(fields));
+        }
+
+        // Check for function type: fn(T, U) -> R (the return type defaults to nil)
+        if self.match_keyword(Keyword::Fn) {
+            self.consume(TokenType::LeftParen, "Expected '(' after 'fn' in function type")?;
+            let mut params = Vec::new();
+            if !self.check(TokenType::RightParen) {
+                loop {
+                    params.push(self.parse_type()?);
+                    if !self.match_token(TokenType::Comma) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after function type parameters")?;
+            let return_type = if self.match_token(TokenType::Arrow) {
+                self.parse_type()?
+            } else {
+                Type::Any
+            };
+            return Ok(Type::Function {
+                params,
+                return_type: Box::new(return_type),
+            });
+        }
+
+        // Check for map type: map[K]V (`map` stays an ordinary identifier elsewhere)
+        if self.check_identifier() && self.peek().lexeme == "map" {
+            self.advance();
+            self.consume(TokenType::LeftBracket, "Expected '[' after 'map'")?;
+            let key = self.parse_type()?;
+            self.consume(TokenType::RightBracket, "Expected ']' after map key type")?;
+            let value = self.parse_type()?;
+            return Ok(Type::Map {
+                key: Box::new(key),
+                value: Box::new(value),
+            });
+        }
+
         if self.match_keyword(Keyword::Int) {
             Ok(Type::Int)
         } else if self.match_keyword(Keyword::Float) {
@@ -718,6 +1126,10 @@ impl Parser {
             Ok(Type::Nil)
         } else if self.match_keyword(Keyword::Table) {
             Ok(Type::Table)
+        } else if self.match_keyword(Keyword::Any) {
+            Ok(Type::Any)
+        } else if self.check_identifier() {
+            Ok(Type::Named(self.advance().lexeme.to_string()))
         } else {
             Err(LuxError::parse_error(
                 "Expected type",
@@ -728,6 +1140,16 @@ impl Parser {
 
     // ===== Helper Methods =====
 
+    /// Match an identifier used as a contextual keyword, such as `as` in imports
+    fn match_word(&mut self, word: &str) -> bool {
+        if self.check_identifier() && self.peek().lexeme == word {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
     fn match_token(&mut self, token_type: TokenType) -> bool {
         if self.check(token_type) {
             self.advance();
@@ -764,6 +1186,41 @@ impl Parser {
         }
     }
 
+    /// Whether the current token is a `*` that starts a line assigning
+    /// through a pointer, as in `*p = 5`, which begins a new statement
+    /// rather than multiplying. Any other `*` at the start of a line
+    /// continues the expression on the line before.
+    fn starts_pointer_assignment(&self) -> bool {
+        if !self.check(TokenType::Star) || self.current == 0 {
+            return false;
+        }
+        let line = self.peek().location.line;
+        if self.previous().end.line == line {
+            return false;
+        }
+        let mut depth = 0usize;
+        for token in self.tokens[self.current + 1..].iter().take_while(|token| token.location.line == line) {
+            match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => depth += 1,
+                TokenType::RightParen | TokenType::RightBracket | TokenType::RightBrace => {
+                    depth = depth.saturating_sub(1)
+                }
+                TokenType::Assign if depth == 0 => return true,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    /// The span from `start` to the end of the last consumed token
+    fn span_from(&self, start: &SourceLocation) -> Span {
+        Span::new(start.clone(), self.previous().end.clone())
+    }
+
+    fn check_identifier(&self) -> bool {
+        matches!(self.peek().token_type, TokenType::Identifier(_))
+    }
+
     fn check_keyword(&self, keyword: Keyword) -> bool {
         if self.is_at_end() {
             false
@@ -807,15 +1264,58 @@ impl Parser {
         }
     }
 
+    /// Property names after '.' may also be keywords (e.g. `node.type`)
+    fn consume_property_name(&mut self) -> LuxResult<String> {
+        if matches!(self.peek().token_type, TokenType::Keyword(_)) {
+            Ok(self.advance().lexeme.to_string())
+        } else {
+            self.consume_identifier("Expected property name after '.'")
+        }
+    }
+
     fn consume_identifier(&mut self, message: &str) -> LuxResult<String> {
-        if self.check(TokenType::Identifier) {
-            Ok(self.advance().lexeme.clone())
+        if self.check_identifier() {
+            Ok(self.advance().lexeme.to_string())
+        } else {
+            Err(LuxError::parse_error(message, self.peek().location.clone()))
+        }
+    }
+
+    /// Like `consume_identifier`, for names of variables and functions
+    fn consume_symbol(&mut self, message: &str) -> LuxResult<Symbol> {
+        if let TokenType::Identifier(name) = self.peek().token_type {
+            self.advance();
+            Ok(name)
         } else {
             Err(LuxError::parse_error(message, self.peek().location.clone()))
         }
     }
 }
 
+// Helper method for Stmt to get location
+impl Stmt {
+    pub fn location(&self) -> &SourceLocation {
+        match self {
+            Stmt::Import { location, .. }
+            | Stmt::VarDecl { location, .. }
+            | Stmt::Destructure { location, .. }
+            | Stmt::FunctionDecl { location, .. }
+            | Stmt::Expression { location, .. }
+            | Stmt::If { location, .. }
+            | Stmt::While { location, .. }
+            | Stmt::For { location, .. }
+            | Stmt::Return { location, .. }
+            | Stmt::Break { location, .. }
+            | Stmt::Continue { location, .. }
+            | Stmt::Defer { location, .. }
+            | Stmt::Block { location, .. }
+            | Stmt::TypeAlias { location, .. }
+            | Stmt::EnumDecl { location, .. }
+            | Stmt::StructDecl { location, .. } => location,
+        }
+    }
+}
+
 // Helper method for Expr to get location
 impl Expr {
     pub fn location(&self) -> &SourceLocation {
@@ -826,13 +1326,441 @@ impl Expr {
             | Expr::Unary { location, .. }
             | Expr::Assign { location, .. }
             | Expr::Call { location, .. }
+            | Expr::MethodCall { location, .. }
             | Expr::Table { location, .. }
             | Expr::TableAccess { location, .. }
             | Expr::Logical { location, .. }
             | Expr::Function { location, .. }
+            | Expr::DoBlock { location, .. }
+            | Expr::StructInit { location, .. }
+            | Expr::Tuple { location, .. }
             | Expr::Spawn { location, .. }
             | Expr::Await { location, .. } => location,
         }
     }
+
+    /// Get the source span covered by the expression
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Literal { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::MethodCall { span, .. }
+            | Expr::Table { span, .. }
+            | Expr::TableAccess { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Function { span, .. }
+            | Expr::Tuple { span, .. }
+            | Expr::StructInit { span, .. }
+            | Expr::DoBlock { span, .. }
+            | Expr::Spawn { span, .. }
+            | Expr::Await { span, .. } => span.clone(),
+        }
+    }
+
+    /// Widen the span to `span`, as when the expression is parenthesized
+    fn set_span(&mut self, new_span: Span) {
+        match self {
+            Expr::Literal { span, .. }
+            | Expr::Variable { span, .. }
+            | Expr::Binary { span, .. }
+            | Expr::Unary { span, .. }
+            | Expr::Assign { span, .. }
+            | Expr::Call { span, .. }
+            | Expr::MethodCall { span, .. }
+            | Expr::Table { span, .. }
+            | Expr::TableAccess { span, .. }
+            | Expr::Logical { span, .. }
+            | Expr::Function { span, .. }
+            | Expr::Tuple { span, .. }
+            | Expr::StructInit { span, .. }
+            | Expr::DoBlock { span, .. }
+            | Expr::Spawn { span, .. }
+            | Expr::Await { span, .. } => *span = new_span,
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Lexer;
+
+    fn parse(source: &str) -> LuxResult<Ast> {
+        let tokens = Lexer::new(source, None).with_newlines().tokenize()?;
+        Parser::new(tokens).parse()
+    }
+
+    #[test]
+    fn test_parse_all_recovers_after_errors() {
+        let source = "local a := 1 + / 2\nlocal b := 2\nlocal := 3\nprint(b) local c := )\nlocal d := b";
+        let tokens = Lexer::new(source, None).with_newlines().tokenize().unwrap();
+        let (ast, errors) = Parser::new(tokens).parse_all();
+
+        let lines: Vec<usize> = errors.iter().map(|error| error.location().unwrap().line).collect();
+        assert_eq!(lines, vec![1, 3, 4]);
+        // The statements around the errors still parse
+        let names: Vec<String> = ast
+            .statements
+            .iter()
+            .map(|statement| match statement {
+                Stmt::VarDecl { name, .. } => name.to_string(),
+                Stmt::Expression { .. } => "expr".to_string(),
+                other => panic!("unexpected statement {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, vec!["b", "expr", "d"]);
+
+        // Without newlines, statement keywords are the boundaries
+        let tokens = Lexer::new("local := 1 local ok := 2", None).tokenize().unwrap();
+        let (ast, errors) = Parser::new(tokens).parse_all();
+        assert_eq!((ast.statements.len(), errors.len()), (1, 1));
+    }
+
+    #[test]
+    fn test_expression_spans_cover_their_tokens() {
+        // (start line, start column, end line, end column) of the first statement's expression
+        let span_of = |source: &str| {
+            let span = match parse(source).unwrap().statements.remove(0) {
+                Stmt::VarDecl { initializer: Some(init), .. } => init.span(),
+                Stmt::Expression { expr, .. } => expr.span(),
+                other => panic!("unexpected statement {:?}", other),
+            };
+            (span.start.line, span.start.column, span.end.line, span.end.column)
+        };
+        // Escapes are measured in source characters, not in the decoded string
+        assert_eq!(span_of(r#"local s := "a\nb\t""#), (1, 12, 1, 20));
+        assert_eq!(span_of("local f := 1.50"), (1, 12, 1, 16));
+        assert_eq!(span_of("local n := (a + b) * 2"), (1, 12, 1, 23));
+        assert_eq!(span_of("print(1,\n  2\n)"), (1, 1, 3, 2));
+        assert_eq!(span_of("t.items[i]:push(x)"), (1, 1, 1, 19));
+    }
+
+    #[test]
+    fn test_newline_ends_statement_before_paren() {
+        let ast = parse("x = f\n(1)").unwrap();
+        assert_eq!(ast.statements.len(), 2);
+        match &ast.statements[0] {
+            Stmt::Expression { expr: Expr::Assign { value, .. }, .. } => {
+                assert!(matches!(value.as_ref(), Expr::Variable { name, .. } if name == "f"))
+            }
+            other => panic!("expected an assignment, got {:?}", other),
+        }
+        assert!(matches!(&ast.statements[1], Stmt::Expression { expr: Expr::Literal { .. }, .. }));
+
+        // Arguments may still span lines once the '(' is on the callee's line
+        let ast = parse("x = f(\n1,\n2\n)").unwrap();
+        assert_eq!(ast.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_power_precedence() {
+        let expr = |source: &str| match parse(source).unwrap().statements.remove(0) {
+            Stmt::Expression { expr, .. } => expr,
+            other => panic!("expected an expression, got {:?}", other),
+        };
+        let int = |n: i64| move |e: &Expr| matches!(e, Expr::Literal { value: Literal::Integer(v), .. } if *v == n);
+
+        // -2 ^ 2 is -(2 ^ 2)
+        match expr("-2 ^ 2") {
+            Expr::Unary { operator: UnaryOp::Negate, operand, .. } => match operand.as_ref() {
+                Expr::Binary { left, operator: BinaryOp::Power, right, .. } => assert!(int(2)(left) && int(2)(right)),
+                other => panic!("expected 2 ^ 2, got {:?}", other),
+            },
+            other => panic!("expected a negation, got {:?}", other),
+        }
+
+        // 2 ^ -3 is 2 ^ (-3)
+        match expr("2 ^ -3") {
+            Expr::Binary { left, operator: BinaryOp::Power, right, .. } => {
+                assert!(int(2)(&left));
+                assert!(matches!(right.as_ref(), Expr::Unary { operator: UnaryOp::Negate, operand, .. } if int(3)(operand)));
+            }
+            other => panic!("expected a power, got {:?}", other),
+        }
+
+        // Right-associative: 2 ^ 3 ^ 2 is 2 ^ (3 ^ 2)
+        assert!(matches!(
+            expr("2 ^ 3 ^ 2"),
+            Expr::Binary { left, right, .. } if int(2)(&left) && matches!(right.as_ref(), Expr::Binary { operator: BinaryOp::Power, .. })
+        ));
+    }
+
+    #[test]
+    fn test_assign_through_pointer() {
+        let ast = parse("local p := &x\n*p = 5").unwrap();
+        assert_eq!(ast.statements.len(), 2);
+        match &ast.statements[1] {
+            Stmt::Expression { expr: Expr::Assign { target, .. }, .. } => assert!(matches!(
+                target.as_ref(),
+                Expr::Unary { operator: UnaryOp::Dereference, .. }
+            )),
+            other => panic!("expected an assignment, got {:?}", other),
+        }
+        // Mid-line, `*` still multiplies
+        assert!(parse("a * b = 1").is_err());
+        // Lines are known from token locations, not just `Newline` tokens
+        let tokens = Lexer::new("local p := &x\n*p = 5", None).tokenize().unwrap();
+        assert_eq!(Parser::new(tokens).parse().unwrap().statements.len(), 2);
+    }
+
+    #[test]
+    fn test_multiplication_continues_on_next_line() {
+        let ast = parse("local area := width\n    * height").unwrap();
+        assert_eq!(ast.statements.len(), 1);
+        match &ast.statements[0] {
+            Stmt::VarDecl { initializer: Some(init), .. } => {
+                assert!(matches!(init, Expr::Binary { operator: BinaryOp::Multiply, .. }))
+            }
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_array_type() {
+        let ast = parse("local xs: [][]int = {}").unwrap();
+        match &ast.statements[0] {
+            Stmt::VarDecl { type_annotation, .. } => assert_eq!(
+                type_annotation,
+                &Some(Type::Array(Box::new(Type::Array(Box::new(Type::Int)))))
+            ),
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+        assert!(parse("local xs: [int = {}").is_err());
+    }
+
+    #[test]
+    fn test_parse_map_type() {
+        let ast = parse("local ages: map[string]int = {}").unwrap();
+        match &ast.statements[0] {
+            Stmt::VarDecl { type_annotation, .. } => assert_eq!(
+                type_annotation,
+                &Some(Type::Map {
+                    key: Box::new(Type::String),
+                    value: Box::new(Type::Int),
+                })
+            ),
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+        // `map` is only special in type position
+        assert!(parse("local map := 1").is_ok());
+    }
+
+    #[test]
+    fn test_parse_shape_type() {
+        let ast = parse("local p: {name: string, age: int} = {name = \"ann\", age = 31}").unwrap();
+        match &ast.statements[0] {
+            Stmt::VarDecl { type_annotation, .. } => assert_eq!(
+                type_annotation,
+                &Some(Type::Shape(vec![
+                    ("name".to_string(), Type::String),
+                    ("age".to_string(), Type::Int),
+                ]))
+            ),
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_type_alias() {
+        let ast = parse("type Id = int\nlocal x: Id = 5\nlocal t := {}\nlocal k := t.type").unwrap();
+        assert_eq!(
+            ast.statements[0],
+            Stmt::TypeAlias {
+                name: "Id".to_string(),
+                target: Type::Int,
+                location: SourceLocation::new(1, 1, None),
+            }
+        );
+        match &ast.statements[1] {
+            Stmt::VarDecl { type_annotation, .. } => {
+                assert_eq!(type_annotation, &Some(Type::Named("Id".to_string())))
+            }
+            other => panic!("expected a variable declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_do_block() {
+        match &parse("local x := do { local a := 2; a * 21 }").unwrap().statements[0] {
+            Stmt::VarDecl { initializer: Some(Expr::DoBlock { body, .. }), .. } => {
+                assert_eq!(body.len(), 2);
+                assert!(matches!(body[1], Stmt::Expression { expr: Expr::Binary { .. }, .. }));
+            }
+            other => panic!("expected a do block, got {:?}", other),
+        }
+        assert!(parse("local x := do 1").is_err());
+    }
+
+    #[test]
+    fn test_trailing_commas() {
+        match &parse("print(1, 2,)").unwrap().statements[0] {
+            Stmt::Expression { expr: Expr::Call { arguments, .. }, .. } => assert_eq!(arguments.len(), 2),
+            other => panic!("expected a call, got {:?}", other),
+        }
+        match &parse("local t := {\n    1,\n    name = \"x\",\n}").unwrap().statements[0] {
+            Stmt::VarDecl { initializer: Some(Expr::Table { fields, .. }), .. } => assert_eq!(fields.len(), 2),
+            other => panic!("expected a table, got {:?}", other),
+        }
+        match &parse("fn f(a: int, b,) {}").unwrap().statements[0] {
+            Stmt::FunctionDecl { params, .. } => assert_eq!(params.len(), 2),
+            other => panic!("expected a function declaration, got {:?}", other),
+        }
+
+        // Only a single trailing comma is accepted
+        assert!(parse("print(1,,)").is_err());
+        assert!(parse("local t := {1,,}").is_err());
+        assert!(parse("fn f(a,,) {}").is_err());
+    }
+
+    #[test]
+    fn test_parse_local_function() {
+        let ast = parse("local fn square(n: int) -> int { return n * n }").unwrap();
+        match &ast.statements[0] {
+            Stmt::FunctionDecl { name, params, return_type, is_async, location, .. } => {
+                assert_eq!(name, "square");
+                assert_eq!(params, &vec![(Symbol::intern("n"), Type::Int)]);
+                assert_eq!(return_type, &Some(Type::Int));
+                assert!(!is_async);
+                assert_eq!(location, &SourceLocation::new(1, 7, None));
+            }
+            other => panic!("expected a function declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_method_call() {
+        let ast = parse("obj:greet(\"hi\")").unwrap();
+        match &ast.statements[0] {
+            Stmt::Expression { expr: Expr::MethodCall { receiver, method, arguments, .. }, .. } => {
+                assert!(matches!(receiver.as_ref(), Expr::Variable { name, .. } if name == "obj"));
+                assert_eq!(method, "greet");
+                // The receiver is not repeated among the arguments
+                assert!(matches!(
+                    arguments.as_slice(),
+                    [Expr::Literal { value: Literal::String(s), .. }] if s == "hi"
+                ));
+            }
+            other => panic!("expected a method call, got {:?}", other),
+        }
+
+        // The receiver can be any expression, including another method call
+        let ast = parse("make():scale(2):add(x, 1)").unwrap();
+        let Stmt::Expression { expr: Expr::MethodCall { receiver, method, arguments, .. }, .. } = &ast.statements[0]
+        else {
+            panic!("expected a method call, got {:?}", ast.statements[0]);
+        };
+        assert_eq!(method, "add");
+        assert!(matches!(
+            arguments.as_slice(),
+            [Expr::Variable { name, .. }, Expr::Literal { value: Literal::Integer(1), .. }] if name == "x"
+        ));
+        match receiver.as_ref() {
+            Expr::MethodCall { receiver, method, arguments, .. } => {
+                assert!(matches!(receiver.as_ref(), Expr::Call { arguments, .. } if arguments.is_empty()));
+                assert_eq!(method, "scale");
+                assert!(matches!(arguments.as_slice(), [Expr::Literal { value: Literal::Integer(2), .. }]));
+            }
+            other => panic!("expected a method call receiver, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let ast = parse("enum Level { Low, High = 10, Max, }").unwrap();
+        assert_eq!(
+            ast.statements[0],
+            Stmt::EnumDecl {
+                name: "Level".to_string(),
+                members: vec![("Low".to_string(), 0), ("High".to_string(), 10), ("Max".to_string(), 11)],
+                location: SourceLocation::new(1, 1, None),
+            }
+        );
+        assert!(parse("enum E { A, A }").unwrap_err().to_string().contains("Duplicate enum member 'A'"));
+        assert!(parse("enum E { A = \"x\" }").is_err());
+    }
+
+    #[test]
+    fn test_parse_struct() {
+        let ast = parse("struct Point { x: int, y: int }\nlocal p := Point { x = 1, y = 2 }\nif p { }").unwrap();
+        assert_eq!(
+            ast.statements[0],
+            Stmt::StructDecl {
+                name: "Point".to_string(),
+                fields: vec![("x".to_string(), Type::Int), ("y".to_string(), Type::Int)],
+                location: SourceLocation::new(1, 1, None),
+            }
+        );
+        match &ast.statements[1] {
+            Stmt::VarDecl { initializer: Some(Expr::StructInit { name, fields, .. }), .. } => {
+                assert_eq!(name, "Point");
+                assert_eq!(fields.len(), 2);
+            }
+            other => panic!("expected a struct constructor, got {:?}", other),
+        }
+        // Names that aren't structs keep their meaning before a block
+        assert!(matches!(ast.statements[2], Stmt::If { .. }));
+    }
+
+    #[test]
+    fn test_parse_import_forms() {
+        let ast = parse("import \"math\"\nimport \"math\" as m\nimport { sin, cos, } from \"math\"").unwrap();
+        let kinds: Vec<_> = ast
+            .statements
+            .iter()
+            .map(|stmt| match stmt {
+                Stmt::Import { path, kind, .. } => {
+                    assert_eq!(path, "math");
+                    kind.clone()
+                }
+                other => panic!("expected an import, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ImportKind::All,
+                ImportKind::Alias("m".to_string()),
+                ImportKind::Names(vec!["sin".to_string(), "cos".to_string()]),
+            ]
+        );
+        assert!(parse("import { sin } \"math\"").is_err());
+        assert!(parse("import \"math\" as").is_err());
+    }
+
+    #[test]
+    fn test_parse_tuples() {
+        let ast = parse("local pair: (int, string) = (1, \"a\")\nlocal one := (1,)\nlocal x := (1)\nlocal a, b = pair").unwrap();
+        match &ast.statements[0] {
+            Stmt::VarDecl { type_annotation, initializer: Some(Expr::Tuple { elements, .. }), .. } => {
+                assert_eq!(type_annotation, &Some(Type::Tuple(vec![Type::Int, Type::String])));
+                assert_eq!(elements.len(), 2);
+            }
+            other => panic!("expected a tuple declaration, got {:?}", other),
+        }
+        assert!(matches!(&ast.statements[1], Stmt::VarDecl { initializer: Some(Expr::Tuple { elements, .. }), .. } if elements.len() == 1));
+        assert!(matches!(&ast.statements[2], Stmt::VarDecl { initializer: Some(Expr::Literal { .. }), .. }));
+        match &ast.statements[3] {
+            Stmt::Destructure { names, .. } => assert_eq!(names, &["a", "b"]),
+            other => panic!("expected a destructuring declaration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_function_type() {
+        let ast = parse("fn apply(f: fn(int, string) -> bool) {}").unwrap();
+        match &ast.statements[0] {
+            Stmt::FunctionDecl { params, .. } => assert_eq!(
+                params[0].1,
+                Type::Function {
+                    params: vec![Type::Int, Type::String],
+                    return_type: Box::new(Type::Bool),
+                }
+            ),
+            other => panic!("expected a function declaration, got {:?}", other),
+        }
+    }
+}
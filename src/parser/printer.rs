@@ -0,0 +1,347 @@
+//! AST pretty printer
+//!
+//! This module renders an AST as an indented tree for debugging the parser.
+
+use std::fmt::Write;
+use super::ast::*;
+
+/// Renders AST nodes as an indented, human-readable tree
+pub struct AstPrinter {
+    output: String,
+    depth: usize,
+}
+
+impl AstPrinter {
+    /// Render a complete program
+    pub fn print(ast: &Ast) -> String {
+        let mut printer = Self {
+            output: String::new(),
+            depth: 0,
+        };
+        printer.line("Program");
+        printer.indented(|p| {
+            for stmt in &ast.statements {
+                p.stmt(stmt);
+            }
+        });
+        printer.output
+    }
+
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.output, "{}{}", "  ".repeat(self.depth), text);
+    }
+
+    fn indented(&mut self, f: impl FnOnce(&mut Self)) {
+        self.depth += 1;
+        f(self);
+        self.depth -= 1;
+    }
+
+    fn block(&mut self, label: &str, stmts: &[Stmt]) {
+        self.line(label);
+        self.indented(|p| {
+            for stmt in stmts {
+                p.stmt(stmt);
+            }
+        });
+    }
+
+    fn table_fields(&mut self, fields: &[(TableKey, Expr)]) {
+        self.indented(|p| {
+            for (key, value) in fields {
+                match key {
+                    TableKey::Identifier(name) => {
+                        p.line(&format!("Field {}", name));
+                        p.indented(|p| p.expr(value));
+                    }
+                    TableKey::Expression(key_expr) => {
+                        p.line("Entry");
+                        p.indented(|p| {
+                            p.expr(key_expr);
+                            p.expr(value);
+                        });
+                    }
+                }
+            }
+        });
+    }
+
+    fn params(params: &[(impl std::fmt::Display, Type)]) -> String {
+        params
+            .iter()
+            .map(|(name, typ)| format!("{}: {}", name, typ))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Import { path, kind, .. } => match kind {
+                ImportKind::All => self.line(&format!("Import {:?}", path)),
+                ImportKind::Alias(name) => self.line(&format!("Import {:?} as {}", path, name)),
+                ImportKind::Names(names) => self.line(&format!("Import {{{}}} from {:?}", names.join(", "), path)),
+            },
+            Stmt::VarDecl { name, type_annotation, initializer, is_const, .. } => {
+                let keyword = if *is_const { "Const" } else { "Local" };
+                let annotation = type_annotation
+                    .as_ref()
+                    .map(|t| format!(": {}", t))
+                    .unwrap_or_default();
+                self.line(&format!("{} {}{}", keyword, name, annotation));
+                if let Some(init) = initializer {
+                    self.indented(|p| p.expr(init));
+                }
+            }
+            Stmt::Destructure { names, initializer, is_const, .. } => {
+                let keyword = if *is_const { "Const" } else { "Local" };
+                let names: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+                self.line(&format!("{} {}", keyword, names.join(", ")));
+                self.indented(|p| p.expr(initializer));
+            }
+            Stmt::FunctionDecl { name, params, return_type, body, is_async, .. } => {
+                let prefix = if *is_async { "async " } else { "" };
+                let ret = return_type
+                    .as_ref()
+                    .map(|t| format!(" -> {}", t))
+                    .unwrap_or_default();
+                self.block(&format!("{}Function {}({}){}", prefix, name, Self::params(params), ret), body);
+            }
+            Stmt::Expression { expr, .. } => {
+                self.line("Expression");
+                self.indented(|p| p.expr(expr));
+            }
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.line("If");
+                self.indented(|p| {
+                    p.expr(condition);
+                    p.block("Then", then_branch);
+                    if let Some(else_stmts) = else_branch {
+                        p.block("Else", else_stmts);
+                    }
+                });
+            }
+            Stmt::While { condition, body, .. } => {
+                self.line("While");
+                self.indented(|p| {
+                    p.expr(condition);
+                    p.block("Body", body);
+                });
+            }
+            Stmt::For { initializer, condition, increment, body, .. } => {
+                self.line("For");
+                self.indented(|p| {
+                    if let Some(init) = initializer {
+                        p.line("Init");
+                        p.indented(|p| p.stmt(init));
+                    }
+                    if let Some(cond) = condition {
+                        p.line("Condition");
+                        p.indented(|p| p.expr(cond));
+                    }
+                    if let Some(inc) = increment {
+                        p.line("Increment");
+                        p.indented(|p| p.expr(inc));
+                    }
+                    p.block("Body", body);
+                });
+            }
+            Stmt::Return { value, .. } => {
+                self.line("Return");
+                if let Some(v) = value {
+                    self.indented(|p| p.expr(v));
+                }
+            }
+            Stmt::Break { .. } => self.line("Break"),
+            Stmt::Continue { .. } => self.line("Continue"),
+            Stmt::Defer { expr, .. } => {
+                self.line("Defer");
+                self.indented(|p| p.expr(expr));
+            }
+            Stmt::Block { statements, .. } => self.block("Block", statements),
+            Stmt::TypeAlias { name, target, .. } => {
+                self.line(&format!("TypeAlias {} = {}", name, target))
+            }
+            Stmt::StructDecl { name, fields, .. } => {
+                self.line(&format!("Struct {} {{{}}}", name, Self::params(fields)))
+            }
+            Stmt::EnumDecl { name, members, .. } => {
+                self.line(&format!("Enum {}", name));
+                self.indented(|p| {
+                    for (member, value) in members {
+                        p.line(&format!("{} = {}", member, value));
+                    }
+                });
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { value, .. } => {
+                let text = match value {
+                    Literal::Integer(n) => n.to_string(),
+                    Literal::Float(f) => format!("{:?}", f),
+                    Literal::String(s) => format!("{:?}", s),
+                    Literal::Boolean(b) => b.to_string(),
+                    Literal::Nil => "nil".to_string(),
+                };
+                self.line(&format!("Literal {}", text));
+            }
+            Expr::Variable { name, .. } => self.line(&format!("Variable {}", name)),
+            Expr::Binary { left, operator, right, .. } => {
+                self.line(&format!("Binary {:?}", operator));
+                self.indented(|p| {
+                    p.expr(left);
+                    p.expr(right);
+                });
+            }
+            Expr::Unary { operator, operand, .. } => {
+                self.line(&format!("Unary {:?}", operator));
+                self.indented(|p| p.expr(operand));
+            }
+            Expr::Assign { target, value, .. } => {
+                self.line("Assign");
+                self.indented(|p| {
+                    p.expr(target);
+                    p.expr(value);
+                });
+            }
+            Expr::Call { callee, arguments, .. } => {
+                self.line("Call");
+                self.indented(|p| {
+                    p.expr(callee);
+                    if !arguments.is_empty() {
+                        p.line("Arguments");
+                        p.indented(|p| {
+                            for arg in arguments {
+                                p.expr(arg);
+                            }
+                        });
+                    }
+                });
+            }
+            Expr::MethodCall { receiver, method, arguments, .. } => {
+                self.line(&format!("MethodCall {}", method));
+                self.indented(|p| {
+                    p.expr(receiver);
+                    if !arguments.is_empty() {
+                        p.line("Arguments");
+                        p.indented(|p| {
+                            for arg in arguments {
+                                p.expr(arg);
+                            }
+                        });
+                    }
+                });
+            }
+            Expr::Table { fields, .. } => {
+                self.line("Table");
+                self.table_fields(fields);
+            }
+            Expr::Tuple { elements, .. } => {
+                self.line("Tuple");
+                self.indented(|p| {
+                    for element in elements {
+                        p.expr(element);
+                    }
+                });
+            }
+            Expr::StructInit { name, fields, .. } => {
+                self.line(&format!("StructInit {}", name));
+                self.table_fields(fields);
+            }
+            Expr::TableAccess { table, key, .. } => {
+                self.line("Index");
+                self.indented(|p| {
+                    p.expr(table);
+                    p.expr(key);
+                });
+            }
+            Expr::Logical { left, operator, right, .. } => {
+                self.line(&format!("Logical {:?}", operator));
+                self.indented(|p| {
+                    p.expr(left);
+                    p.expr(right);
+                });
+            }
+            Expr::Function { params, return_type, body, .. } => {
+                let ret = return_type
+                    .as_ref()
+                    .map(|t| format!(" -> {}", t))
+                    .unwrap_or_default();
+                self.block(&format!("Function({}){}", Self::params(params), ret), body);
+            }
+            Expr::DoBlock { body, .. } => self.block("DoBlock", body),
+            Expr::Spawn { call, .. } => {
+                self.line("Spawn");
+                self.indented(|p| p.expr(call));
+            }
+            Expr::Await { task, .. } => {
+                self.line("Await");
+                self.indented(|p| p.expr(task));
+            }
+        }
+    }
+}
+
+/// Types print in the syntax of annotations, such as `fn(int) -> []string`
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |types: &[Type]| types.iter().map(Type::to_string).collect::<Vec<_>>().join(", ");
+        match self {
+            Type::Int => f.write_str("int"),
+            Type::Float => f.write_str("float"),
+            Type::String => f.write_str("string"),
+            Type::Bool => f.write_str("bool"),
+            Type::Nil => f.write_str("nil"),
+            Type::Table => f.write_str("table"),
+            Type::Any => f.write_str("any"),
+            Type::Function { params, return_type } => write!(f, "fn({}) -> {}", list(params), return_type),
+            Type::Pointer(inner) => write!(f, "*{}", inner),
+            Type::Array(element) => write!(f, "[]{}", element),
+            Type::Map { key, value } => write!(f, "map[{}]{}", key, value),
+            Type::Shape(fields) => write!(f, "{{{}}}", AstPrinter::params(fields)),
+            Type::Named(name) | Type::Enum(name) => f.write_str(name),
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Variadic(inner) => write!(f, "...{}", inner),
+            Type::Tuple(elements) => write!(f, "({})", list(elements)),
+        }
+    }
+}
+
+impl std::fmt::Display for Ast {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", AstPrinter::print(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Lexer, Parser};
+
+    #[test]
+    fn test_print_function_and_call() {
+        let source = "fn add(a: int, b: int) -> int { return a + b }\nprint(add(1, 2))";
+        let tokens = Lexer::new(source, None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+
+        let expected = "\
+Program
+  Function add(a: int, b: int) -> int
+    Return
+      Binary Add
+        Variable a
+        Variable b
+  Expression
+    Call
+      Variable print
+      Arguments
+        Call
+          Variable add
+          Arguments
+            Literal 1
+            Literal 2
+";
+        assert_eq!(ast.to_string(), expected);
+    }
+}
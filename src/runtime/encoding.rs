@@ -0,0 +1,122 @@
+//! Hex and base64 encoding
+//!
+//! This module backs the `to_hex`/`from_hex` and `base64_encode`/
+//! `base64_decode` builtins. Lux has no byte string type, so bytes are
+//! passed around as an array table of ints in `0..=255`; the encoders also
+//! accept a string, which is encoded as its UTF-8 bytes.
+
+use super::value::{TableValue, Value};
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The bytes of a string, or of an array table of ints in `0..=255`
+pub fn bytes_arg(name: &str, value: &Value) -> Result<Vec<u8>, String> {
+    match value {
+        Value::String(s) => Ok(s.as_bytes().to_vec()),
+        Value::Table(table) => table
+            .lock()
+            .unwrap()
+            .array
+            .iter()
+            .enumerate()
+            .map(|(i, element)| match element {
+                Value::Int(byte) if (0..=255).contains(byte) => Ok(*byte as u8),
+                Value::Int(n) => Err(format!("{}: element {} is out of byte range (0 to 255): {}", name, i + 1, n)),
+                other => Err(format!("{}: element {} is a {}, not a byte", name, i + 1, other.type_name())),
+            })
+            .collect(),
+        other => Err(format!("{} expects a string or a table of bytes, got {}", name, other.type_name())),
+    }
+}
+
+/// An array table holding `bytes` as ints
+pub fn bytes_value(bytes: Vec<u8>) -> Value {
+    let mut table = TableValue::new();
+    table.array = bytes.into_iter().map(|byte| Value::Int(byte as i64)).collect();
+    Value::table(table)
+}
+
+/// Lowercase hex digits, two per byte
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Decode hex digits (either case) into bytes
+pub fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits = text
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            c.to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or_else(|| format!("from_hex: invalid hex digit '{}' at position {}", c, i + 1))
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    if digits.len() % 2 != 0 {
+        return Err(format!("from_hex: expected an even number of digits, got {}", digits.len()));
+    }
+    Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+/// Standard base64 with `=` padding
+pub fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| group | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode standard base64; padding is optional, but nothing may follow it
+pub fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let data = text.trim_end_matches('=');
+    if text.len() - data.len() > 2 {
+        return Err("base64_decode: too much padding".to_string());
+    }
+    let sextets = data
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            BASE64_ALPHABET
+                .iter()
+                .position(|&symbol| symbol as char == c)
+                .map(|sextet| sextet as u32)
+                .ok_or_else(|| format!("base64_decode: invalid character '{}' at position {}", c, i + 1))
+        })
+        .collect::<Result<Vec<u32>, String>>()?;
+    if sextets.len() % 4 == 1 {
+        return Err("base64_decode: input is truncated".to_string());
+    }
+
+    let mut out = Vec::with_capacity(sextets.len() * 3 / 4);
+    for chunk in sextets.chunks(4) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, sextet)| group | sextet << (18 - 6 * i));
+        // n sextets carry n - 1 whole bytes
+        for i in 0..chunk.len() - 1 {
+            out.push((group >> (16 - 8 * i)) as u8);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_known_vectors() {
+        let vectors = [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foobar", "Zm9vYmFy")];
+        for (plain, encoded) in vectors {
+            assert_eq!(base64_encode(plain.as_bytes()), encoded);
+            assert_eq!(base64_decode(encoded).unwrap(), plain.as_bytes());
+        }
+        assert_eq!(base64_decode("Zm8").unwrap(), b"fo");
+    }
+}
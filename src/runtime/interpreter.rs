@@ -3,60 +3,129 @@
 //! This module implements the tree-walking interpreter for Lux.
 
 use std::collections::HashMap;
-use std::sync::Arc;
-use crate::error::{LuxError, LuxResult, SourceLocation};
-use crate::parser::ast::{Ast, Stmt, Expr, BinaryOp, UnaryOp, LogicalOp, Literal, TableKey};
+use std::io::Write;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::error::{LuxError, LuxResult, SourceLocation, StackFrame};
+use crate::parser::ast::{Ast, Stmt, Expr, BinaryOp, UnaryOp, LogicalOp, Literal, TableKey, ImportKind};
 use crate::async_runtime::{AsyncExecutor, TaskState};
-use super::value::{Value, TableValue, FunctionValue, NativeFunctionValue};
-use crate::lexer::Lexer;
+use super::value::{Value, TableValue, TableRef, FunctionValue, NativeFunctionValue, NativeFn};
+use super::encoding;
+use super::json;
+use super::pattern;
+use super::random::Rng;
+use crate::lexer::{Lexer, Symbol};
+use crate::module::{search, ImportStack, ModuleCache, SearchPath};
 use crate::parser::Parser;
 
+/// Builtins, registered once and shared by every interpreter
+static BUILTINS: LazyLock<HashMap<Symbol, Value>> = LazyLock::new(|| {
+    static NONE: LazyLock<HashMap<Symbol, Value>> = LazyLock::new(HashMap::new);
+    let mut env = Environment::with_builtins(&NONE);
+    Interpreter::register_builtins(&mut env);
+    env.globals
+});
+
 /// Environment for variable storage
+///
+/// The global scope is a hash map, backed by the shared builtins. Inner
+/// scopes are usually small, so their bindings share one stack and are found
+/// by scanning it from the innermost binding out: entering and leaving a
+/// block costs no allocation, and a lookup compares symbols rather than hashing.
 #[derive(Debug, Clone)]
 struct Environment {
-    scopes: Vec<HashMap<String, Value>>,
+    globals: HashMap<Symbol, Value>,
+    /// Looked up after `globals`; assigning to a builtin shadows it in `globals`
+    builtins: &'static HashMap<Symbol, Value>,
+    /// Bindings of the inner scopes, innermost last
+    locals: Vec<(Symbol, Value)>,
+    /// Where each inner scope's bindings start in `locals`
+    scope_starts: Vec<usize>,
 }
 
 impl Environment {
     fn new() -> Self {
+        Self::with_builtins(&BUILTINS)
+    }
+
+    fn with_builtins(builtins: &'static HashMap<Symbol, Value>) -> Self {
+        // Programs can modify builtin tables such as `math`, so each
+        // environment gets copies of its own
+        let globals = builtins
+            .iter()
+            .filter(|(_, value)| matches!(value, Value::Table(_)))
+            .map(|(name, value)| (*name, value.deep_copy()))
+            .collect();
         Self {
-            scopes: vec![HashMap::new()],
+            globals,
+            builtins,
+            locals: Vec::new(),
+            scope_starts: Vec::new(),
         }
     }
 
     fn push_scope(&mut self) {
-        self.scopes.push(HashMap::new());
+        self.scope_starts.push(self.locals.len());
     }
 
     fn pop_scope(&mut self) {
-        if self.scopes.len() > 1 {
-            self.scopes.pop();
+        if let Some(start) = self.scope_starts.pop() {
+            self.locals.truncate(start);
         }
     }
 
-    fn define(&mut self, name: String, value: Value) {
-        if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name, value);
-        }
+    /// Push a scope that starts out holding `bindings`
+    fn push_scope_with(&mut self, bindings: HashMap<Symbol, Value>) {
+        self.push_scope();
+        self.locals.extend(bindings);
     }
 
-    fn get(&self, name: &str) -> Option<Value> {
-        for scope in self.scopes.iter().rev() {
-            if let Some(value) = scope.get(name) {
-                return Some(value.clone());
-            }
+    /// Pop the innermost scope, returning its bindings
+    fn take_scope(&mut self) -> HashMap<Symbol, Value> {
+        match self.scope_starts.pop() {
+            Some(start) => self.locals.drain(start..).collect(),
+            None => HashMap::new(),
         }
-        None
     }
 
-    fn set(&mut self, name: &str, value: Value) -> bool {
-        for scope in self.scopes.iter_mut().rev() {
-            if scope.contains_key(name) {
-                scope.insert(name.to_string(), value);
-                return true;
-            }
+    fn define(&mut self, name: impl Into<Symbol>, value: Value) {
+        let name = name.into();
+        let Some(&start) = self.scope_starts.last() else {
+            self.globals.insert(name, value);
+            return;
+        };
+        // Redeclaring a name in the same scope replaces it
+        match self.locals[start..].iter_mut().find(|(bound, _)| *bound == name) {
+            Some((_, slot)) => *slot = value,
+            None => self.locals.push((name, value)),
         }
-        false
+    }
+
+    fn get(&self, name: Symbol) -> Option<Value> {
+        self.locals
+            .iter()
+            .rev()
+            .find(|(bound, _)| *bound == name)
+            .map(|(_, value)| value)
+            .or_else(|| self.globals.get(&name))
+            .or_else(|| self.builtins.get(&name))
+            .cloned()
+    }
+
+    fn set(&mut self, name: Symbol, value: Value) -> bool {
+        let slot = match self.locals.iter_mut().rev().find(|(bound, _)| *bound == name) {
+            Some((_, slot)) => slot,
+            None => match self.globals.get_mut(&name) {
+                Some(slot) => slot,
+                None if self.builtins.contains_key(&name) => {
+                    self.globals.insert(name, value);
+                    return true;
+                }
+                None => return false,
+            },
+        };
+        *slot = value;
+        true
     }
 }
 
@@ -69,86 +138,274 @@ enum ControlFlow {
     Continue,
 }
 
+/// Destination for program output, shared with spawned task interpreters
+type OutputSink = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Default limit on nested function calls, see [`Interpreter::with_max_depth`]
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// Stack size for threads that run Lux code
+///
+/// Each Lux call goes through several large interpreter frames, so a default
+/// thread stack overflows long before `DEFAULT_MAX_DEPTH` calls.
+pub const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Largest string, in bytes, that builtins such as `string_repeat` and
+/// `pad_left` will build; larger results are runtime errors rather than
+/// failed allocations that abort the process
+pub const MAX_STRING_LEN: usize = 1 << 30;
+
+/// What integer arithmetic does when a result doesn't fit in an `i64`,
+/// see [`Interpreter::with_int_overflow`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntOverflow {
+    /// Raise an "integer overflow" runtime error
+    #[default]
+    Checked,
+    /// Wrap around in two's complement, so `i64::MAX + 1` is `i64::MIN`
+    Wrapping,
+}
+
 /// Interpreter
 pub struct Interpreter {
     env: Environment,
     control_flow: ControlFlow,
     executor: Arc<AsyncExecutor>,
     loaded_modules: HashMap<String, bool>,
+    /// Parsed modules, shared with the type checker and task interpreters
+    modules: ModuleCache,
+    /// Modules whose top level is still running
+    imports: ImportStack,
+    /// Where imported modules are looked up after `current_file_dir`
+    search_path: SearchPath,
+    /// Top-level bindings of modules run in a scope of their own, by resolved
+    /// path; functions from those modules run with these bindings in scope
+    module_scopes: HashMap<String, HashMap<Symbol, Value>>,
     current_file_dir: Option<String>,
+    output: OutputSink,
+    /// Generator behind `random`/`random_int`, shared with spawned tasks
+    rng: Arc<Mutex<Rng>>,
+    /// Reference point for `clock()`
+    started: Instant,
+    /// Command-line arguments passed to the script, returned by `args()`
+    script_args: Vec<String>,
+    /// User function calls in progress, outermost first
+    call_stack: Vec<StackFrame>,
+    /// Current number of nested calls and tasks
+    depth: usize,
+    /// Nesting beyond this raises a "stack overflow" runtime error
+    max_depth: usize,
+    /// Whether int `+`, `-`, `*`, `~/` and negation fail or wrap on overflow
+    int_overflow: IntOverflow,
+    /// Expressions deferred by each running function body or block, innermost last
+    defers: Vec<Vec<Expr>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        let mut interpreter = Self {
+        Self {
             env: Environment::new(),
             control_flow: ControlFlow::None,
             executor: Arc::new(AsyncExecutor::new()),
             loaded_modules: HashMap::new(),
+            modules: ModuleCache::new(),
+            imports: ImportStack::new(),
+            search_path: SearchPath::from_env(),
+            module_scopes: HashMap::new(),
             current_file_dir: None,
-        };
-        interpreter.register_builtins();
-        interpreter
+            output: Arc::new(Mutex::new(Box::new(std::io::stdout()))),
+            rng: Arc::new(Mutex::new(Rng::from_time())),
+            started: Instant::now(),
+            script_args: Vec::new(),
+            call_stack: Vec::new(),
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            int_overflow: IntOverflow::default(),
+            defers: Vec::new(),
+        }
+    }
+
+    /// Send program output (e.g. from `print`) to `output` instead of stdout
+    pub fn with_output(mut self, output: Box<dyn Write + Send>) -> Self {
+        self.output = Arc::new(Mutex::new(output));
+        self
+    }
+
+    /// Make `args` available to the program through the `args()` builtin
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.script_args = args;
+        self
+    }
+
+    /// Load imported modules through `modules`, reusing any already parsed
+    /// by an earlier phase such as the type checker
+    pub fn with_modules(mut self, modules: ModuleCache) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Also look for imported modules in `dir`, after the directories
+    /// already on the search path (`LUX_PATH`, then `lib` and `tools`)
+    pub fn with_search_path(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.search_path.push(dir);
+        self
+    }
+
+    /// Resolve imports relative to the directory containing `file` first
+    pub fn with_current_file(mut self, file: &str) -> Self {
+        self.current_file_dir = search::file_dir(file);
+        self
+    }
+
+    /// Limit how deeply function calls may nest before a "stack overflow"
+    /// runtime error is raised (default [`DEFAULT_MAX_DEPTH`])
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Choose what int arithmetic does on overflow; by default
+    /// ([`IntOverflow::Checked`]) it raises an "integer overflow" runtime error
+    pub fn with_int_overflow(mut self, policy: IntOverflow) -> Self {
+        self.int_overflow = policy;
+        self
+    }
+
+    /// Create an interpreter for running a spawned task on another thread
+    fn task_interpreter(&self) -> Interpreter {
+        Interpreter {
+            env: self.env.clone(),
+            control_flow: ControlFlow::None,
+            executor: self.executor.clone(),
+            loaded_modules: HashMap::new(),
+            modules: self.modules.clone(),
+            imports: ImportStack::new(),
+            search_path: self.search_path.clone(),
+            module_scopes: self.module_scopes.clone(),
+            current_file_dir: None,
+            output: self.output.clone(),
+            rng: self.rng.clone(),
+            started: self.started,
+            script_args: self.script_args.clone(),
+            call_stack: Vec::new(),
+            depth: 0,
+            max_depth: self.max_depth,
+            int_overflow: self.int_overflow,
+            defers: Vec::new(),
+        }
     }
 
-    fn register_builtins(&mut self) {
+    fn register_builtins(env: &mut Environment) {
         // print function
-        self.env.define(
+        env.define(
             "print".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "print".to_string(),
+                name: "print",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Interpreter(|interp, args| {
+                    let mut output = interp.output.lock().map_err(|_| "Output stream is poisoned".to_string())?;
+                    writeln!(output, "{}", args[0]).map_err(|e| format!("Failed to write output: {}", e))?;
+                    Ok(Value::Nil)
+                }),
+            }),
+        );
+
+        // dump(value) -> nil, printing value with nested tables written out
+        env.define(
+            "dump".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "dump",
                 arity: 1,
-                func: |args| {
-                    println!("{}", args[0]);
+                variadic: false,
+                func: NativeFn::Interpreter(|interp, args| {
+                    let mut output = interp.output.lock().map_err(|_| "Output stream is poisoned".to_string())?;
+                    writeln!(output, "{}", args[0].display()).map_err(|e| format!("Failed to write output: {}", e))?;
                     Ok(Value::Nil)
-                },
+                }),
+            }),
+        );
+
+        // inspect(value) -> string holding what dump would print, with strings quoted
+        env.define(
+            "inspect".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "inspect",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| Ok(Value::String(args[0].inspect()))),
+            }),
+        );
+
+        // assert(cond, message?) -> nil, raising an error when `cond` is falsy
+        env.define(
+            "assert".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "assert",
+                arity: 1,
+                variadic: true,
+                func: NativeFn::Pure(|args| match args {
+                    [cond] | [cond, _] if cond.is_truthy() => Ok(Value::Nil),
+                    [_] => Err("assertion failed".to_string()),
+                    [_, message] => Err(message.to_string()),
+                    _ => Err(format!("assert expects a condition and an optional message, got {} arguments", args.len())),
+                }),
             }),
         );
 
         // setmetatable function
-        self.env.define(
+        env.define(
             "setmetatable".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "setmetatable".to_string(),
+                name: "setmetatable",
                 arity: 2,
-                func: |args| {
-                    if let (Value::Table(mut table), Value::Table(meta)) = (args[0].clone(), args[1].clone()) {
-                        table.metatable = Some(Box::new(meta));
-                        Ok(Value::Table(table))
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let (Value::Table(table), Value::Table(meta)) = (&args[0], &args[1]) {
+                        table.lock().unwrap().metatable = Some(meta.clone());
+                        Ok(args[0].clone())
                     } else {
                         Err("setmetatable expects two tables".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // getmetatable function
-        self.env.define(
+        env.define(
             "getmetatable".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "getmetatable".to_string(),
+                name: "getmetatable",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::Table(table) = &args[0] {
-                        if let Some(meta) = &table.metatable {
-                            Ok(Value::Table((**meta).clone()))
+                        if let Some(meta) = &table.lock().unwrap().metatable {
+                            Ok(Value::Table(meta.clone()))
                         } else {
                             Ok(Value::Nil)
                         }
                     } else {
                         Err("getmetatable expects a table".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // read_file function
-        self.env.define(
+        env.define(
             "read_file".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "read_file".to_string(),
+                name: "read_file",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::String(path) = &args[0] {
                         match std::fs::read_to_string(path) {
                             Ok(content) => Ok(Value::String(content)),
@@ -157,17 +414,18 @@ impl Interpreter {
                     } else {
                         Err("read_file expects a string path".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // write_file function
-        self.env.define(
+        env.define(
             "write_file".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "write_file".to_string(),
+                name: "write_file",
                 arity: 2,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let (Value::String(path), Value::String(content)) = (&args[0], &args[1]) {
                         match std::fs::write(path, content) {
                             Ok(_) => Ok(Value::Nil),
@@ -176,17 +434,43 @@ impl Interpreter {
                     } else {
                         Err("write_file expects two strings (path, content)".to_string())
                     }
-                },
+                }),
+            }),
+        );
+
+        // append_file function (creates the file if it does not exist)
+        env.define(
+            "append_file".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "append_file",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let (Value::String(path), Value::String(content)) = (&args[0], &args[1]) {
+                        let result = std::fs::OpenOptions::new()
+                            .create(true)
+                            .append(true)
+                            .open(path)
+                            .and_then(|mut file| file.write_all(content.as_bytes()));
+                        match result {
+                            Ok(_) => Ok(Value::Nil),
+                            Err(e) => Err(format!("Failed to append to file '{}': {}", path, e)),
+                        }
+                    } else {
+                        Err("append_file expects two strings (path, content)".to_string())
+                    }
+                }),
             }),
         );
 
         // string_split function
-        self.env.define(
+        env.define(
             "string_split".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_split".to_string(),
+                name: "string_split",
                 arity: 2,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let (Value::String(text), Value::String(delimiter)) = (&args[0], &args[1]) {
                         let parts: Vec<Value> = text
                             .split(delimiter.as_str())
@@ -194,121 +478,568 @@ impl Interpreter {
                             .collect();
                         let mut table = TableValue::new();
                         table.array = parts;
-                        Ok(Value::Table(table))
+                        Ok(Value::table(table))
                     } else {
                         Err("string_split expects two strings (text, delimiter)".to_string())
                     }
-                },
+                }),
+            }),
+        );
+
+        // split_lines(text: string) -> table of lines, without their "\n" or "\r\n" endings
+        env.define(
+            "split_lines".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "split_lines",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let Value::String(text) = &args[0] else {
+                        return Err(format!("split_lines expects a string, got {}", args[0].type_name()));
+                    };
+                    let mut table = TableValue::new();
+                    table.array = text.lines().map(|line| Value::String(line.to_string())).collect();
+                    Ok(Value::table(table))
+                }),
+            }),
+        );
+
+        // join_lines(lines: table) -> string joining the array part with "\n"
+        env.define(
+            "join_lines".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "join_lines",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let Value::Table(table) = &args[0] else {
+                        return Err(format!("join_lines expects a table, got {}", args[0].type_name()));
+                    };
+                    Ok(Value::String(Interpreter::array_strings("join_lines", table)?.join("\n")))
+                }),
             }),
         );
 
         // string_contains function
-        self.env.define(
+        env.define(
             "string_contains".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_contains".to_string(),
+                name: "string_contains",
                 arity: 2,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let (Value::String(text), Value::String(pattern)) = (&args[0], &args[1]) {
                         Ok(Value::Bool(text.contains(pattern.as_str())))
                     } else {
                         Err("string_contains expects two strings (text, pattern)".to_string())
                     }
-                },
+                }),
+            }),
+        );
+
+        // string_find(text: string, needle: string, start?: int) -> int
+        env.define(
+            "string_find".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "string_find",
+                arity: 2,
+                variadic: true,
+                func: NativeFn::Pure(|args| {
+                    let start = match args.get(2) {
+                        None => 1,
+                        Some(Value::Int(n)) => (*n).max(1) as usize,
+                        Some(_) => return Err("string_find expects an int start offset".to_string()),
+                    };
+                    if args.len() > 3 {
+                        return Err(format!("string_find expects at most 3 arguments, got {}", args.len()));
+                    }
+                    if let (Value::String(text), Value::String(needle)) = (&args[0], &args[1]) {
+                        // Search by chars so the result is a character index, not a byte offset
+                        let text: Vec<char> = text.chars().collect();
+                        let needle: Vec<char> = needle.chars().collect();
+                        let position = (start - 1..=text.len().saturating_sub(needle.len()))
+                            .find(|&i| i + needle.len() <= text.len() && text[i..i + needle.len()] == needle[..])
+                            .map(|i| i as i64 + 1)
+                            .unwrap_or(0);
+                        Ok(Value::Int(position))
+                    } else {
+                        Err("string_find expects (string, string)".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // char_at(text: string, index: int) -> string
+        env.define(
+            "char_at".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "char_at",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let (Value::String(text), Value::Int(index)) = (&args[0], &args[1]) {
+                        // 1-based and char-aware; out-of-range indices yield ""
+                        let c = if *index >= 1 {
+                            text.chars().nth((*index - 1) as usize)
+                        } else {
+                            None
+                        };
+                        Ok(Value::String(c.map(String::from).unwrap_or_default()))
+                    } else {
+                        Err("char_at expects (string, int)".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // string_chars(text: string) -> table
+        env.define(
+            "string_chars".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "string_chars",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::String(text) = &args[0] {
+                        let mut table = TableValue::new();
+                        table.array = text.chars().map(|c| Value::String(c.to_string())).collect();
+                        Ok(Value::table(table))
+                    } else {
+                        Err("string_chars expects a string".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // ord(text: string) -> int codepoint of the first character
+        env.define(
+            "ord".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "ord",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| match &args[0] {
+                    Value::String(text) => text
+                        .chars()
+                        .next()
+                        .map(|c| Value::Int(c as i64))
+                        .ok_or_else(|| "ord expects a non-empty string".to_string()),
+                    other => Err(format!("ord expects a string, got {}", other.type_name())),
+                }),
+            }),
+        );
+
+        // chr(code: int) -> string holding the character with that codepoint
+        env.define(
+            "chr".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "chr",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| match &args[0] {
+                    Value::Int(code) => u32::try_from(*code)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .map(|c| Value::String(c.to_string()))
+                        .ok_or_else(|| format!("chr: {} is not a valid Unicode codepoint", code)),
+                    other => Err(format!("chr expects an int, got {}", other.type_name())),
+                }),
             }),
         );
 
         // string_starts_with function
-        self.env.define(
+        env.define(
             "string_starts_with".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_starts_with".to_string(),
+                name: "string_starts_with",
                 arity: 2,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let (Value::String(text), Value::String(prefix)) = (&args[0], &args[1]) {
                         Ok(Value::Bool(text.starts_with(prefix.as_str())))
                     } else {
                         Err("string_starts_with expects two strings (text, prefix)".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // string_trim function
-        self.env.define(
+        env.define(
             "string_trim".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_trim".to_string(),
+                name: "string_trim",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::String(text) = &args[0] {
                         Ok(Value::String(text.trim().to_string()))
                     } else {
                         Err("string_trim expects a string".to_string())
                     }
-                },
+                }),
+            }),
+        );
+
+        let one_sided_trims = [
+            // string_trim_start(text: string) -> string without leading whitespace
+            ("string_trim_start", NativeFn::Pure(|args| match &args[0] {
+                Value::String(text) => Ok(Value::String(text.trim_start().to_string())),
+                other => Err(format!("string_trim_start expects a string, got {}", other.type_name())),
+            })),
+            // string_trim_end(text: string) -> string without trailing whitespace
+            ("string_trim_end", NativeFn::Pure(|args| match &args[0] {
+                Value::String(text) => Ok(Value::String(text.trim_end().to_string())),
+                other => Err(format!("string_trim_end expects a string, got {}", other.type_name())),
+            })),
+        ];
+        for (name, func) in one_sided_trims {
+            env.define(
+                name.to_string(),
+                Value::NativeFunction(NativeFunctionValue {
+                    name,
+                    arity: 1,
+                    variadic: false,
+                    func,
+                }),
+            );
+        }
+
+        // string_trim_chars(text: string, chars: string) -> string with any of
+        // `chars` stripped from both ends
+        env.define(
+            "string_trim_chars".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "string_trim_chars",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| match (&args[0], &args[1]) {
+                    (Value::String(text), Value::String(chars)) => {
+                        Ok(Value::String(text.trim_matches(|c| chars.contains(c)).to_string()))
+                    }
+                    _ => Err("string_trim_chars expects (string, string)".to_string()),
+                }),
             }),
         );
 
         // string_length function
-        self.env.define(
+        env.define(
             "string_length".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_length".to_string(),
+                name: "string_length",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::String(text) = &args[0] {
-                        Ok(Value::Int(text.len() as i64))
+                        Ok(Value::Int(text.chars().count() as i64))
                     } else {
                         Err("string_length expects a string".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // table_length function (for arrays)
-        self.env.define(
+        env.define(
             "table_length".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "table_length".to_string(),
+                name: "table_length",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::Table(table) = &args[0] {
-                        Ok(Value::Int(table.array.len() as i64))
+                        Ok(Value::Int(table.lock().unwrap().len() as i64))
                     } else {
                         Err("table_length expects a table".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // table_push function
-        self.env.define(
+        env.define(
             "table_push".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "table_push".to_string(),
+                name: "table_push",
                 arity: 2,
-                func: |args| {
-                    if let Value::Table(mut table) = args[0].clone() {
-                        table.array.push(args[1].clone());
-                        Ok(Value::Table(table))
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::Table(table) = &args[0] {
+                        table.lock().unwrap().array.push(args[1].clone());
+                        Ok(args[0].clone())
                     } else {
                         Err("table_push expects a table as first argument".to_string())
                     }
-                },
+                }),
+            }),
+        );
+
+        // count(t: table) -> int number of non-nil entries, including fields
+        env.define(
+            "count".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "count",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| match &args[0] {
+                    Value::Table(table) => Ok(Value::Int(table.lock().unwrap().count() as i64)),
+                    other => Err(format!("count expects a table, got {}", other.type_name())),
+                }),
+            }),
+        );
+
+        // table_concat(t: table, sep: string) -> string joining the array part
+        env.define(
+            "table_concat".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "table_concat",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let (Value::Table(table), Value::String(separator)) = (&args[0], &args[1]) else {
+                        return Err("table_concat expects a table and a separator string".to_string());
+                    };
+                    Ok(Value::String(Interpreter::array_strings("table_concat", table)?.join(separator)))
+                }),
+            }),
+        );
+
+        // table_slice(t: table, start: int, end: int) -> table of t[start..=end], clamped
+        env.define(
+            "table_slice".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "table_slice",
+                arity: 3,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let (Value::Table(table), Value::Int(start), Value::Int(end)) = (&args[0], &args[1], &args[2]) else {
+                        return Err("table_slice expects a table and two int bounds".to_string());
+                    };
+                    let table = table.lock().unwrap();
+                    let start = (*start).max(1) as usize;
+                    let end = (*end).min(table.array.len() as i64);
+                    let mut slice = TableValue::new();
+                    if end >= start as i64 {
+                        slice.array = table.array[start - 1..end as usize].to_vec();
+                    }
+                    Ok(Value::table(slice))
+                }),
+            }),
+        );
+
+        // table_reverse(t: table) -> table holding t's array part in reverse order
+        env.define(
+            "table_reverse".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "table_reverse",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let Value::Table(table) = &args[0] else {
+                        return Err("table_reverse expects a table".to_string());
+                    };
+                    let mut reversed = TableValue::new();
+                    reversed.array = table.lock().unwrap().array.iter().rev().cloned().collect();
+                    Ok(Value::table(reversed))
+                }),
+            }),
+        );
+
+        // table_contains(t: table, value) -> bool
+        env.define(
+            "table_contains".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "table_contains",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    Interpreter::array_position("table_contains", &args[0], &args[1])
+                        .map(|position| Value::Bool(position.is_some()))
+                }),
+            }),
+        );
+
+        // index_of(t: table, value) -> int: 1-based position in t's array part, or 0
+        env.define(
+            "index_of".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "index_of",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    Interpreter::array_position("index_of", &args[0], &args[1])
+                        .map(|position| Value::Int(position.map_or(0, |i| i as i64 + 1)))
+                }),
+            }),
+        );
+
+        // pairs(t: table) -> table of {key, value} entries (array part, then fields)
+        env.define(
+            "pairs".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "pairs",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::Table(table) = &args[0] {
+                        let mut result = TableValue::new();
+                        let entries = table.lock().unwrap().entries();
+                        result.array = entries
+                            .into_iter()
+                            .map(|(key, value)| Interpreter::entry_value(key, value))
+                            .collect();
+                        Ok(Value::table(result))
+                    } else {
+                        Err("pairs expects a table".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // ipairs(t: table) -> table of {key, value} entries up to the first nil
+        env.define(
+            "ipairs".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "ipairs",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::Table(table) = &args[0] {
+                        let mut result = TableValue::new();
+                        let array = table.lock().unwrap().array.clone();
+                        result.array = array
+                            .into_iter()
+                            .take_while(|value| !matches!(value, Value::Nil))
+                            .enumerate()
+                            .map(|(i, value)| Interpreter::entry_value(Value::Int(i as i64 + 1), value))
+                            .collect();
+                        Ok(Value::table(result))
+                    } else {
+                        Err("ipairs expects a table".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // next(t: table, key) -> {key, value} entry following `key`, or nil at the end
+        env.define(
+            "next".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "next",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let table = match &args[0] {
+                        Value::Table(table) => table,
+                        _ => return Err("next expects a table as first argument".to_string()),
+                    };
+                    let entries = table.lock().unwrap().entries();
+                    let index = match &args[1] {
+                        Value::Nil => 0,
+                        key => match entries.iter().position(|(k, _)| k == key) {
+                            Some(position) => position + 1,
+                            None => return Err(format!("next: key {} is not in the table", key)),
+                        },
+                    };
+                    Ok(entries
+                        .into_iter()
+                        .nth(index)
+                        .map(|(key, value)| Interpreter::entry_value(key, value))
+                        .unwrap_or(Value::Nil))
+                }),
+            }),
+        );
+
+        // keys(t: table) -> table, in the same order as `values` and `pairs`
+        env.define(
+            "keys".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "keys",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::Table(table) = &args[0] {
+                        let mut result = TableValue::new();
+                        let entries = table.lock().unwrap().entries();
+                        result.array = entries.into_iter().map(|(key, _)| key).collect();
+                        Ok(Value::table(result))
+                    } else {
+                        Err("keys expects a table".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // values(t: table) -> table, in the same order as `keys` and `pairs`
+        env.define(
+            "values".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "values",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::Table(table) = &args[0] {
+                        let mut result = TableValue::new();
+                        let entries = table.lock().unwrap().entries();
+                        result.array = entries.into_iter().map(|(_, value)| value).collect();
+                        Ok(Value::table(result))
+                    } else {
+                        Err("values expects a table".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // deep_copy(value) -> value with every reachable table copied
+        env.define(
+            "deep_copy".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "deep_copy",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| Ok(args[0].deep_copy())),
+            }),
+        );
+
+        // weak_ref(t: table) -> weakref that doesn't keep `t` alive
+        env.define(
+            "weak_ref".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "weak_ref",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| match &args[0] {
+                    Value::Table(table) => Ok(Value::WeakRef(Arc::downgrade(table))),
+                    other => Err(format!("weak_ref expects a table, got {}", other.type_name())),
+                }),
+            }),
+        );
+
+        // weak_get(ref: weakref) -> the table, or nil once it has been dropped
+        env.define(
+            "weak_get".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "weak_get",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| match &args[0] {
+                    Value::WeakRef(weak) => Ok(weak.upgrade().map(Value::Table).unwrap_or(Value::Nil)),
+                    other => Err(format!("weak_get expects a weakref, got {}", other.type_name())),
+                }),
             }),
         );
 
         // parse_lux function - parses Lux source code and returns AST as table
-        self.env.define(
+        env.define(
             "parse_lux".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "parse_lux".to_string(),
+                name: "parse_lux",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::String(source) = &args[0] {
                         // Tokenize
-                        let mut lexer = Lexer::new(source.as_str(), None);
+                        let mut lexer = Lexer::new(source.as_str(), None).with_newlines();
                         let tokens = match lexer.tokenize() {
                             Ok(t) => t,
                             Err(e) => return Err(format!("Lexer error: {}", e)),
@@ -326,17 +1057,18 @@ impl Interpreter {
                     } else {
                         Err("parse_lux expects a string (source code)".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // type_of(value) -> string
-        self.env.define(
+        env.define(
             "type_of".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "type_of".to_string(),
+                name: "type_of",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     let type_name = match &args[0] {
                         Value::Int(_) => "int",
                         Value::Float(_) => "float",
@@ -347,39 +1079,80 @@ impl Interpreter {
                         Value::Function(_) => "function",
                         Value::NativeFunction(_) => "function",
                         Value::Pointer(_) => "pointer",
+                        Value::Tuple(_) => "tuple",
+                        Value::WeakRef(_) => "weakref",
                     };
                     Ok(Value::String(type_name.to_string()))
-                },
+                }),
             }),
         );
 
+        // is_int(value) -> bool, and likewise for the other value types
+        let predicates: [(&str, NativeFn); 7] = [
+            ("is_int", NativeFn::Pure(|args| Ok(Value::Bool(matches!(args[0], Value::Int(_)))))),
+            ("is_float", NativeFn::Pure(|args| Ok(Value::Bool(matches!(args[0], Value::Float(_)))))),
+            ("is_string", NativeFn::Pure(|args| Ok(Value::Bool(matches!(args[0], Value::String(_)))))),
+            ("is_bool", NativeFn::Pure(|args| Ok(Value::Bool(matches!(args[0], Value::Bool(_)))))),
+            ("is_table", NativeFn::Pure(|args| Ok(Value::Bool(matches!(args[0], Value::Table(_)))))),
+            ("is_nil", NativeFn::Pure(|args| Ok(Value::Bool(matches!(args[0], Value::Nil))))),
+            ("is_function", NativeFn::Pure(|args| {
+                Ok(Value::Bool(matches!(args[0], Value::Function(_) | Value::NativeFunction(_))))
+            })),
+        ];
+        for (name, func) in predicates {
+            env.define(
+                name.to_string(),
+                Value::NativeFunction(NativeFunctionValue {
+                    name,
+                    arity: 1,
+                    variadic: false,
+                    func,
+                }),
+            );
+        }
+
         // to_string(value) -> string
-        self.env.define(
+        env.define(
             "to_string".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "to_string".to_string(),
+                name: "to_string",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     let s = match &args[0] {
                         Value::Int(i) => i.to_string(),
                         Value::Float(f) => f.to_string(),
                         Value::String(s) => s.clone(),
                         Value::Bool(b) => b.to_string(),
                         Value::Nil => "nil".to_string(),
-                        _ => format!("{:?}", args[0]),
+                        other => other.to_string(),
                     };
                     Ok(Value::String(s))
-                },
+                }),
             }),
         );
 
-        // to_int(value) -> int
-        self.env.define(
+        // to_int(value) -> int, to_int(text: string, base: int) -> int
+        env.define(
             "to_int".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "to_int".to_string(),
+                name: "to_int",
                 arity: 1,
-                func: |args| {
+                variadic: true,
+                func: NativeFn::Pure(|args| {
+                    match args {
+                        [_] => {}
+                        [Value::String(s), Value::Int(base)] => {
+                            if !(2..=36).contains(base) {
+                                return Err(format!("to_int: base must be between 2 and 36, got {}", base));
+                            }
+                            return i64::from_str_radix(s, *base as u32)
+                                .map(Value::Int)
+                                .map_err(|_| format!("Cannot convert '{}' to int in base {}", s, base));
+                        }
+                        [_, _] => return Err("to_int with a base expects a string and an int base".to_string()),
+                        _ => return Err(format!("to_int expects a value and optional base, got {} arguments", args.len())),
+                    }
                     match &args[0] {
                         Value::Int(i) => Ok(Value::Int(*i)),
                         Value::Float(f) => Ok(Value::Int(*f as i64)),
@@ -391,17 +1164,18 @@ impl Interpreter {
                         Value::Bool(b) => Ok(Value::Int(if *b { 1 } else { 0 })),
                         _ => Err("Cannot convert to int".to_string()),
                     }
-                },
+                }),
             }),
         );
 
         // to_float(value) -> float
-        self.env.define(
+        env.define(
             "to_float".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "to_float".to_string(),
+                name: "to_float",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     match &args[0] {
                         Value::Int(i) => Ok(Value::Float(*i as f64)),
                         Value::Float(f) => Ok(Value::Float(*f)),
@@ -412,125 +1186,218 @@ impl Interpreter {
                         }
                         _ => Err("Cannot convert to float".to_string()),
                     }
-                },
+                }),
             }),
         );
 
         // substring(text: string, start: int, length: int) -> string
-        self.env.define(
+        env.define(
             "substring".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "substring".to_string(),
+                name: "substring",
                 arity: 3,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let (Value::String(text), Value::Int(start), Value::Int(length)) = (&args[0], &args[1], &args[2]) {
-                        let start = *start as usize;
-                        let length = *length as usize;
                         let chars: Vec<char> = text.chars().collect();
+                        // Negative starts count back from the end of the string
+                        let start = if *start < 0 { chars.len() as i64 + *start } else { *start };
 
-                        if start >= chars.len() {
+                        if start < 0 || start as usize >= chars.len() || *length < 0 {
                             return Ok(Value::String(String::new()));
                         }
+                        let start = start as usize;
+                        let length = *length as usize;
 
-                        let end = std::cmp::min(start + length, chars.len());
+                        let end = std::cmp::min(start.saturating_add(length), chars.len());
                         let result: String = chars[start..end].iter().collect();
                         Ok(Value::String(result))
                     } else {
                         Err("substring expects (string, int, int)".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // string_replace(text: string, from: string, to: string) -> string
-        self.env.define(
+        env.define(
             "string_replace".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_replace".to_string(),
+                name: "string_replace",
                 arity: 3,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let (Value::String(text), Value::String(from), Value::String(to)) = (&args[0], &args[1], &args[2]) {
                         Ok(Value::String(text.replace(from, to)))
                     } else {
                         Err("string_replace expects (string, string, string)".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // string_upper(text: string) -> string
-        self.env.define(
+        env.define(
             "string_upper".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_upper".to_string(),
+                name: "string_upper",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::String(text) = &args[0] {
                         Ok(Value::String(text.to_uppercase()))
                     } else {
                         Err("string_upper expects a string".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // string_lower(text: string) -> string
-        self.env.define(
+        env.define(
             "string_lower".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_lower".to_string(),
+                name: "string_lower",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let Value::String(text) = &args[0] {
                         Ok(Value::String(text.to_lowercase()))
                     } else {
                         Err("string_lower expects a string".to_string())
                     }
-                },
+                }),
             }),
         );
 
         // string_ends_with(text: string, suffix: string) -> bool
-        self.env.define(
+        env.define(
             "string_ends_with".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "string_ends_with".to_string(),
+                name: "string_ends_with",
                 arity: 2,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     if let (Value::String(text), Value::String(suffix)) = (&args[0], &args[1]) {
                         Ok(Value::Bool(text.ends_with(suffix)))
                     } else {
                         Err("string_ends_with expects (string, string)".to_string())
                     }
-                },
+                }),
+            }),
+        );
+
+        // string_repeat(text: string, count: int) -> string
+        env.define(
+            "string_repeat".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "string_repeat",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| match (&args[0], &args[1]) {
+                    (Value::String(_), Value::Int(count)) if *count < 0 => {
+                        Err(format!("string_repeat: count must not be negative, got {}", count))
+                    }
+                    (Value::String(text), Value::Int(count)) => match text.len().checked_mul(*count as usize) {
+                        Some(size) if size <= MAX_STRING_LEN => Ok(Value::String(text.repeat(*count as usize))),
+                        _ => Err(format!("string_repeat: result too large ({} copies)", count)),
+                    },
+                    _ => Err("string_repeat expects (string, int)".to_string()),
+                }),
+            }),
+        );
+
+        // pad_left(text: string, width: int, fill: string) -> string
+        env.define(
+            "pad_left".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "pad_left",
+                arity: 3,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let (text, padding) = Interpreter::padding("pad_left", args)?;
+                    Ok(Value::String(padding + text))
+                }),
+            }),
+        );
+
+        // pad_right(text: string, width: int, fill: string) -> string
+        env.define(
+            "pad_right".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "pad_right",
+                arity: 3,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let (text, padding) = Interpreter::padding("pad_right", args)?;
+                    Ok(Value::String(text.to_string() + &padding))
+                }),
+            }),
+        );
+
+        // string_format(fmt: string, ...args) -> string
+        env.define(
+            "string_format".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "string_format",
+                arity: 1,
+                variadic: true,
+                func: NativeFn::Pure(|args| {
+                    if let Value::String(fmt) = &args[0] {
+                        Interpreter::format_string(fmt, &args[1..])
+                    } else {
+                        Err("string_format expects a format string as first argument".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // format_float(x: number, mode: string, precision: int) -> string
+        env.define(
+            "format_float".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "format_float",
+                arity: 3,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    let x = Interpreter::number_arg("format_float", &args[0])?;
+                    let (Value::String(mode), Value::Int(precision)) = (&args[1], &args[2]) else {
+                        return Err("format_float expects (number, string, int)".to_string());
+                    };
+                    let precision = usize::try_from(*precision)
+                        .map_err(|_| format!("format_float: precision must not be negative, got {}", precision))?;
+                    Interpreter::format_float(x, mode, precision).map(Value::String)
+                }),
             }),
         );
 
         // Math functions
         // sqrt(x: float) -> float
-        self.env.define(
+        env.define(
             "sqrt".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "sqrt".to_string(),
+                name: "sqrt",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     let num = match &args[0] {
                         Value::Float(f) => *f,
                         Value::Int(i) => *i as f64,
                         _ => return Err("sqrt expects a number".to_string()),
                     };
                     Ok(Value::Float(num.sqrt()))
-                },
+                }),
             }),
         );
 
         // pow(base: float, exp: float) -> float
-        self.env.define(
+        env.define(
             "pow".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "pow".to_string(),
+                name: "pow",
                 arity: 2,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     let base = match &args[0] {
                         Value::Float(f) => *f,
                         Value::Int(i) => *i as f64,
@@ -542,85 +1409,112 @@ impl Interpreter {
                         _ => return Err("pow expects numbers".to_string()),
                     };
                     Ok(Value::Float(base.powf(exp)))
-                },
+                }),
             }),
         );
 
         // abs(x: number) -> number
-        self.env.define(
+        env.define(
             "abs".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "abs".to_string(),
+                name: "abs",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                // abs(i64::MIN) overflows like negating it does
+                func: NativeFn::Interpreter(|interp, args| {
                     match &args[0] {
-                        Value::Int(i) => Ok(Value::Int(i.abs())),
+                        Value::Int(i) => interp.int_value(i.overflowing_abs()),
                         Value::Float(f) => Ok(Value::Float(f.abs())),
                         _ => Err("abs expects a number".to_string()),
                     }
-                },
+                }),
             }),
         );
 
         // floor(x: float) -> int
-        self.env.define(
+        env.define(
             "floor".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "floor".to_string(),
+                name: "floor",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     let num = match &args[0] {
                         Value::Float(f) => *f,
                         Value::Int(i) => return Ok(Value::Int(*i)),
                         _ => return Err("floor expects a number".to_string()),
                     };
                     Ok(Value::Int(num.floor() as i64))
-                },
+                }),
             }),
         );
 
         // ceil(x: float) -> int
-        self.env.define(
+        env.define(
             "ceil".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "ceil".to_string(),
+                name: "ceil",
                 arity: 1,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     let num = match &args[0] {
                         Value::Float(f) => *f,
                         Value::Int(i) => return Ok(Value::Int(*i)),
                         _ => return Err("ceil expects a number".to_string()),
                     };
                     Ok(Value::Int(num.ceil() as i64))
-                },
+                }),
             }),
         );
 
-        // min(a: number, b: number) -> number
-        self.env.define(
-            "min".to_string(),
+        // round(x: number) -> int, round(x: number, digits: int) -> float
+        // Both round half away from zero; negative digits round to tens, hundreds, ...
+        env.define(
+            "round".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "min".to_string(),
-                arity: 2,
-                func: |args| {
-                    match (&args[0], &args[1]) {
+                name: "round",
+                arity: 1,
+                variadic: true,
+                func: NativeFn::Pure(|args| match args {
+                    [x] => Ok(Value::Int(Interpreter::number_arg("round", x)?.round() as i64)),
+                    [x, Value::Int(digits)] => {
+                        let x = Interpreter::number_arg("round", x)?;
+                        let scale = 10f64.powi((*digits).clamp(i32::MIN as i64, i32::MAX as i64) as i32);
+                        Ok(Value::Float((x * scale).round() / scale))
+                    }
+                    [_, other] => Err(format!("round expects an int number of digits, got {}", other.type_name())),
+                    _ => Err(format!("round expects a number and optional digits, got {} arguments", args.len())),
+                }),
+            }),
+        );
+
+        // min(a: number, b: number) -> number
+        env.define(
+            "min".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "min",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    match (&args[0], &args[1]) {
                         (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a.min(b))),
                         (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a.min(*b))),
                         (Value::Int(a), Value::Float(b)) => Ok(Value::Float((*a as f64).min(*b))),
                         (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a.min(*b as f64))),
                         _ => Err("min expects two numbers".to_string()),
                     }
-                },
+                }),
             }),
         );
 
         // max(a: number, b: number) -> number
-        self.env.define(
+        env.define(
             "max".to_string(),
             Value::NativeFunction(NativeFunctionValue {
-                name: "max".to_string(),
+                name: "max",
                 arity: 2,
-                func: |args| {
+                variadic: false,
+                func: NativeFn::Pure(|args| {
                     match (&args[0], &args[1]) {
                         (Value::Int(a), Value::Int(b)) => Ok(Value::Int(*a.max(b))),
                         (Value::Float(a), Value::Float(b)) => Ok(Value::Float(a.max(*b))),
@@ -628,9 +1522,503 @@ impl Interpreter {
                         (Value::Float(a), Value::Int(b)) => Ok(Value::Float(a.max(*b as f64))),
                         _ => Err("max expects two numbers".to_string()),
                     }
-                },
+                }),
+            }),
+        );
+
+        // clamp(x: number, lo: number, hi: number) -> number; float if any argument is
+        env.define(
+            "clamp".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "clamp",
+                arity: 3,
+                variadic: false,
+                func: NativeFn::Pure(|args| match (&args[0], &args[1], &args[2]) {
+                    (Value::Int(x), Value::Int(lo), Value::Int(hi)) if lo > hi => {
+                        Err(format!("clamp: lo ({}) is greater than hi ({})", lo, hi))
+                    }
+                    (Value::Int(x), Value::Int(lo), Value::Int(hi)) => Ok(Value::Int(*x.max(lo).min(hi))),
+                    _ => {
+                        let x = Interpreter::number_arg("clamp", &args[0])?;
+                        let lo = Interpreter::number_arg("clamp", &args[1])?;
+                        let hi = Interpreter::number_arg("clamp", &args[2])?;
+                        if lo > hi {
+                            return Err(format!("clamp: lo ({}) is greater than hi ({})", lo, hi));
+                        }
+                        Ok(Value::Float(x.max(lo).min(hi)))
+                    }
+                }),
+            }),
+        );
+
+        // min_of(t: table) / max_of(t: table) -> smallest / largest number in the array part
+        env.define(
+            "min_of".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "min_of",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| Interpreter::extreme_of("min_of", &args[0], |x, best| x < best)),
+            }),
+        );
+        env.define(
+            "max_of".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "max_of",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| Interpreter::extreme_of("max_of", &args[0], |x, best| x > best)),
+            }),
+        );
+
+        // random() -> float in [0, 1)
+        env.define(
+            "random".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "random",
+                arity: 0,
+                variadic: false,
+                func: NativeFn::Interpreter(|interp, _args| {
+                    Ok(Value::Float(interp.rng.lock().unwrap().next_float()))
+                }),
+            }),
+        );
+
+        // random_int(min: int, max: int) -> int in [min, max]
+        env.define(
+            "random_int".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "random_int",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Interpreter(|interp, args| {
+                    if let (Value::Int(min), Value::Int(max)) = (&args[0], &args[1]) {
+                        if min > max {
+                            return Err(format!("random_int: min ({}) is greater than max ({})", min, max));
+                        }
+                        Ok(Value::Int(interp.rng.lock().unwrap().next_int(*min, *max)))
+                    } else {
+                        Err("random_int expects (int, int)".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // seed(n: int) -> nil
+        env.define(
+            "seed".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "seed",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Interpreter(|interp, args| {
+                    if let Value::Int(n) = &args[0] {
+                        interp.rng.lock().unwrap().reseed(*n as u64);
+                        Ok(Value::Nil)
+                    } else {
+                        Err("seed expects an int".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // now_millis() -> int milliseconds since the Unix epoch
+        env.define(
+            "now_millis".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "now_millis",
+                arity: 0,
+                variadic: false,
+                func: NativeFn::Pure(|_args| {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_err(|e| format!("now_millis: system clock is before the epoch: {}", e))?;
+                    Ok(Value::Int(now.as_millis() as i64))
+                }),
+            }),
+        );
+
+        // clock() -> float seconds since the interpreter started (monotonic)
+        env.define(
+            "clock".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "clock",
+                arity: 0,
+                variadic: false,
+                func: NativeFn::Interpreter(|interp, _args| {
+                    Ok(Value::Float(interp.started.elapsed().as_secs_f64()))
+                }),
+            }),
+        );
+
+        // sleep(ms: int) -> nil
+        env.define(
+            "sleep".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "sleep",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    match &args[0] {
+                        Value::Int(ms) if *ms >= 0 => {
+                            std::thread::sleep(Duration::from_millis(*ms as u64));
+                            Ok(Value::Nil)
+                        }
+                        Value::Int(ms) => Err(format!("sleep: duration must not be negative, got {}", ms)),
+                        other => Err(format!("sleep expects an int, got {}", other.type_name())),
+                    }
+                }),
             }),
         );
+
+        // json_parse(text: string) -> value
+        env.define(
+            "json_parse".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "json_parse",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::String(text) = &args[0] {
+                        json::parse(text)
+                    } else {
+                        Err("json_parse expects a string".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // json_stringify(value) -> string
+        env.define(
+            "json_stringify".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "json_stringify",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| json::stringify(&args[0]).map(Value::String)),
+            }),
+        );
+
+        // Bytes are array tables of ints 0-255; the encoders also take a string
+        let encodings: [(&str, NativeFn); 4] = [
+            // to_hex(data: string | bytes) -> string
+            ("to_hex", NativeFn::Pure(|args| Ok(Value::String(encoding::to_hex(&encoding::bytes_arg("to_hex", &args[0])?))))),
+            // from_hex(text: string) -> bytes
+            ("from_hex", NativeFn::Pure(|args| match &args[0] {
+                Value::String(text) => encoding::from_hex(text).map(encoding::bytes_value),
+                other => Err(format!("from_hex expects a string, got {}", other.type_name())),
+            })),
+            // base64_encode(data: string | bytes) -> string
+            ("base64_encode", NativeFn::Pure(|args| {
+                Ok(Value::String(encoding::base64_encode(&encoding::bytes_arg("base64_encode", &args[0])?)))
+            })),
+            // base64_decode(text: string) -> bytes
+            ("base64_decode", NativeFn::Pure(|args| match &args[0] {
+                Value::String(text) => encoding::base64_decode(text).map(encoding::bytes_value),
+                other => Err(format!("base64_decode expects a string, got {}", other.type_name())),
+            })),
+        ];
+        for (name, func) in encodings {
+            env.define(
+                name.to_string(),
+                Value::NativeFunction(NativeFunctionValue {
+                    name,
+                    arity: 1,
+                    variadic: false,
+                    func,
+                }),
+            );
+        }
+
+        // regex_match(text: string, pattern: string) -> bool
+        env.define(
+            "regex_match".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "regex_match",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| match (&args[0], &args[1]) {
+                    (Value::String(text), Value::String(regex)) => {
+                        Ok(Value::Bool(pattern::compile("regex_match", regex)?.is_match(text)))
+                    }
+                    _ => Err("regex_match expects (string, string)".to_string()),
+                }),
+            }),
+        );
+
+        // regex_find(text: string, pattern: string) -> string | nil (the first match)
+        env.define(
+            "regex_find".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "regex_find",
+                arity: 2,
+                variadic: false,
+                func: NativeFn::Pure(|args| match (&args[0], &args[1]) {
+                    (Value::String(text), Value::String(regex)) => Ok(pattern::compile("regex_find", regex)?
+                        .find(text)
+                        .map_or(Value::Nil, |found| Value::String(found.as_str().to_string()))),
+                    _ => Err("regex_find expects (string, string)".to_string()),
+                }),
+            }),
+        );
+
+        // regex_replace(text: string, pattern: string, replacement: string) -> string
+        // Replaces every match; $1 or ${name} in the replacement inserts a capture group
+        env.define(
+            "regex_replace".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "regex_replace",
+                arity: 3,
+                variadic: false,
+                func: NativeFn::Pure(|args| match (&args[0], &args[1], &args[2]) {
+                    (Value::String(text), Value::String(regex), Value::String(replacement)) => {
+                        let regex = pattern::compile("regex_replace", regex)?;
+                        Ok(Value::String(regex.replace_all(text, replacement.as_str()).into_owned()))
+                    }
+                    _ => Err("regex_replace expects (string, string, string)".to_string()),
+                }),
+            }),
+        );
+
+        // get_env(name: string) -> string | nil
+        env.define(
+            "get_env".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "get_env",
+                arity: 1,
+                variadic: false,
+                func: NativeFn::Pure(|args| {
+                    if let Value::String(name) = &args[0] {
+                        Ok(std::env::var(name).map(Value::String).unwrap_or(Value::Nil))
+                    } else {
+                        Err("get_env expects a string".to_string())
+                    }
+                }),
+            }),
+        );
+
+        // args() -> table of the script's command-line arguments
+        env.define(
+            "args".to_string(),
+            Value::NativeFunction(NativeFunctionValue {
+                name: "args",
+                arity: 0,
+                variadic: false,
+                func: NativeFn::Interpreter(|interp, _args| {
+                    let mut table = TableValue::new();
+                    table.array = interp.script_args.iter().cloned().map(Value::String).collect();
+                    Ok(Value::table(table))
+                }),
+            }),
+        );
+
+        // math module: math.sin(x), math.round(x), math.pi, ...
+        let mut math = TableValue::new();
+        let functions: [(&str, &str, NativeFn); 6] = [
+            ("sin", "math.sin", NativeFn::Pure(|args| Ok(Value::Float(Interpreter::number_arg("math.sin", &args[0])?.sin())))),
+            ("cos", "math.cos", NativeFn::Pure(|args| Ok(Value::Float(Interpreter::number_arg("math.cos", &args[0])?.cos())))),
+            ("tan", "math.tan", NativeFn::Pure(|args| Ok(Value::Float(Interpreter::number_arg("math.tan", &args[0])?.tan())))),
+            ("log", "math.log", NativeFn::Pure(|args| Ok(Value::Float(Interpreter::number_arg("math.log", &args[0])?.ln())))),
+            ("exp", "math.exp", NativeFn::Pure(|args| Ok(Value::Float(Interpreter::number_arg("math.exp", &args[0])?.exp())))),
+            // Rounds half away from zero
+            ("round", "math.round", NativeFn::Pure(|args| Ok(Value::Int(Interpreter::number_arg("math.round", &args[0])?.round() as i64)))),
+        ];
+        for (field, name, func) in functions {
+            math.fields.insert(
+                field.to_string(),
+                Value::NativeFunction(NativeFunctionValue {
+                    name,
+                    arity: 1,
+                    variadic: false,
+                    func,
+                }),
+            );
+        }
+        math.fields.insert("pi".to_string(), Value::Float(std::f64::consts::PI));
+        math.fields.insert("e".to_string(), Value::Float(std::f64::consts::E));
+        env.define("math".to_string(), Value::table(math));
+    }
+
+    /// The element of `table`'s array part that `better` prefers over every
+    /// earlier one, for `min_of`/`max_of`; ties keep the first element
+    fn extreme_of(name: &str, table: &Value, better: fn(f64, f64) -> bool) -> Result<Value, String> {
+        let Value::Table(table) = table else {
+            return Err(format!("{} expects a table, got {}", name, table.type_name()));
+        };
+        let table = table.lock().unwrap();
+        let mut best: Option<(f64, &Value)> = None;
+        for (i, element) in table.array.iter().enumerate() {
+            let number = match element {
+                Value::Int(n) => *n as f64,
+                Value::Float(f) => *f,
+                other => return Err(format!("{}: element {} is a {}, not a number", name, i + 1, other.type_name())),
+            };
+            if best.is_none_or(|(current, _)| better(number, current)) {
+                best = Some((number, element));
+            }
+        }
+        best.map(|(_, element)| element.clone())
+            .ok_or_else(|| format!("{}: table is empty", name))
+    }
+
+    /// The text argument of `pad_left`/`pad_right` and the fill it needs to
+    /// reach the requested width in chars; text that is already wide enough
+    /// gets no fill and is never truncated
+    fn padding<'a>(name: &str, args: &'a [Value]) -> Result<(&'a str, String), String> {
+        let (Value::String(text), Value::Int(width), Value::String(fill)) = (&args[0], &args[1], &args[2]) else {
+            return Err(format!("{} expects (string, int, string)", name));
+        };
+        let mut fill_chars = fill.chars();
+        let (Some(fill), None) = (fill_chars.next(), fill_chars.next()) else {
+            return Err(format!("{}: fill must be a single character, got \"{}\"", name, fill));
+        };
+        let missing = (*width).max(0) as usize;
+        let missing = missing.saturating_sub(text.chars().count());
+        let size = missing.checked_mul(fill.len_utf8()).and_then(|size| size.checked_add(text.len()));
+        if size.is_none_or(|size| size > MAX_STRING_LEN) {
+            return Err(format!("{}: result too large (width {})", name, width));
+        }
+        Ok((text, std::iter::repeat_n(fill, missing).collect()))
+    }
+
+    /// The array part of a table as strings, for builtins that join it;
+    /// numbers are formatted and any other element is an error
+    fn array_strings(name: &str, table: &TableRef) -> Result<Vec<String>, String> {
+        table
+            .lock()
+            .unwrap()
+            .array
+            .iter()
+            .enumerate()
+            .map(|(i, element)| match element {
+                Value::String(_) | Value::Int(_) | Value::Float(_) => Ok(element.to_string()),
+                other => Err(format!("{}: element {} is a {}, not a string or number", name, i + 1, other.type_name())),
+            })
+            .collect()
+    }
+
+    /// Index of the first element of `table`'s array part that `==` `value`
+    fn array_position(name: &str, table: &Value, value: &Value) -> Result<Option<usize>, String> {
+        let Value::Table(table) = table else {
+            return Err(format!("{} expects a table, got {}", name, table.type_name()));
+        };
+        let table = table.lock().unwrap();
+        Ok(table.array[..table.len()].iter().position(|element| element.equals(value)))
+    }
+
+    /// Read a numeric argument as a float, for math builtins
+    fn number_arg(name: &str, value: &Value) -> Result<f64, String> {
+        match value {
+            Value::Float(f) => Ok(*f),
+            Value::Int(i) => Ok(*i as f64),
+            _ => Err(format!("{} expects a number, got {}", name, value.type_name())),
+        }
+    }
+
+    /// Format `x` for `format_float`
+    ///
+    /// `"fixed"` writes `precision` digits after the point (`3.14`), `"sci"`
+    /// writes a mantissa with `precision` decimals and an exponent (`3.14e0`),
+    /// and `"general"` rounds to `precision` significant digits, then writes
+    /// whichever of those is shorter with trailing zeros dropped, like C's `%g`.
+    fn format_float(x: f64, mode: &str, precision: usize) -> Result<String, String> {
+        match mode {
+            "fixed" => Ok(format!("{:.*}", precision, x)),
+            "sci" => Ok(format!("{:.*e}", precision, x)),
+            "general" if !x.is_finite() || x == 0.0 => Ok(format!("{}", x)),
+            "general" => {
+                let digits = precision.max(1);
+                let sci = format!("{:.*e}", digits - 1, x);
+                let (mantissa, exponent) = sci.split_once('e').expect("{:e} output has an exponent");
+                let exponent: i64 = exponent.parse().expect("{:e} exponents are integers");
+                let trim = |s: &str| {
+                    if s.contains('.') {
+                        s.trim_end_matches('0').trim_end_matches('.').to_string()
+                    } else {
+                        s.to_string()
+                    }
+                };
+                if exponent < -4 || exponent >= digits as i64 {
+                    Ok(format!("{}e{}", trim(mantissa), exponent))
+                } else {
+                    let decimals = (digits as i64 - 1 - exponent) as usize;
+                    Ok(trim(&format!("{:.*}", decimals, x)))
+                }
+            }
+            other => Err(format!("format_float: unknown mode \"{}\" (expected \"fixed\", \"sci\" or \"general\")", other)),
+        }
+    }
+
+    /// Substitute `args` into the `%d`/`%i`/`%s`/`%f` specifiers of `fmt`
+    ///
+    /// `%f` takes an optional precision (`%.2f`) and `%%` is a literal percent.
+    fn format_string(fmt: &str, args: &[Value]) -> Result<Value, String> {
+        let mut result = String::new();
+        let mut chars = fmt.chars().peekable();
+        let mut remaining = args.iter();
+
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                result.push(c);
+                continue;
+            }
+
+            if chars.peek() == Some(&'%') {
+                chars.next();
+                result.push('%');
+                continue;
+            }
+
+            let mut precision = None;
+            if chars.peek() == Some(&'.') {
+                chars.next();
+                let mut digits = String::new();
+                while let Some(d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                    digits.push(*d);
+                    chars.next();
+                }
+                precision = Some(digits.parse::<usize>().map_err(|_| {
+                    "string_format: expected digits after '.' in specifier".to_string()
+                })?);
+            }
+
+            let spec = chars.next().ok_or("string_format: incomplete format specifier at end of string")?;
+            let arg = remaining
+                .next()
+                .ok_or_else(|| format!("string_format: missing argument for '%{}'", spec))?;
+
+            match (spec, arg) {
+                ('d' | 'i', Value::Int(n)) => result.push_str(&n.to_string()),
+                ('s', value) => result.push_str(&value.to_string()),
+                ('f', Value::Float(f)) => result.push_str(&format!("{:.*}", precision.unwrap_or(6), f)),
+                ('f', Value::Int(n)) => result.push_str(&format!("{:.*}", precision.unwrap_or(6), *n as f64)),
+                ('d' | 'i', value) => {
+                    return Err(format!(
+                        "string_format: '%{}' expects an integer, got {}",
+                        spec,
+                        value.type_name()
+                    ));
+                }
+                ('f', value) => {
+                    return Err(format!("string_format: '%f' expects a number, got {}", value.type_name()));
+                }
+                _ => return Err(format!("string_format: unknown format specifier '%{}'", spec)),
+            }
+        }
+
+        if remaining.next().is_some() {
+            return Err("string_format: too many arguments for format string".to_string());
+        }
+
+        Ok(Value::String(result))
+    }
+
+    /// Build the `{key = ..., value = ...}` table produced by `pairs`, `ipairs` and `next`
+    fn entry_value(key: Value, value: Value) -> Value {
+        let mut entry = TableValue::new();
+        entry.fields.insert("key".to_string(), key);
+        entry.fields.insert("value".to_string(), value);
+        Value::table(entry)
     }
 
     /// Convert AST to a Value (table structure) that Lux code can work with
@@ -642,7 +2030,7 @@ impl Interpreter {
             table.array.push(Self::stmt_to_value(stmt));
         }
 
-        Value::Table(table)
+        Value::table(table)
     }
 
     fn stmt_to_value(stmt: &Stmt) -> Value {
@@ -651,7 +2039,7 @@ impl Interpreter {
         match stmt {
             Stmt::VarDecl { name, type_annotation, initializer, .. } => {
                 table.fields.insert("type".to_string(), Value::String("VarDecl".to_string()));
-                table.fields.insert("name".to_string(), Value::String(name.clone()));
+                table.fields.insert("name".to_string(), Value::String(name.to_string()));
                 if let Some(vt) = type_annotation {
                     table.fields.insert("type_annotation".to_string(), Value::String(format!("{:?}", vt)));
                 }
@@ -661,27 +2049,27 @@ impl Interpreter {
             }
             Stmt::FunctionDecl { name, params, return_type, body, is_async, .. } => {
                 table.fields.insert("type".to_string(), Value::String("FunctionDecl".to_string()));
-                table.fields.insert("name".to_string(), Value::String(name.clone()));
+                table.fields.insert("name".to_string(), Value::String(name.to_string()));
                 table.fields.insert("is_async".to_string(), Value::Bool(*is_async));
 
                 let mut params_table = TableValue::new();
                 for (param_name, param_type) in params {
                     let mut param_table = TableValue::new();
-                    param_table.fields.insert("name".to_string(), Value::String(param_name.clone()));
+                    param_table.fields.insert("name".to_string(), Value::String(param_name.to_string()));
                     param_table.fields.insert("type".to_string(), Value::String(format!("{:?}", param_type)));
-                    params_table.array.push(Value::Table(param_table));
+                    params_table.array.push(Value::table(param_table));
                 }
-                table.fields.insert("params".to_string(), Value::Table(params_table));
+                table.fields.insert("params".to_string(), Value::table(params_table));
 
                 if let Some(rt) = return_type {
                     table.fields.insert("return_type".to_string(), Value::String(format!("{:?}", rt)));
                 }
 
                 let mut body_table = TableValue::new();
-                for s in body {
+                for s in body.iter() {
                     body_table.array.push(Self::stmt_to_value(s));
                 }
-                table.fields.insert("body".to_string(), Value::Table(body_table));
+                table.fields.insert("body".to_string(), Value::table(body_table));
             }
             Stmt::Return { value, .. } => {
                 table.fields.insert("type".to_string(), Value::String("Return".to_string()));
@@ -701,14 +2089,14 @@ impl Interpreter {
                 for s in then_branch {
                     then_table.array.push(Self::stmt_to_value(s));
                 }
-                table.fields.insert("then_branch".to_string(), Value::Table(then_table));
+                table.fields.insert("then_branch".to_string(), Value::table(then_table));
 
                 if let Some(else_b) = else_branch {
                     let mut else_table = TableValue::new();
                     for s in else_b {
                         else_table.array.push(Self::stmt_to_value(s));
                     }
-                    table.fields.insert("else_branch".to_string(), Value::Table(else_table));
+                    table.fields.insert("else_branch".to_string(), Value::table(else_table));
                 }
             }
             Stmt::While { condition, body, .. } => {
@@ -719,7 +2107,7 @@ impl Interpreter {
                 for s in body {
                     body_table.array.push(Self::stmt_to_value(s));
                 }
-                table.fields.insert("body".to_string(), Value::Table(body_table));
+                table.fields.insert("body".to_string(), Value::table(body_table));
             }
             Stmt::For { initializer, condition, increment, body, .. } => {
                 table.fields.insert("type".to_string(), Value::String("For".to_string()));
@@ -737,14 +2125,14 @@ impl Interpreter {
                 for s in body {
                     body_table.array.push(Self::stmt_to_value(s));
                 }
-                table.fields.insert("body".to_string(), Value::Table(body_table));
+                table.fields.insert("body".to_string(), Value::table(body_table));
             }
             _ => {
                 table.fields.insert("type".to_string(), Value::String(format!("{:?}", stmt)));
             }
         }
 
-        Value::Table(table)
+        Value::table(table)
     }
 
     fn expr_to_value(expr: &Expr) -> Value {
@@ -763,7 +2151,7 @@ impl Interpreter {
             }
             Expr::Variable { name, .. } => {
                 table.fields.insert("type".to_string(), Value::String("Variable".to_string()));
-                table.fields.insert("name".to_string(), Value::String(name.clone()));
+                table.fields.insert("name".to_string(), Value::String(name.to_string()));
             }
             Expr::Binary { left, operator, right, .. } => {
                 table.fields.insert("type".to_string(), Value::String("Binary".to_string()));
@@ -779,64 +2167,91 @@ impl Interpreter {
                 for arg in arguments {
                     args_table.array.push(Self::expr_to_value(arg));
                 }
-                table.fields.insert("arguments".to_string(), Value::Table(args_table));
+                table.fields.insert("arguments".to_string(), Value::table(args_table));
             }
             _ => {
                 table.fields.insert("type".to_string(), Value::String(format!("{:?}", expr)));
             }
         }
 
-        Value::Table(table)
+        Value::table(table)
     }
 
     pub fn interpret(&mut self, ast: &Ast) -> LuxResult<()> {
-        for stmt in &ast.statements {
-            self.execute_stmt(stmt)?;
-
-            // Check for early return at top level
-            if matches!(self.control_flow, ControlFlow::Return(_)) {
-                break;
-            }
-        }
+        self.eval(ast)?;
         Ok(())
     }
 
-    /// Execute a task (function with arguments)
-    fn execute_task(&mut self, task_id: usize, func: FunctionValue, args: Vec<Value>) -> LuxResult<Value> {
-        // Push a new scope for the function
-        self.env.push_scope();
+    /// Run a program and return its result
+    ///
+    /// The result is the value of a top-level `return`, or else the value of
+    /// the final statement when it is an expression statement, or else nil.
+    pub fn eval(&mut self, ast: &Ast) -> LuxResult<Value> {
+        // Top-level defers run when the program finishes
+        self.with_defers(|this| {
+            let mut result = Value::Nil;
+
+            // Define top-level functions up front so they can be called before
+            // the line that declares them
+            for stmt in &ast.statements {
+                if let Stmt::FunctionDecl { .. } = stmt {
+                    this.execute_stmt(stmt)?;
+                }
+            }
 
-        // Bind parameters
-        for (param, arg) in func.params.iter().zip(args.iter()) {
-            self.env.define(param.clone(), arg.clone());
-        }
+            for stmt in &ast.statements {
+                result = match stmt {
+                    // Already defined above; defining it again would undo
+                    // any assignment made to the name before this line
+                    Stmt::FunctionDecl { .. } => Value::Nil,
+                    Stmt::Expression { expr, .. } => this.eval_expr(expr)?,
+                    _ => {
+                        this.execute_stmt(stmt)?;
+                        Value::Nil
+                    }
+                };
 
-        // Execute the function body
-        for stmt in &func.body {
-            if let Err(e) = self.execute_stmt(stmt) {
-                self.executor.update_task_state(task_id, TaskState::Failed(e.to_string()));
-                self.env.pop_scope();
-                return Err(e);
+                // Check for early return at top level
+                if let ControlFlow::Return(value) = &this.control_flow {
+                    return Ok(value.clone());
+                }
             }
 
-            // Check for early return
-            if matches!(self.control_flow, ControlFlow::Return(_)) {
-                break;
-            }
+            Ok(result)
+        })
+    }
+
+    /// Look up a variable in the interpreter's environment, such as a builtin
+    pub(crate) fn global(&self, name: &str) -> Option<Value> {
+        self.env.get(Symbol::intern(name))
+    }
+
+    /// Execute a task (function with arguments)
+    fn execute_task(&mut self, task_id: usize, func: FunctionValue, args: Vec<Value>) -> LuxResult<Value> {
+        if let Err(e) = self.enter_call(&func.location) {
+            self.executor.update_task_state(task_id, TaskState::Failed(e.to_string()));
+            return Err(e);
         }
+        let result = self.run_task(task_id, func, args);
+        self.depth -= 1;
+        result
+    }
 
-        let return_value = match &self.control_flow {
-            ControlFlow::Return(v) => v.clone(),
-            _ => Value::Nil,
-        };
+    fn run_task(&mut self, task_id: usize, func: FunctionValue, args: Vec<Value>) -> LuxResult<Value> {
+        self.push_function_scope(&func, &args);
+
+        // Execute the function body
+        let result = self.with_defers(|this| this.execute_function_body(&func.body));
 
         // Reset control flow
         self.control_flow = ControlFlow::None;
+        self.pop_function_scope();
 
-        self.executor.update_task_state(task_id, TaskState::Completed(return_value.clone()));
-        self.env.pop_scope();
-
-        Ok(return_value)
+        match &result {
+            Ok(value) => self.executor.update_task_state(task_id, TaskState::Completed(value.clone())),
+            Err(e) => self.executor.update_task_state(task_id, TaskState::Failed(e.to_string())),
+        }
+        result
     }
 
     fn import_module(&mut self, path: &str, location: &SourceLocation) -> LuxResult<()> {
@@ -848,23 +2263,20 @@ impl Interpreter {
         // Resolve the module path
         let resolved_path = self.resolve_module_path(path, location)?;
 
-        // Read the file
-        let source = std::fs::read_to_string(&resolved_path)
-            .map_err(|e| LuxError::runtime_error(
-                format!("Failed to read module '{}': {}", path, e),
-                Some(location.clone()),
-            ))?;
+        // Read and parse the module, unless it is already cached
+        let ast = self.modules.load(&resolved_path, |e| {
+            LuxError::runtime_error(format!("Failed to read module '{}': {}", path, e), Some(location.clone()))
+        })?;
 
-        // Parse the module
-        let mut lexer = Lexer::new(&source, Some(&resolved_path));
-        let tokens = lexer.tokenize()?;
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse()?;
+        // A module whose top level is still running is importing itself
+        self.imports.enter(path).map_err(|cycle| {
+            LuxError::runtime_error(format!("Circular import: {}", cycle), Some(location.clone()))
+        })?;
 
         // Execute the module in the current environment
-        for stmt in &ast.statements {
-            self.execute_stmt(stmt)?;
-        }
+        let executed = ast.statements.iter().try_for_each(|stmt| self.execute_stmt(stmt));
+        self.imports.exit();
+        executed?;
 
         // Mark as loaded
         self.loaded_modules.insert(path.to_string(), true);
@@ -872,68 +2284,121 @@ impl Interpreter {
         Ok(())
     }
 
-    fn resolve_module_path(&self, path: &str, location: &SourceLocation) -> LuxResult<String> {
-        use std::path::Path;
-
-        // Try different locations:
-        // 1. Relative to current file directory
-        if let Some(ref current_dir) = self.current_file_dir {
-            let candidate = Path::new(current_dir).join(format!("{}.lux", path));
-            if candidate.exists() {
-                return Ok(candidate.to_string_lossy().to_string());
-            }
+    /// Run a module in a scope of its own and return its top-level bindings.
+    /// Each module runs at most once; later imports reuse its bindings.
+    fn module_exports(&mut self, path: &str, location: &SourceLocation) -> LuxResult<HashMap<Symbol, Value>> {
+        let resolved_path = self.resolve_module_path(path, location)?;
+        if let Some(exports) = self.module_scopes.get(&resolved_path) {
+            return Ok(exports.clone());
         }
 
-        // 2. In lib/ directory
-        let lib_path = Path::new("lib").join(format!("{}.lux", path));
-        if lib_path.exists() {
-            return Ok(lib_path.to_string_lossy().to_string());
-        }
+        let ast = self.modules.load(&resolved_path, |e| {
+            LuxError::runtime_error(format!("Failed to read module '{}': {}", path, e), Some(location.clone()))
+        })?;
+        self.imports.enter(path).map_err(|cycle| {
+            LuxError::runtime_error(format!("Circular import: {}", cycle), Some(location.clone()))
+        })?;
 
-        // 3. In tools/ directory
-        let tools_path = Path::new("tools").join(format!("{}.lux", path));
-        if tools_path.exists() {
-            return Ok(tools_path.to_string_lossy().to_string());
-        }
+        self.env.push_scope();
+        let executed = ast.statements.iter().try_for_each(|stmt| self.execute_stmt(stmt));
+        let exports = self.env.take_scope();
+        self.imports.exit();
+        executed?;
 
-        // 4. As absolute or relative path with .lux extension
-        let direct_path_str = format!("{}.lux", path);
-        let direct_path = Path::new(&direct_path_str);
-        if direct_path.exists() {
-            return Ok(direct_path.to_string_lossy().to_string());
-        }
+        self.module_scopes.insert(resolved_path, exports.clone());
+        Ok(exports)
+    }
 
-        Err(LuxError::runtime_error(
-            format!("Module '{}' not found", path),
-            Some(location.clone()),
-        ))
+    fn resolve_module_path(&self, path: &str, location: &SourceLocation) -> LuxResult<String> {
+        let current_dir = self.current_file_dir.as_deref().map(std::path::Path::new);
+        match self.search_path.resolve(path, current_dir) {
+            Some(resolved) => Ok(resolved.to_string_lossy().to_string()),
+            None => Err(LuxError::runtime_error(
+                format!("Module '{}' not found", path),
+                Some(location.clone()),
+            )),
+        }
     }
 
     fn execute_stmt(&mut self, stmt: &Stmt) -> LuxResult<()> {
         match stmt {
-            Stmt::Import { path, location } => {
-                self.import_module(path, location)?;
+            Stmt::Import { path, kind, location } => {
+                match kind {
+                    ImportKind::All => self.import_module(path, location)?,
+                    ImportKind::Alias(name) => {
+                        let mut namespace = TableValue::new();
+                        namespace.fields = self
+                            .module_exports(path, location)?
+                            .into_iter()
+                            .map(|(name, value)| (name.to_string(), value))
+                            .collect();
+                        self.env.define(name.clone(), Value::table(namespace));
+                    }
+                    ImportKind::Names(names) => {
+                        let exports = self.module_exports(path, location)?;
+                        for name in names {
+                            let Some(value) = exports.get(&Symbol::intern(name)) else {
+                                return Err(LuxError::runtime_error(
+                                    format!("Module '{}' has no '{}'", path, name),
+                                    Some(location.clone()),
+                                ));
+                            };
+                            self.env.define(name.clone(), value.clone());
+                        }
+                    }
+                }
+                Ok(())
+            }
+
+            // Type aliases only matter to the type checker
+            Stmt::TypeAlias { .. } => Ok(()),
+
+            // Struct declarations only matter to the parser and type checker
+            Stmt::StructDecl { .. } => Ok(()),
+
+            Stmt::EnumDecl { name, members, .. } => {
+                let mut namespace = TableValue::new();
+                for (member, value) in members {
+                    namespace.fields.insert(member.clone(), Value::Int(*value));
+                }
+                self.env.define(name.clone(), Value::table(namespace));
                 Ok(())
             }
 
-            Stmt::VarDecl { name, initializer, location, .. } => {
+            Stmt::VarDecl { name, initializer, .. } => {
                 let value = if let Some(init) = initializer {
                     self.eval_expr(init)?
                 } else {
                     Value::Nil
                 };
-                self.env.define(name.clone(), value);
+                self.env.define(*name, value);
                 Ok(())
             }
 
-            Stmt::FunctionDecl { name, params, body, is_async, .. } => {
+            Stmt::Destructure { names, initializer, location, .. } => {
+                match self.eval_expr(initializer)? {
+                    Value::Tuple(elements) if elements.len() == names.len() => {
+                        for (name, value) in names.iter().zip(elements) {
+                            self.env.define(*name, value);
+                        }
+                        Ok(())
+                    }
+                    other => Err(LuxError::runtime_error(
+                        format!("Cannot destructure {} into {} variables", other.type_name(), names.len()),
+                        Some(location.clone()),
+                    )),
+                }
+            }
+
+            Stmt::FunctionDecl { name, params, body, is_async, location, .. } => {
                 let func = FunctionValue {
-                    name: name.clone(),
-                    params: params.iter().map(|(n, _)| n.clone()).collect(),
+                    name: name.to_string(),
+                    params: params.iter().map(|(n, _)| *n).collect(),
                     body: body.clone(),
                     is_async: *is_async,
+                    location: location.clone(),
                 };
-                self.env.define(name.clone(), Value::Function(func));
+                self.env.define(*name, Value::Function(func));
                 Ok(())
             }
 
@@ -942,7 +2407,7 @@ impl Interpreter {
                 Ok(())
             }
 
-            Stmt::If { condition, then_branch, else_branch, location } => {
+            Stmt::If { condition, then_branch, else_branch, .. } => {
                 let cond_value = self.eval_expr(condition)?;
 
                 if cond_value.is_truthy() {
@@ -963,7 +2428,7 @@ impl Interpreter {
                 Ok(())
             }
 
-            Stmt::While { condition, body, location } => {
+            Stmt::While { condition, body, .. } => {
                 loop {
                     let cond_value = self.eval_expr(condition)?;
                     if !cond_value.is_truthy() {
@@ -990,7 +2455,7 @@ impl Interpreter {
                 Ok(())
             }
 
-            Stmt::For { initializer, condition, increment, body, location } => {
+            Stmt::For { initializer, condition, increment, body, .. } => {
                 self.env.push_scope();
 
                 if let Some(init) = initializer {
@@ -1035,7 +2500,7 @@ impl Interpreter {
                 Ok(())
             }
 
-            Stmt::Return { value, location } => {
+            Stmt::Return { value, .. } => {
                 let return_value = if let Some(v) = value {
                     self.eval_expr(v)?
                 } else {
@@ -1055,18 +2520,31 @@ impl Interpreter {
                 Ok(())
             }
 
-            Stmt::Block { statements, location } => {
-                self.env.push_scope();
-                for stmt in statements {
-                    self.execute_stmt(stmt)?;
-                    if !matches!(self.control_flow, ControlFlow::None) {
-                        self.env.pop_scope();
-                        return Ok(());
+            Stmt::Defer { expr, .. } => {
+                match self.defers.last_mut() {
+                    Some(deferred) => deferred.push(expr.clone()),
+                    // Outside any function or block there is nothing to wait for
+                    None => {
+                        self.eval_expr(expr)?;
                     }
                 }
-                self.env.pop_scope();
                 Ok(())
             }
+
+            Stmt::Block { statements, .. } => {
+                self.env.push_scope();
+                let result = self.with_defers(|this| {
+                    for stmt in statements {
+                        this.execute_stmt(stmt)?;
+                        if !matches!(this.control_flow, ControlFlow::None) {
+                            break;
+                        }
+                    }
+                    Ok(())
+                });
+                self.env.pop_scope();
+                result
+            }
         }
     }
 
@@ -1082,8 +2560,8 @@ impl Interpreter {
                 })
             }
 
-            Expr::Variable { name, location } => {
-                self.env.get(name).ok_or_else(|| {
+            Expr::Variable { name, location, .. } => {
+                self.env.get(*name).ok_or_else(|| {
                     LuxError::runtime_error(
                         format!("Undefined variable '{}'", name),
                         Some(location.clone()),
@@ -1091,24 +2569,24 @@ impl Interpreter {
                 })
             }
 
-            Expr::Binary { left, operator, right, location } => {
+            Expr::Binary { left, operator, right, location, .. } => {
                 let left_val = self.eval_expr(left)?;
                 let right_val = self.eval_expr(right)?;
                 self.eval_binary(left_val, operator, right_val, location)
             }
 
-            Expr::Unary { operator, operand, location } => {
+            Expr::Unary { operator, operand, location, .. } => {
                 let operand_val = self.eval_expr(operand)?;
                 self.eval_unary(operator, operand_val, location)
             }
 
-            Expr::Assign { target, value, location } => {
+            Expr::Assign { target, value, location, .. } => {
                 let val = self.eval_expr(value)?;
 
                 match target.as_ref() {
                     Expr::Variable { name, .. } => {
                         // Simple variable assignment
-                        if self.env.set(name, val.clone()) {
+                        if self.env.set(*name, val.clone()) {
                             Ok(val)
                         } else {
                             Err(LuxError::runtime_error(
@@ -1123,16 +2601,12 @@ impl Interpreter {
                         let key_val = self.eval_expr(key)?;
 
                         match table_val {
-                            Value::Table(mut t) => {
-                                // Use the existing set method
-                                t.set(key_val, val.clone());
-
-                                // Update the table in the environment
-                                // We need to get the table variable name and update it
-                                if let Expr::Variable { name, .. } = table.as_ref() {
-                                    self.env.set(name, Value::Table(t));
-                                }
-
+                            Value::Table(t) => {
+                                // Tables are shared references, so this updates every alias
+                                t.lock()
+                                    .unwrap()
+                                    .set(key_val, val.clone())
+                                    .map_err(|e| LuxError::runtime_error(e, Some(location.clone())))?;
                                 Ok(val)
                             }
                             _ => {
@@ -1143,6 +2617,23 @@ impl Interpreter {
                             }
                         }
                     }
+                    Expr::Unary { operator: UnaryOp::Dereference, operand, .. } => {
+                        // *p = value writes into the pointed-to cell, shared by copies of p
+                        match self.eval_expr(operand)? {
+                            Value::Pointer(ptr) => {
+                                let mut cell = ptr.lock().map_err(|_| LuxError::runtime_error(
+                                    "Failed to lock pointer (poisoned mutex)".to_string(),
+                                    Some(location.clone()),
+                                ))?;
+                                *cell = val.clone();
+                                Ok(val)
+                            }
+                            other => Err(LuxError::runtime_error(
+                                format!("Cannot dereference non-pointer type {}", other.type_name()),
+                                Some(location.clone()),
+                            )),
+                        }
+                    }
                     _ => {
                         Err(LuxError::runtime_error(
                             "Invalid assignment target".to_string(),
@@ -1152,7 +2643,7 @@ impl Interpreter {
                 }
             }
 
-            Expr::Call { callee, arguments, location } => {
+            Expr::Call { callee, arguments, location, .. } => {
                 let func = self.eval_expr(callee)?;
                 let mut args = Vec::new();
                 for arg in arguments {
@@ -1161,40 +2652,83 @@ impl Interpreter {
                 self.call_function(func, args, location)
             }
 
-            Expr::Table { fields, location } => {
-                let mut table = TableValue::new();
-
-                for (key, value_expr) in fields {
-                    let value = self.eval_expr(value_expr)?;
-                    match key {
-                        TableKey::Identifier(name) => {
-                            table.fields.insert(name.clone(), value);
-                        }
-                        TableKey::Expression(key_expr) => {
+            Expr::MethodCall { receiver, method, arguments, location, .. } => {
+                let receiver_val = self.eval_expr(receiver)?;
+                let func = match &receiver_val {
+                    Value::Table(t) => t.lock().unwrap().get(&Value::String(method.clone())).unwrap_or(Value::Nil),
+                    other => {
+                        return Err(LuxError::runtime_error(
+                            format!("Cannot call method '{}' on {}", method, other.type_name()),
+                            Some(location.clone()),
+                        ))
+                    }
+                };
+                let mut args = vec![receiver_val];
+                for arg in arguments {
+                    args.push(self.eval_expr(arg)?);
+                }
+                self.call_function(func, args, location)
+            }
+
+            // Struct constructors are checked statically and build plain tables
+            Expr::Table { fields, location, .. } | Expr::StructInit { fields, location, .. } => {
+                let mut table = TableValue::new();
+
+                for (key, value_expr) in fields {
+                    let value = self.eval_expr(value_expr)?;
+                    match key {
+                        TableKey::Identifier(name) => {
+                            table.fields.insert(name.clone(), value);
+                        }
+                        TableKey::Expression(key_expr) => {
                             let key_val = self.eval_expr(key_expr)?;
-                            table.set(key_val, value);
+                            table
+                                .set(key_val, value)
+                                .map_err(|e| LuxError::runtime_error(e, Some(location.clone())))?;
                         }
                     }
                 }
 
-                Ok(Value::Table(table))
+                Ok(Value::table(table))
             }
 
-            Expr::TableAccess { table, key, location } => {
+            Expr::Tuple { elements, .. } => Ok(Value::Tuple(
+                elements.iter().map(|e| self.eval_expr(e)).collect::<LuxResult<_>>()?,
+            )),
+
+            Expr::TableAccess { table, key, location, .. } => {
                 let table_val = self.eval_expr(table)?;
                 let key_val = self.eval_expr(key)?;
 
-                if let Value::Table(t) = table_val {
-                    Ok(t.get(&key_val).unwrap_or(Value::Nil))
-                } else {
-                    Err(LuxError::runtime_error(
+                match table_val {
+                    Value::Table(t) => Ok(t.lock().unwrap().get(&key_val).unwrap_or(Value::Nil)),
+                    // Tuples are indexed from 1, or from the end with negative indices
+                    Value::Tuple(elements) => {
+                        let Value::Int(index) = key_val else {
+                            return Err(LuxError::runtime_error(
+                                format!("Tuple index must be an int, got {}", key_val.type_name()),
+                                Some(location.clone()),
+                            ));
+                        };
+                        let position = if index < 0 { elements.len() as i64 + index } else { index - 1 };
+                        usize::try_from(position)
+                            .ok()
+                            .and_then(|i| elements.get(i).cloned())
+                            .ok_or_else(|| {
+                                LuxError::runtime_error(
+                                    format!("Tuple index {} out of range for a tuple of {}", index, elements.len()),
+                                    Some(location.clone()),
+                                )
+                            })
+                    }
+                    _ => Err(LuxError::runtime_error(
                         "Can only index tables",
                         Some(location.clone()),
-                    ))
+                    )),
                 }
             }
 
-            Expr::Logical { left, operator, right, location } => {
+            Expr::Logical { left, operator, right, .. } => {
                 let left_val = self.eval_expr(left)?;
 
                 match operator {
@@ -1215,18 +2749,26 @@ impl Interpreter {
                 }
             }
 
-            Expr::Function { params, body, .. } => {
+            Expr::Function { params, body, location, .. } => {
                 // Create an anonymous function value
                 let func = FunctionValue {
                     name: "<anonymous>".to_string(),
-                    params: params.iter().map(|(n, _)| n.clone()).collect(),
+                    params: params.iter().map(|(n, _)| *n).collect(),
                     body: body.clone(),
                     is_async: false,
+                    location: location.clone(),
                 };
                 Ok(Value::Function(func))
             }
 
-            Expr::Spawn { call, location } => {
+            Expr::DoBlock { body, .. } => {
+                self.env.push_scope();
+                let result = self.with_defers(|this| this.eval_do_block(body));
+                self.env.pop_scope();
+                result
+            }
+
+            Expr::Spawn { call, location, .. } => {
                 // Spawn expects a function call expression
                 match call.as_ref() {
                     Expr::Call { callee, arguments, .. } => {
@@ -1260,7 +2802,7 @@ impl Interpreter {
                 }
             }
 
-            Expr::Await { task, location } => {
+            Expr::Await { task, location, .. } => {
                 // Await expects a task ID (integer) or a table of task IDs
                 let task_value = self.eval_expr(task)?;
 
@@ -1271,7 +2813,7 @@ impl Interpreter {
                             match task.state {
                                 TaskState::Completed(value) => Ok(value),
                                 TaskState::Failed(msg) => Err(LuxError::runtime_error(
-                                    &format!("Task {} failed: {}", task_id, msg),
+                                    format!("Task {} failed: {}", task_id, msg),
                                     Some(location.clone()),
                                 )),
                                 TaskState::Pending => {
@@ -1281,19 +2823,19 @@ impl Interpreter {
                                         Ok(result)
                                     } else {
                                         Err(LuxError::runtime_error(
-                                            &format!("Task {} has no function to execute", task_id),
+                                            format!("Task {} has no function to execute", task_id),
                                             Some(location.clone()),
                                         ))
                                     }
                                 }
                                 _ => Err(LuxError::runtime_error(
-                                    &format!("Task {} is in invalid state", task_id),
+                                    format!("Task {} is in invalid state", task_id),
                                     Some(location.clone()),
                                 )),
                             }
                         } else {
                             Err(LuxError::runtime_error(
-                                &format!("Task {} not found", task_id),
+                                format!("Task {} not found", task_id),
                                 Some(location.clone()),
                             ))
                         }
@@ -1302,6 +2844,8 @@ impl Interpreter {
                         // Multiple tasks await - execute all tasks in parallel using threads
                         use std::thread;
 
+                        let table = table.lock().unwrap().clone();
+
                         let mut handles = Vec::new();
                         let mut task_ids_array = Vec::new();
                         let mut task_ids_fields = HashMap::new();
@@ -1317,25 +2861,18 @@ impl Interpreter {
                                         if matches!(task.state, TaskState::Pending) {
                                             if let Some(func) = task.function {
                                                 let args = task.arguments.clone();
-                                                let env = self.env.clone();
-                                                let executor = self.executor.clone();
-
-                                                let handle = thread::spawn(move || {
-                                                    let mut task_interp = Interpreter {
-                                                        env,
-                                                        control_flow: ControlFlow::None,
-                                                        executor: executor.clone(),
-                                                        loaded_modules: HashMap::new(),
-                                                        current_file_dir: None,
-                                                    };
-                                                    task_interp.execute_task(tid, func, args)
-                                                });
+                                                let mut task_interp = self.task_interpreter();
+
+                                                let handle = thread::Builder::new()
+                                                    .stack_size(STACK_SIZE)
+                                                    .spawn(move || task_interp.execute_task(tid, func, args))
+                                                    .expect("failed to spawn task thread");
                                                 handles.push((tid, handle));
                                             }
                                         }
                                     } else {
                                         return Err(LuxError::runtime_error(
-                                            &format!("Task {} not found", task_id),
+                                            format!("Task {} not found", task_id),
                                             Some(location.clone()),
                                         ));
                                     }
@@ -1360,25 +2897,18 @@ impl Interpreter {
                                         if matches!(task.state, TaskState::Pending) {
                                             if let Some(func) = task.function {
                                                 let args = task.arguments.clone();
-                                                let env = self.env.clone();
-                                                let executor = self.executor.clone();
-
-                                                let handle = thread::spawn(move || {
-                                                    let mut task_interp = Interpreter {
-                                                        env,
-                                                        control_flow: ControlFlow::None,
-                                                        executor: executor.clone(),
-                                                        loaded_modules: HashMap::new(),
-                                                        current_file_dir: None,
-                                                    };
-                                                    task_interp.execute_task(tid, func, args)
-                                                });
+                                                let mut task_interp = self.task_interpreter();
+
+                                                let handle = thread::Builder::new()
+                                                    .stack_size(STACK_SIZE)
+                                                    .spawn(move || task_interp.execute_task(tid, func, args))
+                                                    .expect("failed to spawn task thread");
                                                 handles.push((tid, handle));
                                             }
                                         }
                                     } else {
                                         return Err(LuxError::runtime_error(
-                                            &format!("Task {} not found", task_id),
+                                            format!("Task {} not found", task_id),
                                             Some(location.clone()),
                                         ));
                                     }
@@ -1396,7 +2926,7 @@ impl Interpreter {
                         for (_tid, handle) in handles {
                             if let Err(e) = handle.join() {
                                 return Err(LuxError::runtime_error(
-                                    &format!("Task thread panicked: {:?}", e),
+                                    format!("Task thread panicked: {:?}", e),
                                     Some(location.clone()),
                                 ));
                             }
@@ -1413,13 +2943,13 @@ impl Interpreter {
                                     }
                                     TaskState::Failed(msg) => {
                                         return Err(LuxError::runtime_error(
-                                            &format!("Task {} failed: {}", tid, msg),
+                                            format!("Task {} failed: {}", tid, msg),
                                             Some(location.clone()),
                                         ));
                                     }
                                     _ => {
                                         return Err(LuxError::runtime_error(
-                                            &format!("Task {} did not complete", tid),
+                                            format!("Task {} did not complete", tid),
                                             Some(location.clone()),
                                         ));
                                     }
@@ -1435,13 +2965,13 @@ impl Interpreter {
                                     }
                                     TaskState::Failed(msg) => {
                                         return Err(LuxError::runtime_error(
-                                            &format!("Task {} failed: {}", tid, msg),
+                                            format!("Task {} failed: {}", tid, msg),
                                             Some(location.clone()),
                                         ));
                                     }
                                     _ => {
                                         return Err(LuxError::runtime_error(
-                                            &format!("Task {} did not complete", tid),
+                                            format!("Task {} did not complete", tid),
                                             Some(location.clone()),
                                         ));
                                     }
@@ -1450,7 +2980,7 @@ impl Interpreter {
                         }
 
                         // Return table of results
-                        Ok(Value::Table(result_table))
+                        Ok(Value::table(result_table))
                     }
                     _ => Err(LuxError::runtime_error(
                         "await expects a task ID (integer) or table of task IDs",
@@ -1461,20 +2991,36 @@ impl Interpreter {
         }
     }
 
-    fn eval_binary(&self, left: Value, op: &BinaryOp, right: Value, location: &SourceLocation) -> LuxResult<Value> {
+    pub(crate) fn eval_binary(&self, left: Value, op: &BinaryOp, right: Value, location: &SourceLocation) -> LuxResult<Value> {
         match (left, right) {
             (Value::Int(a), Value::Int(b)) => {
                 Ok(match op {
-                    BinaryOp::Add => Value::Int(a + b),
-                    BinaryOp::Subtract => Value::Int(a - b),
-                    BinaryOp::Multiply => Value::Int(a * b),
+                    BinaryOp::Add => self.int_result(a.overflowing_add(b), location)?,
+                    BinaryOp::Subtract => self.int_result(a.overflowing_sub(b), location)?,
+                    BinaryOp::Multiply => self.int_result(a.overflowing_mul(b), location)?,
                     BinaryOp::Divide => {
                         if b == 0 {
                             return Err(LuxError::runtime_error("Division by zero", Some(location.clone())));
                         }
-                        Value::Int(a / b)
+                        Value::Float(a as f64 / b as f64)
+                    }
+                    BinaryOp::FloorDivide => {
+                        if b == 0 {
+                            return Err(LuxError::runtime_error("Division by zero", Some(location.clone())));
+                        }
+                        // Round toward negative infinity rather than zero
+                        let (quotient, overflowed) = a.overflowing_div(b);
+                        let round_down = a.wrapping_rem(b) != 0 && (a < 0) != (b < 0);
+                        self.int_result((quotient - round_down as i64, overflowed), location)?
                     }
-                    BinaryOp::Modulo => Value::Int(a % b),
+                    BinaryOp::Modulo => {
+                        if b == 0 {
+                            return Err(LuxError::runtime_error("Modulo by zero", Some(location.clone())));
+                        }
+                        // i64::MIN % -1 is 0, even though computing it overflows
+                        Value::Int(a.wrapping_rem(b))
+                    }
+                    BinaryOp::Power => Value::Float((a as f64).powf(b as f64)),
                     BinaryOp::Equal => Value::Bool(a == b),
                     BinaryOp::NotEqual => Value::Bool(a != b),
                     BinaryOp::Less => Value::Bool(a < b),
@@ -1484,12 +3030,16 @@ impl Interpreter {
                 })
             }
             (Value::Float(a), Value::Float(b)) => {
+                // Float division and modulo by zero follow IEEE 754 and
+                // produce inf or NaN rather than raising an error
                 Ok(match op {
                     BinaryOp::Add => Value::Float(a + b),
                     BinaryOp::Subtract => Value::Float(a - b),
                     BinaryOp::Multiply => Value::Float(a * b),
                     BinaryOp::Divide => Value::Float(a / b),
+                    BinaryOp::FloorDivide => Value::Float((a / b).floor()),
                     BinaryOp::Modulo => Value::Float(a % b),
+                    BinaryOp::Power => Value::Float(a.powf(b)),
                     BinaryOp::Equal => Value::Bool(a == b),
                     BinaryOp::NotEqual => Value::Bool(a != b),
                     BinaryOp::Less => Value::Bool(a < b),
@@ -1498,6 +3048,13 @@ impl Interpreter {
                     BinaryOp::GreaterEqual => Value::Bool(a >= b),
                 })
             }
+            // Mixed int/float operands promote the int to float
+            (Value::Int(a), Value::Float(b)) => {
+                self.eval_binary(Value::Float(a as f64), op, Value::Float(b), location)
+            }
+            (Value::Float(a), Value::Int(b)) => {
+                self.eval_binary(Value::Float(a), op, Value::Float(b as f64), location)
+            }
             (Value::String(a), Value::String(b)) => {
                 Ok(match op {
                     BinaryOp::Add => Value::String(format!("{}{}", a, b)),
@@ -1511,9 +3068,9 @@ impl Interpreter {
             }
             (a, b) => {
                 if matches!(op, BinaryOp::Equal) {
-                    Ok(Value::Bool(a == b))
+                    Ok(Value::Bool(a.equals(&b)))
                 } else if matches!(op, BinaryOp::NotEqual) {
-                    Ok(Value::Bool(a != b))
+                    Ok(Value::Bool(!a.equals(&b)))
                 } else {
                     Err(LuxError::runtime_error(
                         format!("Type mismatch: cannot apply {:?} to {} and {}", op, a.type_name(), b.type_name()),
@@ -1524,11 +3081,25 @@ impl Interpreter {
         }
     }
 
-    fn eval_unary(&self, op: &UnaryOp, operand: Value, location: &SourceLocation) -> LuxResult<Value> {
+    /// The result of int arithmetic from an `overflowing_*` operation,
+    /// applying the interpreter's [`IntOverflow`] policy
+    fn int_result(&self, result: (i64, bool), location: &SourceLocation) -> LuxResult<Value> {
+        self.int_value(result).map_err(|e| LuxError::runtime_error(e, Some(location.clone())))
+    }
+
+    /// Like [`Interpreter::int_result`], for builtins that report errors as strings
+    fn int_value(&self, (result, overflowed): (i64, bool)) -> Result<Value, String> {
+        if overflowed && self.int_overflow == IntOverflow::Checked {
+            return Err("integer overflow".to_string());
+        }
+        Ok(Value::Int(result))
+    }
+
+    pub(crate) fn eval_unary(&self, op: &UnaryOp, operand: Value, location: &SourceLocation) -> LuxResult<Value> {
         match op {
             UnaryOp::Negate => {
                 match operand {
-                    Value::Int(n) => Ok(Value::Int(-n)),
+                    Value::Int(n) => self.int_result(n.overflowing_neg(), location),
                     Value::Float(f) => Ok(Value::Float(-f)),
                     _ => Err(LuxError::runtime_error(
                         format!("Cannot negate {}", operand.type_name()),
@@ -1539,8 +3110,9 @@ impl Interpreter {
             UnaryOp::Not => Ok(Value::Bool(!operand.is_truthy())),
             UnaryOp::Length => {
                 match operand {
-                    Value::Table(t) => Ok(Value::Int(t.len() as i64)),
-                    Value::String(s) => Ok(Value::Int(s.len() as i64)),
+                    Value::Table(t) => Ok(Value::Int(t.lock().unwrap().len() as i64)),
+                    Value::String(s) => Ok(Value::Int(s.chars().count() as i64)),
+                    Value::Tuple(elements) => Ok(Value::Int(elements.len() as i64)),
                     _ => Err(LuxError::runtime_error(
                         format!("Cannot get length of {}", operand.type_name()),
                         Some(location.clone()),
@@ -1548,7 +3120,7 @@ impl Interpreter {
                 }
             }
             UnaryOp::AddressOf => {
-                // Create a pointer to the value
+                // Box a copy of the value; `&x` doesn't alias `x`
                 use std::sync::{Arc, Mutex};
                 Ok(Value::Pointer(Arc::new(Mutex::new(operand))))
             }
@@ -1571,18 +3143,122 @@ impl Interpreter {
         }
     }
 
-    fn call_function(&mut self, func: Value, args: Vec<Value>, location: &SourceLocation) -> LuxResult<Value> {
+    /// Push the scopes a user function runs in: the top-level bindings of the
+    /// module it came from, if that module was run in a scope of its own,
+    /// then a new scope holding its parameters
+    fn push_function_scope(&mut self, func: &FunctionValue, args: &[Value]) {
+        let module_scope = func
+            .location
+            .filename
+            .as_ref()
+            .and_then(|file| self.module_scopes.get(file))
+            .cloned()
+            .unwrap_or_default();
+        self.env.push_scope_with(module_scope);
+
+        self.env.push_scope();
+        for (param, arg) in func.params.iter().zip(args) {
+            self.env.define(*param, arg.clone());
+        }
+    }
+
+    fn pop_function_scope(&mut self) {
+        self.env.pop_scope();
+        self.env.pop_scope();
+    }
+
+    /// Count one more nested call, failing once the depth limit is exceeded
+    fn enter_call(&mut self, location: &SourceLocation) -> LuxResult<()> {
+        if self.depth >= self.max_depth {
+            return Err(LuxError::runtime_error(
+                format!("stack overflow (more than {} nested calls)", self.max_depth),
+                Some(location.clone()),
+            ));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Run `body` with its own list of deferred expressions, then evaluate
+    /// them in reverse order. The deferred expressions run even if `body`
+    /// fails; an error from `body` takes precedence over one they raise.
+    fn with_defers<T>(&mut self, body: impl FnOnce(&mut Self) -> LuxResult<T>) -> LuxResult<T> {
+        self.defers.push(Vec::new());
+        let result = body(self);
+        let deferred = self.defers.pop().unwrap_or_default();
+        if deferred.is_empty() {
+            return result;
+        }
+
+        // Keep a pending return, break or continue while deferred code runs
+        let pending = std::mem::replace(&mut self.control_flow, ControlFlow::None);
+        let mut deferred_result = Ok(());
+        for expr in deferred.iter().rev() {
+            if let Err(e) = self.eval_expr(expr) {
+                deferred_result = deferred_result.and(Err(e));
+            }
+        }
+        self.control_flow = pending;
+
+        let value = result?;
+        deferred_result?;
+        Ok(value)
+    }
+
+    /// Run the statements of a do block, returning the value of the final
+    /// expression statement (nil if there is none)
+    fn eval_do_block(&mut self, body: &[Stmt]) -> LuxResult<Value> {
+        let Some((last, rest)) = body.split_last() else {
+            return Ok(Value::Nil);
+        };
+        for stmt in rest {
+            self.execute_stmt(stmt)?;
+            // A return, break or continue inside the block leaves it early
+            if !matches!(self.control_flow, ControlFlow::None) {
+                return Ok(Value::Nil);
+            }
+        }
+        match last {
+            Stmt::Expression { expr, .. } => self.eval_expr(expr),
+            other => {
+                self.execute_stmt(other)?;
+                Ok(Value::Nil)
+            }
+        }
+    }
+
+    /// Run a function body, returning the value of its `return` (nil if none)
+    fn execute_function_body(&mut self, body: &[Stmt]) -> LuxResult<Value> {
+        for stmt in body {
+            self.execute_stmt(stmt)?;
+
+            if let ControlFlow::Return(value) = &self.control_flow {
+                return Ok(value.clone());
+            }
+        }
+        Ok(Value::Nil)
+    }
+
+    pub(crate) fn call_function(&mut self, func: Value, args: Vec<Value>, location: &SourceLocation) -> LuxResult<Value> {
         match func {
             Value::NativeFunction(native) => {
-                if args.len() != native.arity {
+                if native.variadic && args.len() < native.arity {
+                    return Err(LuxError::runtime_error(
+                        format!("Expected at least {} arguments but got {}", native.arity, args.len()),
+                        Some(location.clone()),
+                    ));
+                }
+                if !native.variadic && args.len() != native.arity {
                     return Err(LuxError::runtime_error(
                         format!("Expected {} arguments but got {}", native.arity, args.len()),
                         Some(location.clone()),
                     ));
                 }
-                (native.func)(&args).map_err(|e| {
-                    LuxError::runtime_error(e, Some(location.clone()))
-                })
+                let result = match native.func {
+                    NativeFn::Pure(func) => func(&args),
+                    NativeFn::Interpreter(func) => func(self, &args),
+                };
+                result.map_err(|e| LuxError::runtime_error(e, Some(location.clone())))
             }
             Value::Function(user_func) => {
                 if args.len() != user_func.params.len() {
@@ -1592,29 +3268,26 @@ impl Interpreter {
                     ));
                 }
 
-                // Create new scope for function
-                self.env.push_scope();
+                self.enter_call(location)?;
 
-                // Bind parameters
-                for (param, arg) in user_func.params.iter().zip(args.iter()) {
-                    self.env.define(param.clone(), arg.clone());
-                }
+                self.push_function_scope(&user_func, &args);
+                self.call_stack.push(StackFrame {
+                    function: user_func.name.clone(),
+                    definition: user_func.location.clone(),
+                    call_site: location.clone(),
+                });
 
-                // Execute function body
-                for stmt in &user_func.body {
-                    self.execute_stmt(stmt)?;
+                let result = self.with_defers(|this| this.execute_function_body(&user_func.body));
 
-                    if let ControlFlow::Return(value) = &self.control_flow {
-                        let return_value = value.clone();
-                        self.control_flow = ControlFlow::None;
-                        self.env.pop_scope();
-                        return Ok(return_value);
-                    }
-                }
-
-                self.env.pop_scope();
+                // The innermost call that sees an error records the full stack
+                let result = result.map_err(|e| {
+                    e.with_backtrace(|| self.call_stack.iter().rev().cloned().collect())
+                });
+                self.call_stack.pop();
+                self.pop_function_scope();
                 self.control_flow = ControlFlow::None;
-                Ok(Value::Nil)
+                self.depth -= 1;
+                result
             }
             _ => Err(LuxError::runtime_error(
                 format!("Cannot call {}", func.type_name()),
@@ -1623,3 +3296,1017 @@ impl Interpreter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::testing::{run_captured, SharedBuffer};
+
+    fn eval(source: &str) -> LuxResult<Value> {
+        crate::eval(source, None)
+    }
+
+    #[test]
+    fn test_string_format() {
+        assert_eq!(eval(r#"string_format("%s = %d", "x", 5)"#).unwrap(), Value::String("x = 5".to_string()));
+        assert_eq!(eval(r#"string_format("%.2f", 3.14159)"#).unwrap(), Value::String("3.14".to_string()));
+        assert_eq!(eval(r#"string_format("100%%")"#).unwrap(), Value::String("100%".to_string()));
+    }
+
+    #[test]
+    fn test_format_float_modes() {
+        let format = |args: &str| eval(&format!("format_float({})", args)).unwrap();
+        let string = |s: &str| Value::String(s.to_string());
+        assert_eq!(format(r#"3.14159, "fixed", 2"#), string("3.14"));
+        assert_eq!(format(r#"3.14159, "sci", 2"#), string("3.14e0"));
+        assert_eq!(format(r#"3.14159, "general", 2"#), string("3.1"));
+
+        assert_eq!(format(r#"1234.5, "fixed", 2"#), string("1234.50"));
+        assert_eq!(format(r#"1234.5, "sci", 2"#), string("1.23e3"));
+        assert_eq!(format(r#"1234.5, "general", 2"#), string("1.2e3"));
+        assert_eq!(format(r#"0.5, "general", 2"#), string("0.5"));
+        assert_eq!(format(r#"0.00001234, "general", 2"#), string("1.2e-5"));
+        assert_eq!(format(r#"42, "fixed", 2"#), string("42.00"));
+    }
+
+    #[test]
+    fn test_format_float_errors() {
+        let err = eval(r#"format_float(1.5, "percent", 2)"#).unwrap_err();
+        assert!(err.message().starts_with("format_float: unknown mode \"percent\""), "{}", err.message());
+        let err = eval(r#"format_float(1.5, "fixed", -1)"#).unwrap_err();
+        assert_eq!(err.message(), "format_float: precision must not be negative, got -1");
+    }
+
+    #[test]
+    fn test_string_format_type_mismatch() {
+        let err = eval(r#"string_format("%d", "five")"#).unwrap_err();
+        assert_eq!(err.message(), "string_format: '%d' expects an integer, got string");
+        let err = eval(r#"string_format("%d", 2.5)"#).unwrap_err();
+        assert_eq!(err.message(), "string_format: '%d' expects an integer, got float");
+        let err = eval(r#"string_format("%f", true)"#).unwrap_err();
+        assert_eq!(err.message(), "string_format: '%f' expects a number, got bool");
+        assert!(eval(r#"string_format("%s %s", "one")"#).is_err());
+    }
+
+    #[test]
+    fn test_string_find() {
+        assert_eq!(eval(r#"string_find("hello world", "world")"#).unwrap(), Value::Int(7));
+        assert_eq!(eval(r#"string_find("hello", "xyz")"#).unwrap(), Value::Int(0));
+        assert_eq!(eval(r#"string_find("abcabc", "bc", 3)"#).unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn test_string_find_counts_chars() {
+        // 'é' is two bytes, so byte and char offsets differ after it
+        assert_eq!(eval(r#"string_find("café au lait", "au")"#).unwrap(), Value::Int(6));
+    }
+
+    #[test]
+    fn test_char_at() {
+        assert_eq!(eval(r#"char_at("hello", 2)"#).unwrap(), Value::String("e".to_string()));
+        assert_eq!(eval(r#"char_at("héllo", 3)"#).unwrap(), Value::String("l".to_string()));
+        assert_eq!(eval(r#"char_at("hello", 6)"#).unwrap(), Value::String(String::new()));
+        assert_eq!(eval(r#"char_at("hello", 0)"#).unwrap(), Value::String(String::new()));
+    }
+
+    #[test]
+    fn test_string_chars() {
+        assert_eq!(eval(r#"#string_chars("abc")"#).unwrap(), Value::Int(3));
+        assert_eq!(eval(r#"string_chars("naïve")[3]"#).unwrap(), Value::String("ï".to_string()));
+        assert_eq!(eval(r#"#string_chars("naïve")"#).unwrap(), Value::Int(5));
+    }
+
+    #[test]
+    fn test_ord_and_chr() {
+        assert_eq!(eval(r#"ord("A")"#).unwrap(), Value::Int(65));
+        assert_eq!(eval(r#"ord("é!")"#).unwrap(), Value::Int(233));
+        assert_eq!(eval("chr(65)").unwrap(), Value::String("A".to_string()));
+        assert_eq!(eval(r#"chr(ord("λ")) == "λ""#).unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_ord_and_chr_errors() {
+        assert_eq!(eval(r#"ord("")"#).unwrap_err().message(), "ord expects a non-empty string");
+        // Negative, surrogate and past-the-end codepoints
+        for code in ["-1", "55296", "1114112"] {
+            let err = eval(&format!("chr({})", code)).unwrap_err();
+            assert_eq!(err.message(), format!("chr: {} is not a valid Unicode codepoint", code));
+        }
+    }
+
+    #[test]
+    fn test_string_length_counts_chars() {
+        assert_eq!(eval(r#"string_length("café")"#).unwrap(), Value::Int(4));
+        assert_eq!(eval(r#"#"café""#).unwrap(), Value::Int(4));
+    }
+
+    #[test]
+    fn test_string_repeat() {
+        assert_eq!(eval(r#"string_repeat("ab", 3)"#).unwrap(), Value::String("ababab".to_string()));
+        assert_eq!(eval(r#"string_repeat("-", 0)"#).unwrap(), Value::String(String::new()));
+        let err = eval(r#"string_repeat("-", -1)"#).unwrap_err();
+        assert!(err.message().contains("count must not be negative"), "{}", err.message());
+    }
+
+    #[test]
+    fn test_string_repeat_rejects_huge_results() {
+        let err = eval(r#"string_repeat("ab", 9223372036854775807)"#).unwrap_err();
+        assert!(err.message().contains("string_repeat: result too large"), "{}", err.message());
+        let err = eval(r#"string_repeat("-", 2000000000)"#).unwrap_err();
+        assert!(err.message().contains("string_repeat: result too large"), "{}", err.message());
+    }
+
+    #[test]
+    fn test_pad_left_and_right() {
+        assert_eq!(eval(r#"pad_left("7", 3, "0")"#).unwrap(), Value::String("007".to_string()));
+        assert_eq!(eval(r#"pad_right("café", 6, ".")"#).unwrap(), Value::String("café..".to_string()));
+        assert_eq!(eval(r#"pad_left("x", 3, "→")"#).unwrap(), Value::String("→→x".to_string()));
+        // Already wide enough: returned as is, never truncated
+        assert_eq!(eval(r#"pad_left("hello", 3, " ")"#).unwrap(), Value::String("hello".to_string()));
+        assert_eq!(eval(r#"pad_right("hello", 5, " ")"#).unwrap(), Value::String("hello".to_string()));
+        let err = eval(r#"pad_right("a", 4, "ab")"#).unwrap_err();
+        assert!(err.message().contains("fill must be a single character"), "{}", err.message());
+    }
+
+    #[test]
+    fn test_padding_rejects_huge_widths() {
+        let err = eval(r#"pad_left("a", 9223372036854775807, " ")"#).unwrap_err();
+        assert!(err.message().contains("pad_left: result too large"), "{}", err.message());
+        let err = eval(r#"pad_right("a", 2000000000, "→")"#).unwrap_err();
+        assert!(err.message().contains("pad_right: result too large"), "{}", err.message());
+    }
+
+    #[test]
+    fn test_regex_builtins() {
+        assert_eq!(eval(r#"regex_match("order-1234", "^order-[0-9]+$")"#).unwrap(), Value::Bool(true));
+        assert_eq!(eval(r#"regex_match("order-abc", "^order-[0-9]+$")"#).unwrap(), Value::Bool(false));
+        assert_eq!(eval(r#"regex_find("v2.10.3 released", "[0-9]+\\.[0-9]+")"#).unwrap(), Value::String("2.10".to_string()));
+        assert_eq!(eval(r#"regex_find("no digits", "[0-9]+")"#).unwrap(), Value::Nil);
+        assert_eq!(
+            eval(r#"regex_replace("2024-01-31", "([0-9]+)-([0-9]+)-([0-9]+)", "$3/$2/$1")"#).unwrap(),
+            Value::String("31/01/2024".to_string())
+        );
+        assert_eq!(eval(r#"regex_replace("a  b   c", " +", " ")"#).unwrap(), Value::String("a b c".to_string()));
+
+        let err = eval(r#"regex_match("text", "[a-")"#).unwrap_err();
+        assert!(err.message().starts_with("regex_match: invalid pattern:"), "{}", err.message());
+    }
+
+    #[test]
+    fn test_split_lines_handles_crlf() {
+        let source = r#"local lines := split_lines("one\r\ntwo\nthree\r\n\nfive")
+print(#lines)
+print(table_concat(lines, "|"))
+print(#split_lines(""))"#;
+        assert_eq!(run_captured(source), "5\none|two|three||five\n0\n");
+    }
+
+    #[test]
+    fn test_join_lines_round_trip() {
+        let source = r#"local text := "alpha\nbeta\n\ngamma"
+print(join_lines(split_lines(text)) == text)
+print(join_lines({"a", 1, 2.5}))
+print(join_lines({}) == "")"#;
+        assert_eq!(run_captured(source), "true\na\n1\n2.5\ntrue\n");
+    }
+
+    #[test]
+    fn test_one_sided_trims() {
+        assert_eq!(eval(r#"string_trim_start("  \tkey = 1  ")"#).unwrap(), Value::String("key = 1  ".to_string()));
+        assert_eq!(eval(r#"string_trim_end("  key = 1 \n")"#).unwrap(), Value::String("  key = 1".to_string()));
+        assert_eq!(eval(r#"string_trim_end("")"#).unwrap(), Value::String(String::new()));
+    }
+
+    #[test]
+    fn test_string_trim_chars() {
+        assert_eq!(eval(r#"string_trim_chars("x = 1; ;  ", "; ")"#).unwrap(), Value::String("x = 1".to_string()));
+        assert_eq!(eval(r#"string_trim_chars("--«title»--", "-«»")"#).unwrap(), Value::String("title".to_string()));
+        // Characters in the middle are kept; no characters trims nothing
+        assert_eq!(eval(r#"string_trim_chars(";a;b;", ";")"#).unwrap(), Value::String("a;b".to_string()));
+        assert_eq!(eval(r#"string_trim_chars(" a ", "")"#).unwrap(), Value::String(" a ".to_string()));
+    }
+
+    #[test]
+    fn test_mixed_numeric_arithmetic() {
+        assert_eq!(eval("1 + 2.5").unwrap(), Value::Float(3.5));
+        assert_eq!(eval("5 / 2.0").unwrap(), Value::Float(2.5));
+        assert_eq!(eval("7.5 % 2").unwrap(), Value::Float(1.5));
+        assert_eq!(eval("3 < 3.5").unwrap(), Value::Bool(true));
+        assert_eq!(eval("2.0 >= 3").unwrap(), Value::Bool(false));
+    }
+
+    #[test]
+    fn test_division_and_floor_division() {
+        assert_eq!(eval("7 / 2 == 3.5").unwrap(), Value::Bool(true));
+        assert_eq!(eval("6 / 2").unwrap(), Value::Float(3.0));
+        assert_eq!(eval("7 ~/ 2 == 3").unwrap(), Value::Bool(true));
+        assert_eq!(eval("-7 ~/ 2").unwrap(), Value::Int(-4));
+        assert_eq!(eval("7 ~/ -2").unwrap(), Value::Int(-4));
+        assert_eq!(eval("7.5 ~/ 2").unwrap(), Value::Float(3.0));
+        assert!(eval("7 ~/ 0").unwrap_err().to_string().contains("Division by zero"));
+    }
+
+    #[test]
+    fn test_power() {
+        assert_eq!(eval("-2 ^ 2").unwrap(), Value::Float(-4.0));
+        assert_eq!(eval("2 ^ -1").unwrap(), Value::Float(0.5));
+        assert_eq!(eval("2 ^ 3 ^ 2").unwrap(), Value::Float(512.0));
+        assert_eq!(eval("local x := 3\n2 * x ^ 2").unwrap(), Value::Float(18.0));
+        assert_eq!(eval("4.0 ^ 0.5").unwrap(), Value::Float(2.0));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let err = eval("5 % 0").unwrap_err();
+        assert!(err.to_string().contains("Modulo by zero"));
+        let err = eval("5 / 0").unwrap_err();
+        assert!(err.to_string().contains("Division by zero"));
+
+        assert_eq!(eval("1.0 / 0.0").unwrap(), Value::Float(f64::INFINITY));
+        assert_eq!(eval("-1 / 0.0").unwrap(), Value::Float(f64::NEG_INFINITY));
+        match eval("5.0 % 0.0").unwrap() {
+            Value::Float(f) => assert!(f.is_nan()),
+            other => panic!("expected float, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_table_equality() {
+        assert_eq!(eval("local t := {1, 2}\nt == {1, 2}").unwrap(), Value::Bool(true));
+        assert_eq!(eval("local t := {1, 2}\nt == {1, 3}").unwrap(), Value::Bool(false));
+        assert_eq!(eval("local t := {a = {1}}\nt == {a = {1}}").unwrap(), Value::Bool(true));
+        assert_eq!(eval("local t := {a = {1}}\nt != {a = {2}}").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_table_iteration() {
+        let setup = "local t := {10, 20, 30, name = \"lux\", kind = \"lang\"}\n";
+        let run = |body: &str| eval(&format!("{}{}", setup, body)).unwrap();
+
+        assert_eq!(run("#pairs(t)"), Value::Int(5));
+        assert_eq!(run("#ipairs(t)"), Value::Int(3));
+        assert_eq!(run("ipairs(t)[2].value"), Value::Int(20));
+        // Fields follow the array part, sorted by name
+        assert_eq!(run("pairs(t)[4].key"), Value::String("kind".to_string()));
+        assert_eq!(run("pairs(t)[5].value"), Value::String("lux".to_string()));
+
+        let sum = "local total := 0\n\
+                   local entry := next(t, nil)\n\
+                   while entry != nil {\n\
+                       if type_of(entry.value) == \"int\" { total = total + entry.value }\n\
+                       entry = next(t, entry.key)\n\
+                   }\n\
+                   total";
+        assert_eq!(run(sum), Value::Int(60));
+    }
+
+    #[test]
+    fn test_ipairs_stops_at_nil() {
+        assert_eq!(eval("local t := {1, nil, 3}\n#ipairs(t)").unwrap(), Value::Int(1));
+        assert_eq!(eval("local t := {1, nil, 3}\n#pairs(t)").unwrap(), Value::Int(2));
+    }
+
+    #[test]
+    fn test_keys_and_values() {
+        let source = "local t := {\"a\", \"b\", x = 1, y = 2}\n\
+                      local ks := keys(t)\n\
+                      local vs := values(t)\n\
+                      local rebuilt: table = {}\n\
+                      for local i := 1; i <= #ks; i = i + 1 {\n\
+                          rebuilt[ks[i]] = vs[i]\n\
+                      }\n\
+                      rebuilt == t";
+        assert_eq!(eval(source).unwrap(), Value::Bool(true));
+        assert_eq!(eval("#keys({x = 1, y = 2})").unwrap(), Value::Int(2));
+        assert_eq!(eval("keys({\"a\", x = 1})[2]").unwrap(), Value::String("x".to_string()));
+        assert_eq!(eval("values({\"a\", x = 1})[2]").unwrap(), Value::Int(1));
+    }
+
+    #[test]
+    fn test_tables_are_shared_references() {
+        let source = "local a := {1, 2}\n\
+                      local b := a\n\
+                      b[1] = 10\n\
+                      a[1]";
+        assert_eq!(eval(source).unwrap(), Value::Int(10));
+    }
+
+    #[test]
+    fn test_deep_copy() {
+        let source = "local original := {1, inner = {x = 1}}\n\
+                      local copy := deep_copy(original)\n\
+                      copy[1] = 99\n\
+                      copy.inner.x = 2\n\
+                      original[1] + original.inner.x";
+        assert_eq!(eval(source).unwrap(), Value::Int(2));
+        assert_eq!(eval("deep_copy(42)").unwrap(), Value::Int(42));
+    }
+
+    #[test]
+    fn test_deep_copy_self_referential_table() {
+        let source = "local t := {name = \"node\"}\n\
+                      t.self = t\n\
+                      local copy := deep_copy(t)\n\
+                      copy.name = \"copy\"\n\
+                      copy.self.name + \"/\" + t.self.name";
+        assert_eq!(eval(source).unwrap(), Value::String("copy/node".to_string()));
+    }
+
+    #[test]
+    fn test_print_self_referential_array() {
+        let source = "local t := {1, 2}\n\
+                      t[2] = t\n\
+                      print(t)\n\
+                      print(to_string(t))";
+        assert_eq!(run_captured(source), "[1, <cycle>]\n[1, <cycle>]\n");
+    }
+
+    #[test]
+    fn test_negative_indices() {
+        assert_eq!(eval(r#"substring("hello", -3, 2)"#).unwrap(), Value::String("ll".to_string()));
+        assert_eq!(eval(r#"substring("hello", -1, 5)"#).unwrap(), Value::String("o".to_string()));
+        assert_eq!(eval(r#"substring("hello", -6, 2)"#).unwrap(), Value::String(String::new()));
+        assert_eq!(eval(r#"substring("hello", 1, -1)"#).unwrap(), Value::String(String::new()));
+
+        assert_eq!(eval("local t := {1, 2, 3}\nt[-1]").unwrap(), Value::Int(3));
+        assert_eq!(eval("local t := {1, 2, 3}\nt[-3]").unwrap(), Value::Int(1));
+        assert_eq!(eval("local t := {1, 2, 3}\nt[-4]").unwrap(), Value::Nil);
+
+        assert_eq!(eval("local t := {10, 20, 30}\nt[-1] = 99\nreturn t[3]").unwrap(), Value::Int(99));
+        assert_eq!(eval("local t := {10, 20, 30}\nt[-3] = 5\nreturn t[1]").unwrap(), Value::Int(5));
+        let err = eval("local t := {}\nt[-1] = \"neg\"").unwrap_err();
+        assert_eq!(err.message(), "Index -1 out of range for a table of length 0");
+        // Like `#t`, negative indices count back from the border
+        assert_eq!(eval("local t := {1, 2, 3}\nt[6] = 9\nreturn t[-1]").unwrap(), Value::Int(3));
+    }
+
+    #[test]
+    fn test_math_module() {
+        assert_eq!(eval("math.round(2.6)").unwrap(), Value::Int(3));
+        assert_eq!(eval("math.round(-2.5)").unwrap(), Value::Int(-3));
+        assert_eq!(eval("math.pi").unwrap(), Value::Float(std::f64::consts::PI));
+        assert_eq!(eval("math.sin(0)").unwrap(), Value::Float(0.0));
+        assert_eq!(eval("math.log(math.e)").unwrap(), Value::Float(1.0));
+        assert!(eval(r#"math.cos("x")"#).unwrap_err().to_string().contains("math.cos expects a number"));
+    }
+
+    #[test]
+    fn test_random_builtins() {
+        let in_range = "local ok := true\n\
+                        for local i := 0; i < 100; i = i + 1 {\n\
+                            local f := random()\n\
+                            local n := random_int(1, 6)\n\
+                            if f < 0.0 or f >= 1.0 or n < 1 or n > 6 { ok = false }\n\
+                        }\n\
+                        ok";
+        assert_eq!(eval(in_range).unwrap(), Value::Bool(true));
+
+        let sequence = "seed(42)\nlocal a := {random_int(1, 1000), random(), random_int(1, 1000)}\n\
+                        seed(42)\nlocal b := {random_int(1, 1000), random(), random_int(1, 1000)}\n\
+                        a == b";
+        assert_eq!(eval(sequence).unwrap(), Value::Bool(true));
+
+        let err = eval("random_int(5, 1)").unwrap_err();
+        assert!(err.to_string().contains("greater than max"));
+    }
+
+    #[test]
+    fn test_time_builtins() {
+        // Any time after 2020-01-01 counts as plausible
+        match eval("now_millis()").unwrap() {
+            Value::Int(ms) => assert!(ms > 1_577_836_800_000),
+            other => panic!("expected int, got {:?}", other),
+        }
+
+        let elapsed = eval("local start := clock()\nsleep(50)\nclock() - start").unwrap();
+        match elapsed {
+            Value::Float(secs) => assert!((0.045..1.0).contains(&secs), "elapsed {}", secs),
+            other => panic!("expected float, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_json_builtins() {
+        let source = r#"local data := json_parse("{\"user\": {\"name\": \"ann\", \"scores\": [90, 85.5]}}")
+local copy := json_parse(json_stringify(data))
+data.user.scores[2] + table_length(copy.user.scores)"#;
+        assert_eq!(eval(source).unwrap(), Value::Float(87.5));
+
+        assert_eq!(
+            eval(r#"json_stringify({1, "two", {x = true}})"#).unwrap(),
+            Value::String(r#"[1,"two",{"x":true}]"#.to_string())
+        );
+
+        let err = eval(r#"json_parse("[1, 2")"#).unwrap_err();
+        assert!(err.to_string().contains("json_parse: expected ',' or ']' in array at line 1, column 6"));
+        assert!(err.location().is_some());
+    }
+
+    #[test]
+    fn test_clamp() {
+        assert_eq!(eval("clamp(-5, 0, 10)").unwrap(), Value::Int(0));
+        assert_eq!(eval("clamp(5, 0, 10)").unwrap(), Value::Int(5));
+        assert_eq!(eval("clamp(15, 0, 10)").unwrap(), Value::Int(10));
+        assert_eq!(eval("clamp(0.5, 0, 1)").unwrap(), Value::Float(0.5));
+        assert_eq!(eval("clamp(3, 0, 2.5)").unwrap(), Value::Float(2.5));
+
+        assert_eq!(eval("clamp(1, 5, 0)").unwrap_err().message(), "clamp: lo (5) is greater than hi (0)");
+        assert_eq!(eval("clamp(1, 1.5, 0)").unwrap_err().message(), "clamp: lo (1.5) is greater than hi (0)");
+    }
+
+    #[test]
+    fn test_to_int_with_base() {
+        assert_eq!(eval("to_int(\"ff\", 16)").unwrap(), Value::Int(255));
+        assert_eq!(eval("to_int(\"-FF\", 16)").unwrap(), Value::Int(-255));
+        assert_eq!(eval("to_int(\"1011\", 2)").unwrap(), Value::Int(11));
+        assert_eq!(eval("to_int(\"zz\", 36)").unwrap(), Value::Int(1295));
+        assert_eq!(eval("to_int(\"42\")").unwrap(), Value::Int(42));
+
+        let err = eval("to_int(\"102\", 2)").unwrap_err();
+        assert_eq!(err.message(), "Cannot convert '102' to int in base 2");
+        let err = eval("to_int(\"1\", 37)").unwrap_err();
+        assert_eq!(err.message(), "to_int: base must be between 2 and 36, got 37");
+    }
+
+    #[test]
+    fn test_round() {
+        assert_eq!(eval("round(2.5)").unwrap(), Value::Int(3));
+        assert_eq!(eval("round(-2.5)").unwrap(), Value::Int(-3));
+        assert_eq!(eval("round(7)").unwrap(), Value::Int(7));
+        assert_eq!(eval("round(3.14159, 2) == 3.14").unwrap(), Value::Bool(true));
+        assert_eq!(eval("round(1234.5, -2)").unwrap(), Value::Float(1200.0));
+        assert_eq!(eval("round(2.675, 0)").unwrap(), Value::Float(3.0));
+        assert!(eval("round(1.5, 1.0)").unwrap_err().message().contains("an int number of digits"));
+    }
+
+    #[test]
+    fn test_min_of_and_max_of() {
+        assert_eq!(eval("max_of({3, 1, 4, 1, 5})").unwrap(), Value::Int(5));
+        assert_eq!(eval("min_of({3, 1, 4, 1, 5})").unwrap(), Value::Int(1));
+        assert_eq!(eval("max_of({2, 7.5, -1})").unwrap(), Value::Float(7.5));
+
+        let err = eval("max_of({})").unwrap_err();
+        assert_eq!(err.message(), "max_of: table is empty");
+        let err = eval("min_of({1, \"two\"})").unwrap_err();
+        assert_eq!(err.message(), "min_of: element 2 is a string, not a number");
+    }
+
+    #[test]
+    fn test_type_predicates() {
+        let values = ["1", "1.5", "\"s\"", "true", "{}", "nil", "print"];
+        let predicates = ["is_int", "is_float", "is_string", "is_bool", "is_table", "is_nil", "is_function"];
+        for (i, predicate) in predicates.iter().enumerate() {
+            for (j, value) in values.iter().enumerate() {
+                let result = eval(&format!("{}({})", predicate, value)).unwrap();
+                assert_eq!(result, Value::Bool(i == j), "{}({})", predicate, value);
+            }
+        }
+        assert_eq!(eval("is_function(fn(x) { return x })").unwrap(), Value::Bool(true));
+    }
+
+    #[test]
+    fn test_weak_refs() {
+        let source = r#"local cache := {name = "cache"}
+local ref := weak_ref(cache)
+print(weak_get(ref).name)
+print(type_of(ref))
+local holder := {cache}
+cache = nil
+print(weak_get(ref) != nil)
+holder = nil
+print(weak_get(ref) == nil)
+print(ref)"#;
+        assert_eq!(run_captured(source), "cache\nweakref\ntrue\ntrue\n<weakref (collected)>\n");
+        assert!(eval("weak_get({})").unwrap_err().message().contains("weak_get expects a weakref, got table"));
+    }
+
+    #[test]
+    fn test_assign_through_pointer() {
+        let source = "local x := 1\nlocal p := &x\nlocal q := p\n*p = 5\nprint(*p)\nprint(*q)\n*q = *q + 1\nprint(*p)\nprint(x)";
+        // Copies of a pointer share its box, but the box is a copy of x
+        assert_eq!(run_captured(source), "5\n5\n6\n1\n");
+        assert_eq!(eval("local x := 1\nlocal p := &x\n*p = 41\nreturn *p + 1").unwrap(), Value::Int(42));
+        // A `*` starting a line that doesn't assign still multiplies
+        let source = "local width := 6\nlocal height := 7\nlocal area := width\n    * height\nreturn area";
+        assert_eq!(eval(source).unwrap(), Value::Int(42));
+
+        let err = eval("local n := 3\n*n = 4").unwrap_err();
+        assert!(err.message().contains("Cannot dereference non-pointer type"), "{}", err);
+    }
+
+    #[test]
+    fn test_length_stops_at_first_hole() {
+        let source = r#"local t := {1, 2, 3}
+t[6] = 6
+print(#t)
+print(table_length(t))
+print(count(t))
+local mixed := {"a", "b", name = "pair", size = 2}
+print(#mixed)
+print(count(mixed))"#;
+        assert_eq!(run_captured(source), "3\n3\n4\n2\n4\n");
+    }
+
+    #[test]
+    fn test_table_concat() {
+        let source = r#"print(table_concat({1, 2, 3}, "-"))
+print(table_concat({}, "-") == "")
+print(table_concat({"solo"}, ", "))
+print(table_concat({"a", 1.5, 2}, ""))"#;
+        assert_eq!(run_captured(source), "1-2-3\ntrue\nsolo\na1.52\n");
+
+        let err = eval(r#"table_concat({"a", true}, ",")"#).unwrap_err();
+        assert_eq!(err.message(), "table_concat: element 2 is a bool, not a string or number");
+    }
+
+    #[test]
+    fn test_table_slice_and_reverse() {
+        let source = r#"local t := {10, 20, 30, 40, 50, name = "nums"}
+print(table_concat(table_slice(t, 2, 4), ","))
+print(table_concat(table_slice(t, -3, 99), ","))
+print(#table_slice(t, 4, 2))
+print(table_slice(t, 1, 5).name == nil)
+print(table_concat(table_reverse({1, 2, 3}), ","))
+print(#t)"#;
+        assert_eq!(run_captured(source), "20,30,40\n10,20,30,40,50\n0\ntrue\n3,2,1\n5\n");
+    }
+
+    #[test]
+    fn test_table_contains_and_index_of() {
+        let source = r#"local t := {"a", 2, {1, 2}, 3.5}
+print(table_contains(t, "a"))
+print(index_of(t, {1, 2}))
+print(table_contains(t, "z"))
+print(index_of(t, 4))
+print(index_of(t, 2.0))
+print(table_contains({1, 2, 3}, 3.0))
+print(index_of({{1}, {1.0, 2}}, {1, 2}))"#;
+        assert_eq!(run_captured(source), "true\n3\nfalse\n0\n2\ntrue\n2\n");
+    }
+
+    #[test]
+    fn test_dump_prints_nested_tables() {
+        let source = r#"local config := {name = "lux", ports = {80, 443}}
+config.self = config
+dump(config)
+dump("plain")"#;
+        let expected = "{\n  name = \"lux\",\n  ports = [\n    80,\n    443\n  ],\n  self = <cycle>\n}\nplain\n";
+        assert_eq!(run_captured(source), expected);
+    }
+
+    #[test]
+    fn test_inspect_mixed_table() {
+        let source = r#"fn greet(name: string) -> string { return "hi " + name }
+local t := {1, "two", 3.5, tags = {"a", "b"}, meta = {}, greet = greet, len = string_length, ok = true}
+return inspect(t)"#;
+        let expected = r#"{
+  1,
+  "two",
+  3.5,
+  greet = <fn greet>,
+  len = <fn string_length>,
+  meta = {},
+  ok = true,
+  tags = [
+    "a",
+    "b"
+  ]
+}"#;
+        assert_eq!(eval(source).unwrap(), Value::String(expected.to_string()));
+        assert_eq!(eval(r#"inspect("say \"hi\"")"#).unwrap(), Value::String(r#""say \"hi\"""#.to_string()));
+        assert_eq!(eval("inspect(fn(x: int) -> int { return x })").unwrap(), Value::String("<fn>".to_string()));
+    }
+
+    #[test]
+    fn test_assert_builtin() {
+        assert_eq!(eval("assert(1 < 2)\nassert(\"x\", \"unused\")").unwrap(), Value::Nil);
+
+        let err = eval("local total := 3\nassert(total == 4, \"expected 4, got \" + to_string(total))").unwrap_err();
+        assert_eq!(err.message(), "expected 4, got 3");
+        assert_eq!(err.location().map(|l| l.line), Some(2));
+
+        assert_eq!(eval("assert(nil)").unwrap_err().message(), "assertion failed");
+        assert!(eval("assert(false, \"a\", \"b\")").unwrap_err().message().contains("got 3 arguments"));
+    }
+
+    #[test]
+    fn test_hex_and_base64_builtins() {
+        let source = r#"local bytes := from_hex("00ff7f")
+print(bytes[1] + bytes[2] + bytes[3])
+print(to_hex(bytes))
+print(to_hex("Lux"))
+print(to_hex(from_hex("DEADbeef")))
+print(base64_encode("Lux!"))
+print(base64_encode(base64_decode("3q2+7w==")))
+print(to_hex(base64_decode(base64_encode(bytes))))"#;
+        assert_eq!(run_captured(source), "382\n00ff7f\n4c7578\ndeadbeef\nTHV4IQ==\n3q2+7w==\n00ff7f\n");
+
+        let errors = [
+            ("from_hex(\"abc\")", "from_hex: expected an even number of digits, got 3"),
+            ("from_hex(\"0g\")", "from_hex: invalid hex digit 'g' at position 2"),
+            ("base64_decode(\"ab$d\")", "base64_decode: invalid character '$' at position 3"),
+            ("base64_decode(\"abcde\")", "base64_decode: input is truncated"),
+            ("to_hex({1, 256})", "to_hex: element 2 is out of byte range (0 to 255): 256"),
+        ];
+        for (source, expected) in errors {
+            let err = eval(source).unwrap_err();
+            assert!(err.to_string().contains(expected), "{}: {}", source, err);
+            assert!(err.location().is_some());
+        }
+    }
+
+    #[test]
+    fn test_get_env() {
+        // Read a variable the test environment already has; setting one would
+        // race with other tests reading the environment
+        let path = std::env::var("PATH").map_or(Value::Nil, Value::String);
+        assert_eq!(eval(r#"get_env("PATH")"#).unwrap(), path);
+        assert_eq!(eval(r#"get_env("LUX_TEST_SURELY_UNSET")"#).unwrap(), Value::Nil);
+    }
+
+    #[test]
+    fn test_args() {
+        let tokens = Lexer::new("args()", None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new().with_args(vec!["one".to_string(), "two".to_string()]);
+        let value = interpreter.eval(&ast).unwrap();
+        assert_eq!(
+            value,
+            Value::table(TableValue { array: vec![Value::String("one".to_string()), Value::String("two".to_string())], ..TableValue::new() })
+        );
+        assert_eq!(eval("#args()").unwrap(), Value::Int(0));
+    }
+
+    #[test]
+    fn test_append_file() {
+        let path = std::env::temp_dir().join("lux_append_file_test.txt");
+        std::fs::remove_file(&path).ok();
+        let source = format!(
+            "append_file({0:?}, \"first\\n\")\nappend_file({0:?}, \"second\\n\")\nread_file({0:?})",
+            path.to_str().unwrap()
+        );
+        let result = eval(&source);
+        std::fs::remove_file(&path).ok();
+        assert_eq!(result.unwrap(), Value::String("first\nsecond\n".to_string()));
+
+        let err = eval(r#"append_file("/nonexistent-dir/log.txt", "x")"#).unwrap_err();
+        assert!(err.to_string().contains("Failed to append to file"));
+    }
+
+    #[test]
+    fn test_print_writes_to_custom_output() {
+        assert_eq!(run_captured("print(\"hello\")\nprint(1 + 2)"), "hello\n3\n");
+    }
+
+    #[test]
+    fn test_print_keeps_floats_distinct_from_ints() {
+        let source = "print(3.0)\nprint(3)\nprint(7 / 7)\nprint(-0.0)\nprint(2.5)\nprint(10.0 ^ 20)\nprint(1.0 / 0.0)";
+        assert_eq!(run_captured(source), "3.0\n3\n1.0\n-0.0\n2.5\n100000000000000000000.0\ninf\n");
+        // to_string keeps the shortest form
+        assert_eq!(eval("to_string(3.0)").unwrap(), Value::String("3".to_string()));
+    }
+
+    #[test]
+    fn test_runtime_error_backtrace() {
+        let source = "fn c(n: int) -> int {\n    return n % 0\n}\n\
+                      fn b(n: int) -> int {\n    return c(n)\n}\n\
+                      fn a(n: int) -> int {\n    return b(n)\n}\n\
+                      a(1)";
+        let err = eval(source).unwrap_err();
+        let frames: Vec<_> = err.backtrace().iter().map(|frame| frame.function.as_str()).collect();
+        assert_eq!(frames, ["c", "b", "a"]);
+
+        let text = err.to_string();
+        assert!(text.starts_with("Runtime Error: Modulo by zero at 2:14\nBacktrace (most recent call first):"));
+        assert!(text.contains("in c (defined at 1:1, called at 5:13)"));
+        assert!(text.contains("in b (defined at 4:1, called at 8:13)"));
+        assert!(text.contains("in a (defined at 7:1, called at 10:2)"));
+
+        // Errors outside any function have no backtrace
+        assert!(eval("local x := 1\nx % 0").unwrap_err().backtrace().is_empty());
+    }
+
+    #[test]
+    fn test_infinite_recursion_is_a_runtime_error() {
+        let err = eval("fn forever(n: int) -> int {\n    return forever(n + 1)\n}\nforever(0)").unwrap_err();
+        assert!(err.message().starts_with("stack overflow"));
+        assert_eq!(err.backtrace().len(), DEFAULT_MAX_DEPTH);
+    }
+
+    #[test]
+    fn test_int_overflow_policy() {
+        let run = |source: &str, policy: IntOverflow| {
+            let tokens = Lexer::new(source, None).with_newlines().tokenize().unwrap();
+            let ast = Parser::new(tokens).parse().unwrap();
+            Interpreter::new().with_int_overflow(policy).eval(&ast)
+        };
+        let max = "local big := 9223372036854775807\n";
+        let min = "local small := -9223372036854775807 - 1\nreturn ";
+
+        // Checked is the default
+        assert_eq!(eval(&format!("{}big + 1", max)).unwrap_err().message(), "integer overflow");
+        for expr in ["big + 1", "big * 2"] {
+            let err = run(&format!("{}{}", max, expr), IntOverflow::Checked).unwrap_err();
+            assert_eq!(err.message(), "integer overflow", "{}", expr);
+        }
+        for expr in ["small - 1", "-small", "small ~/ -1"] {
+            let err = run(&format!("{}{}", min, expr), IntOverflow::Checked).unwrap_err();
+            assert_eq!(err.message(), "integer overflow", "{}", expr);
+        }
+        assert_eq!(run(&format!("{}small % -1", min), IntOverflow::Checked).unwrap(), Value::Int(0));
+
+        assert_eq!(run(&format!("{}big + 1", max), IntOverflow::Wrapping).unwrap(), Value::Int(i64::MIN));
+        assert_eq!(run(&format!("{}big * 2", max), IntOverflow::Wrapping).unwrap(), Value::Int(-2));
+        assert_eq!(run(&format!("{}-small", min), IntOverflow::Wrapping).unwrap(), Value::Int(i64::MIN));
+        assert_eq!(run(&format!("{}small ~/ -1", min), IntOverflow::Wrapping).unwrap(), Value::Int(i64::MIN));
+        assert_eq!(run(&format!("{}abs(small)", min), IntOverflow::Wrapping).unwrap(), Value::Int(i64::MIN));
+        let err = run(&format!("{}abs(small)", min), IntOverflow::Checked).unwrap_err();
+        assert_eq!(err.message(), "integer overflow");
+        // Results in range are the same under both policies
+        assert_eq!(run("-7 ~/ 2", IntOverflow::Wrapping).unwrap(), Value::Int(-4));
+    }
+
+    #[test]
+    fn test_max_depth_is_configurable() {
+        let source = "fn down(n: int) -> int {\n    if n == 0 { return 0 }\n    return down(n - 1)\n}\n";
+        let run = |calls: i64, max_depth: usize| {
+            let tokens = Lexer::new(&format!("{}down({})", source, calls), None).tokenize().unwrap();
+            let ast = Parser::new(tokens).parse().unwrap();
+            Interpreter::new().with_max_depth(max_depth).eval(&ast)
+        };
+        assert_eq!(run(9, 10).unwrap(), Value::Int(0));
+        let err = run(10, 10).unwrap_err();
+        assert_eq!(err.message(), "stack overflow (more than 10 nested calls)");
+    }
+
+    #[test]
+    fn test_method_call_passes_receiver() {
+        let source = "local counter := {count = 2}\n\
+                      counter.add = fn(self, n) { self.count = self.count + n\n return self.count }\n\
+                      counter:add(3)";
+        assert_eq!(eval(source).unwrap(), Value::Int(5));
+
+        // The receiver expression is evaluated only once
+        let source = "local made := 0\n\
+                      local obj := {get = fn(self) { return made }}\n\
+                      fn make() { made = made + 1\n return obj }\n\
+                      make():get()";
+        assert_eq!(eval(source).unwrap(), Value::Int(1));
+
+        let err = eval("fn call_get(x) { return x:get() }\ncall_get(1)").unwrap_err();
+        assert!(err.message().contains("Cannot call method 'get' on int"));
+    }
+
+    #[test]
+    fn test_local_function_declaration() {
+        let source = "local fn fact(n: int) -> int {\n\
+                      if n <= 1 { return 1 }\n\
+                      return n * fact(n - 1)\n\
+                      }\n\
+                      fact(5)";
+        assert_eq!(eval(source).unwrap(), Value::Int(120));
+    }
+
+    #[test]
+    fn test_do_block_expression() {
+        assert_eq!(eval("local x := do { local a := 2; a * 21 }\nx").unwrap(), Value::Int(42));
+        assert_eq!(eval("do { local a := 1 }").unwrap(), Value::Nil);
+
+        // Locals declared inside the block do not leak out
+        let err = eval("local x := do { local inner := 1\n inner }\ninner").unwrap_err();
+        assert!(err.message().contains("inner"));
+    }
+
+    #[test]
+    fn test_interpreters_share_builtins() {
+        let first = Interpreter::new();
+        let second = Interpreter::new();
+        assert!(std::ptr::eq(first.env.builtins, second.env.builtins));
+        assert!(matches!(first.global("print"), Some(Value::NativeFunction(f)) if f.name == "print"));
+
+        // Builtin tables are copied, so one program's changes don't leak into another
+        assert_eq!(eval("math.pi = 3\nmath.pi").unwrap(), Value::Int(3));
+        assert_eq!(eval("math.pi").unwrap(), Value::Float(std::f64::consts::PI));
+
+        // Builtins can be reassigned and shadowed like any global
+        assert_eq!(run_captured("print = fn(x) { return x }\nprint(1)"), "");
+        assert_eq!(run_captured("{ local string_length := 5\n print(string_length) }\nprint(string_length(\"ab\"))"), "5\n2\n");
+    }
+
+    #[test]
+    fn test_function_values_share_their_body() {
+        let source = "fn twice(n: int) -> int { return n * 2 }\n\
+                      local alias := twice\n\
+                      local apply := fn(f, x) { return f(x) }\n\
+                      print(apply(alias, 21))";
+        assert_eq!(run_captured(source), "42\n");
+
+        let tokens = Lexer::new(source, None).with_newlines().tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        let mut interpreter = Interpreter::new().with_output(Box::new(SharedBuffer::default()));
+        interpreter.interpret(&ast).unwrap();
+
+        let body = |name: &str| match interpreter.global(name) {
+            Some(Value::Function(function)) => function.body,
+            other => panic!("{} is {:?}", name, other),
+        };
+        let Stmt::FunctionDecl { body: declared, .. } = &ast.statements[0] else { unreachable!() };
+        assert!(Arc::ptr_eq(&body("twice"), &body("alias")));
+        assert!(Arc::ptr_eq(&body("twice"), declared));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        // An inner declaration hides the outer one until its block ends;
+        // assignments go to the innermost binding
+        let source = "local x := 1\n\
+                      {\n local x := 2\n { local x := 3\n x = x + 10\n print(x) }\n print(x)\n x = 20 }\n\
+                      print(x)\n\
+                      { x = 5 }\n\
+                      print(x)";
+        assert_eq!(run_captured(source), "13\n2\n1\n5\n");
+
+        // Redeclaring in the same scope replaces the binding
+        assert_eq!(eval("local y := 1\n{ local y := 2\n local y := y + 1\n y = y * 10\n print(y) }\ny").unwrap(), Value::Int(1));
+        assert_eq!(run_captured("{ local z := 1\n local z := 2\n print(z) }"), "2\n");
+
+        // Parameters shadow globals, and a function's locals vanish when it returns
+        let source = "local n := 100\n\
+                      fn f(n) { local m := n * 2\n n = n + 1\n return n + m }\n\
+                      print(f(1))\nprint(n)";
+        assert_eq!(run_captured(source), "4\n100\n");
+        assert!(eval("fn g() { local hidden := 1 }\ng()\nhidden").is_err());
+
+        // Each loop iteration gets fresh locals
+        let source = "for local i := 0; i < 3; i = i + 1 { local seen := i * i\n print(seen) }";
+        assert_eq!(run_captured(source), "0\n1\n4\n");
+    }
+
+    #[test]
+    fn test_continue_from_nested_block() {
+        let source = "local evens := 0\n\
+                      for local i := 0; i < 10; i = i + 1 {\n\
+                      {\n if i % 2 == 1 { continue }\n }\n\
+                      evens = evens + 1\n\
+                      }\n\
+                      local j := 0\n\
+                      while j < 10 {\n\
+                      j = j + 1\n\
+                      {\n if j % 2 == 1 { continue }\n }\n\
+                      evens = evens + 1\n\
+                      }\n\
+                      evens";
+        assert_eq!(eval(source).unwrap(), Value::Int(10));
+    }
+
+    #[test]
+    fn test_loop_signals_from_nested_blocks() {
+        // break one block deep leaves the loop
+        let source = "local i := 0\n\
+                      while true {\n i = i + 1\n { if i == 3 { break } }\n }\n\
+                      for local j := 0; j < 10; j = j + 1 {\n { if j == 4 { break } }\n i = i + 1\n }\n\
+                      i";
+        assert_eq!(eval(source).unwrap(), Value::Int(7));
+
+        // continue one block deep skips the rest of the body
+        let source = "local odd := 0\n\
+                      for local j := 0; j < 6; j = j + 1 {\n { if j % 2 == 0 { continue } }\n odd = odd + 1\n }\n\
+                      odd";
+        assert_eq!(eval(source).unwrap(), Value::Int(3));
+
+        // return one block deep leaves the enclosing function
+        let source = "fn find_while(n: int) -> int {\n\
+                      local i := 0\n\
+                      while i < 10 {\n { if i == n { return i * 10 } }\n i = i + 1\n }\n\
+                      return -1\n\
+                      }\n\
+                      fn find_for(n: int) -> int {\n\
+                      for local i := 0; i < 10; i = i + 1 {\n { if i == n { return i * 100 } }\n }\n\
+                      return -1\n\
+                      }\n\
+                      find_while(4) + find_for(2)";
+        assert_eq!(eval(source).unwrap(), Value::Int(240));
+    }
+
+    #[test]
+    fn test_forward_references_and_mutual_recursion() {
+        let source = "local first := a()\n\
+                      fn a() -> int { return b() + 1 }\n\
+                      fn b() -> int { return 41 }\n\
+                      first";
+        assert_eq!(eval(source).unwrap(), Value::Int(42));
+
+        let source = "fn is_even(n: int) -> bool {\n if n == 0 { return true }\n return is_odd(n - 1)\n }\n\
+                      fn is_odd(n: int) -> bool {\n if n == 0 { return false }\n return is_even(n - 1)\n }\n\
+                      is_even(10) and is_odd(7)";
+        assert_eq!(eval(source).unwrap(), Value::Bool(true));
+
+        // The declaration's own line doesn't redefine the function
+        let source = "f = fn() -> int { return 2 }\n\
+                      print(f())\n\
+                      fn f() -> int { return 1 }\n\
+                      print(f())";
+        assert_eq!(run_captured(source), "2\n2\n");
+    }
+
+    #[test]
+    fn test_defers_run_in_reverse_order() {
+        let source = "fn work() {\n\
+                      defer print(\"first deferred\")\n\
+                      defer print(\"second deferred\")\n\
+                      print(\"body\")\n\
+                      }\n\
+                      work()\n\
+                      {\n defer print(\"block exit\")\n print(\"in block\")\n }\n\
+                      print(\"after\")";
+        assert_eq!(
+            run_captured(source),
+            "body\nsecond deferred\nfirst deferred\nin block\nblock exit\nafter\n"
+        );
+    }
+
+    #[test]
+    fn test_defer_runs_on_early_return() {
+        let source = "fn check(n) {\n\
+                      defer print(\"cleanup\")\n\
+                      if n > 0 { return \"positive\" }\n\
+                      print(\"not positive\")\n\
+                      return \"other\"\n\
+                      }\n\
+                      print(check(1))\n\
+                      print(check(0))";
+        assert_eq!(run_captured(source), "cleanup\npositive\nnot positive\ncleanup\nother\n");
+    }
+
+    #[test]
+    fn test_enum_members_are_ints() {
+        let source = "enum Color { Red, Green, Blue = 10, Violet }\n\
+                      Color.Green == 1 and Color.Red == 0 and Color.Violet == 11";
+        assert_eq!(eval(source).unwrap(), Value::Bool(true));
+        assert!(eval("enum Color { Red }\nColor.Purple").is_err());
+    }
+
+    #[test]
+    fn test_struct_constructor_builds_a_table() {
+        let source = "struct Point { x: int, y: int }\n\
+                      local p := Point { x = 1, y = 2 }\n\
+                      p.x = p.x + 10\n\
+                      p.x + p.y";
+        assert_eq!(eval(source).unwrap(), Value::Int(13));
+    }
+
+    #[test]
+    fn test_custom_search_path() {
+        let dir = std::env::temp_dir().join("lux_custom_search_path");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("only_here.lux"), "fn answer() -> int { return 42 }").unwrap();
+
+        let tokens = Lexer::new("import \"only_here\"\nanswer()", None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        assert!(Interpreter::new().eval(&ast).unwrap_err().to_string().contains("Module 'only_here' not found"));
+        let value = Interpreter::new().with_search_path(&dir).eval(&ast).unwrap();
+        assert_eq!(value, Value::Int(42));
+    }
+
+    #[test]
+    fn test_aliased_and_selective_imports() {
+        let dir = std::env::temp_dir().join("lux_selective_imports");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("shapes.lux"),
+            "local sides := 4\n\
+             fn square(n: int) -> int { return n * n }\n\
+             fn area(n: int) -> int { return square(n) }",
+        )
+        .unwrap();
+        let shapes = dir.join("shapes").to_string_lossy().to_string();
+
+        // Module functions still see the module's other top-level bindings
+        let aliased = format!("import \"{}\" as shapes\nshapes.area(3) + shapes.sides", shapes);
+        assert_eq!(eval(&aliased).unwrap(), Value::Int(13));
+        let selective = format!("import {{ area }} from \"{}\"\narea(5)", shapes);
+        assert_eq!(eval(&selective).unwrap(), Value::Int(25));
+
+        // Nothing else leaks into the importing scope
+        let leaked = format!("import \"{}\" as shapes\nsquare(2)", shapes);
+        assert!(eval(&leaked).is_err());
+        let leaked = format!("import {{ area }} from \"{}\"\nsides", shapes);
+        assert!(eval(&leaked).is_err());
+        let missing = format!("import {{ volume }} from \"{}\"", shapes);
+        assert!(eval(&missing).unwrap_err().to_string().contains("has no 'volume'"));
+    }
+
+    #[test]
+    fn test_tuples() {
+        let source = "local pair := (1, \"x\")\n\
+                      local a, b = pair\n\
+                      print(pair)\n\
+                      print(type_of(pair))\n\
+                      print(#pair + pair[1])\n\
+                      print(pair[-1] == b and a == 1)\n\
+                      print(pair == (1, \"x\"))\n\
+                      print((1,))";
+        assert_eq!(run_captured(source), "(1, x)\ntuple\n3\ntrue\ntrue\n(1)\n");
+        assert!(eval("local t := (1, 2)\nlocal i := 3\nt[i]").unwrap_err().to_string().contains("out of range"));
+    }
+}
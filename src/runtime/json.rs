@@ -0,0 +1,340 @@
+//! JSON conversion
+//!
+//! This module converts between JSON text and Lux values for the
+//! `json_parse` and `json_stringify` builtins. Objects map to table fields
+//! and arrays map to the table's array part.
+
+use std::sync::Arc;
+use super::value::{TableRef, TableValue, Value};
+
+/// Parse JSON text into a Lux value
+pub fn parse(text: &str) -> Result<Value, String> {
+    let mut parser = JsonParser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+/// Serialize a Lux value as compact JSON text
+pub fn stringify(value: &Value) -> Result<String, String> {
+    let mut out = String::new();
+    write_value(value, &mut out, &mut Vec::new())?;
+    Ok(out)
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonParser {
+    /// Build an error message carrying the line and column of the current position
+    fn error(&self, message: &str) -> String {
+        let consumed = &self.chars[..self.pos.min(self.chars.len())];
+        let line = consumed.iter().filter(|&&c| c == '\n').count() + 1;
+        let column = consumed.iter().rev().take_while(|&&c| c != '\n').count() + 1;
+        format!("json_parse: {} at line {}, column {}", message, line, column)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('-' | '0'..='9') => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => self.keyword(),
+            Some(c) => Err(self.error(&format!("unexpected character '{}'", c))),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn keyword(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(c) if c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        let word: String = self.chars[start..self.pos].iter().collect();
+        match word.as_str() {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            "null" => Ok(Value::Nil),
+            _ => {
+                self.pos = start;
+                Err(self.error(&format!("unknown literal '{}'", word)))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        if self.peek() == Some('-') {
+            self.pos += 1;
+        }
+        let mut is_float = false;
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' => {}
+                '.' | 'e' | 'E' | '+' | '-' => is_float = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+
+        if !is_float {
+            if let Ok(n) = text.parse::<i64>() {
+                return Ok(Value::Int(n));
+            }
+        }
+        text.parse::<f64>().map(Value::Float).map_err(|_| {
+            self.pos = start;
+            self.error(&format!("invalid number '{}'", text))
+        })
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut result = String::new();
+        loop {
+            let c = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            match c {
+                '"' => return Ok(result),
+                '\\' => {
+                    let escape = self.peek().ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    match escape {
+                        '"' => result.push('"'),
+                        '\\' => result.push('\\'),
+                        '/' => result.push('/'),
+                        'b' => result.push('\u{8}'),
+                        'f' => result.push('\u{c}'),
+                        'n' => result.push('\n'),
+                        'r' => result.push('\r'),
+                        't' => result.push('\t'),
+                        'u' => result.push(self.unicode_escape()?),
+                        other => {
+                            self.pos -= 1;
+                            return Err(self.error(&format!("invalid escape '\\{}'", other)));
+                        }
+                    }
+                }
+                c if (c as u32) < 0x20 => {
+                    self.pos -= 1;
+                    return Err(self.error("control character in string"));
+                }
+                c => result.push(c),
+            }
+        }
+    }
+
+    /// Decode the hex digits after `\u`, combining surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.peek() != Some('\\') || self.chars.get(self.pos + 1) != Some(&'u') {
+                return Err(self.error("unpaired surrogate in \\u escape"));
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("invalid low surrogate in \\u escape"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.iter().skip(self.pos).take(4).collect();
+        if digits.len() != 4 {
+            return Err(self.error("incomplete \\u escape"));
+        }
+        let code = u32::from_str_radix(&digits, 16).map_err(|_| self.error("invalid \\u escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut table = TableValue::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Value::table(table));
+        }
+        loop {
+            self.skip_whitespace();
+            table.array.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Value::table(table));
+                }
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut table = TableValue::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Value::table(table));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected string key in object"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            table.fields.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Value::table(table));
+                }
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+}
+
+fn write_value(value: &Value, out: &mut String, stack: &mut Vec<*const ()>) -> Result<(), String> {
+    match value {
+        Value::Nil => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Int(n) => out.push_str(&n.to_string()),
+        Value::Float(f) if f.is_finite() => out.push_str(&format!("{:?}", f)),
+        Value::Float(f) => return Err(format!("json_stringify: cannot encode {}", f)),
+        Value::String(s) => write_string(s, out),
+        Value::Table(table) => write_table(table, out, stack)?,
+        other => return Err(format!("json_stringify: cannot encode a {}", other.type_name())),
+    }
+    Ok(())
+}
+
+/// Tables with only an array part become JSON arrays; anything else becomes
+/// an object, with array elements keyed by their index
+fn write_table(table: &TableRef, out: &mut String, stack: &mut Vec<*const ()>) -> Result<(), String> {
+    let id = Arc::as_ptr(table) as *const ();
+    if stack.contains(&id) {
+        return Err("json_stringify: cannot encode a cyclic table".to_string());
+    }
+    stack.push(id);
+
+    let snapshot = table.lock().unwrap().clone();
+    if snapshot.fields.is_empty() {
+        out.push('[');
+        for (i, item) in snapshot.array.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_value(item, out, stack)?;
+        }
+        out.push(']');
+    } else {
+        out.push('{');
+        for (i, (key, item)) in snapshot.entries().iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_string(&key.to_string(), out);
+            out.push(':');
+            write_value(item, out, stack)?;
+        }
+        out.push('}');
+    }
+
+    stack.pop();
+    Ok(())
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let text = r#"{"name":"lux","nested":{"list":[1,2.5,"three",true,null]},"tags":[]}"#;
+        let value = parse(text).unwrap();
+        assert_eq!(stringify(&value).unwrap(), text);
+    }
+
+    #[test]
+    fn test_parse_escapes_and_whitespace() {
+        let value = parse(" [ \"a\\n\\u00e9\\ud83d\\ude00\" , -12 , 1e3 ] ").unwrap();
+        assert_eq!(stringify(&value).unwrap(), "[\"a\\né😀\",-12,1000.0]");
+    }
+
+    #[test]
+    fn test_parse_error_has_position() {
+        let err = parse("{\"a\": 1,\n  \"b\" 2}").unwrap_err();
+        assert_eq!(err, "json_parse: expected ':' at line 2, column 7");
+        assert!(parse("[1, 2").unwrap_err().contains("expected ',' or ']'"));
+        assert!(parse("nope").unwrap_err().contains("unknown literal 'nope'"));
+    }
+
+    #[test]
+    fn test_stringify_rejects_cycles() {
+        let table = Value::table(TableValue::new());
+        if let Value::Table(inner) = &table {
+            inner.lock().unwrap().set(Value::String("me".to_string()), table.clone()).unwrap();
+        }
+        assert!(stringify(&table).unwrap_err().contains("cyclic"));
+    }
+}
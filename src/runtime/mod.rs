@@ -4,7 +4,13 @@
 
 pub mod value;
 pub mod interpreter;
+mod encoding;
+mod json;
+mod pattern;
+mod random;
+#[cfg(test)]
+pub(crate) mod testing;
 
 pub use value::Value;
-pub use interpreter::Interpreter;
+pub use interpreter::{IntOverflow, Interpreter};
 
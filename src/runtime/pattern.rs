@@ -0,0 +1,42 @@
+//! Regular expressions
+//!
+//! This module backs the `regex_match`, `regex_find` and `regex_replace`
+//! builtins using the `regex` crate's syntax. Compiled patterns are cached,
+//! so calling a builtin in a loop with the same pattern compiles it once.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use regex::Regex;
+
+/// Patterns kept compiled at once; the cache is cleared when it fills up
+const CACHE_LIMIT: usize = 64;
+
+static CACHE: LazyLock<Mutex<HashMap<String, Regex>>> = LazyLock::new(Default::default);
+
+/// Compile `pattern`, or describe why it doesn't compile
+pub fn compile(name: &str, pattern: &str) -> Result<Regex, String> {
+    let mut cache = CACHE.lock().unwrap();
+    if let Some(regex) = cache.get(pattern) {
+        return Ok(regex.clone());
+    }
+    let regex = Regex::new(pattern).map_err(|err| format!("{}: invalid pattern: {}", name, err))?;
+    if cache.len() >= CACHE_LIMIT {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_reuses_cached_patterns() {
+        let first = compile("regex_match", r"\d+").unwrap();
+        let second = compile("regex_match", r"\d+").unwrap();
+        assert_eq!(first.as_str(), second.as_str());
+        let err = compile("regex_find", "(unclosed").unwrap_err();
+        assert!(err.starts_with("regex_find: invalid pattern:"), "{}", err);
+    }
+}
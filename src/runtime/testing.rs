@@ -0,0 +1,39 @@
+//! Helpers shared by the interpreter and VM tests
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use super::Interpreter;
+use crate::{Lexer, Parser};
+
+/// Writer that appends into a buffer the test can read back
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl SharedBuffer {
+    pub(crate) fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+/// Interpret `source`, returning everything it printed
+pub(crate) fn run_captured(source: &str) -> String {
+    let buffer = SharedBuffer::default();
+    let tokens = Lexer::new(source, None).with_newlines().tokenize().unwrap();
+    let ast = Parser::new(tokens).parse().unwrap();
+    Interpreter::new()
+        .with_output(Box::new(buffer.clone()))
+        .interpret(&ast)
+        .unwrap();
+    buffer.contents()
+}
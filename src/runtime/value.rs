@@ -4,7 +4,8 @@
 
 use std::collections::HashMap;
 use std::fmt;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, Weak};
+use super::Interpreter;
 
 /// Runtime value
 #[derive(Debug, Clone)]
@@ -14,18 +15,33 @@ pub enum Value {
     String(String),
     Bool(bool),
     Nil,
-    Table(TableValue),
+    Table(TableRef),
     Function(FunctionValue),
     NativeFunction(NativeFunctionValue),
+    /// Box made by `&expr` from a copy of the value; copies of the pointer
+    /// share the box, but it doesn't alias the variable it was taken from
     Pointer(Arc<Mutex<Value>>),
+    /// Fixed-length, immutable sequence of values
+    Tuple(Vec<Value>),
+    /// Reference to a table that doesn't keep it alive, from `weak_ref`
+    WeakRef(Weak<Mutex<TableValue>>),
 }
 
+/// Shared handle to a table; tables have reference semantics
+pub type TableRef = Arc<Mutex<TableValue>>;
+
 /// Table value (Lua-style associative array)
 #[derive(Debug, Clone)]
 pub struct TableValue {
     pub fields: HashMap<String, Value>,
     pub array: Vec<Value>,
-    pub metatable: Option<Box<TableValue>>,
+    pub metatable: Option<TableRef>,
+}
+
+impl Default for TableValue {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TableValue {
@@ -37,18 +53,24 @@ impl TableValue {
         }
     }
 
+    /// Look up `key`; a negative index counts back from the border, so
+    /// `t[-1]` is `t[#t]`
     pub fn get(&self, key: &Value) -> Option<Value> {
         match key {
             Value::Int(n) if *n > 0 => {
                 let index = (*n - 1) as usize;
                 self.array.get(index).cloned()
             }
+            Value::Int(n) if *n < 0 => self.back_from_border(*n).and_then(|i| self.array.get(i).cloned()),
             Value::String(s) => self.fields.get(s).cloned(),
             _ => None,
         }
     }
 
-    pub fn set(&mut self, key: Value, value: Value) {
+    /// Store `value` under `key`, counting negative indices back from the
+    /// border as [`TableValue::get`] does. A negative index before the
+    /// first element is an error.
+    pub fn set(&mut self, key: Value, value: Value) -> Result<(), String> {
         match key {
             Value::Int(n) if n > 0 => {
                 let index = (n - 1) as usize;
@@ -57,15 +79,67 @@ impl TableValue {
                 }
                 self.array[index] = value;
             }
+            Value::Int(n) if n < 0 => {
+                let index = self
+                    .back_from_border(n)
+                    .ok_or_else(|| format!("Index {} out of range for a table of length {}", n, self.len()))?;
+                self.array[index] = value;
+            }
             Value::String(s) => {
                 self.fields.insert(s, value);
             }
             _ => {}
         }
+        Ok(())
     }
 
+    /// The array position of the negative index `n`, counted back from the border
+    fn back_from_border(&self, n: i64) -> Option<usize> {
+        usize::try_from(self.len() as i64 + n).ok()
+    }
+
+    /// All non-nil entries in iteration order: the array part by index,
+    /// then named fields sorted by key so the order is stable between calls
+    pub fn entries(&self) -> Vec<(Value, Value)> {
+        let mut entries: Vec<(Value, Value)> = self
+            .array
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !matches!(value, Value::Nil))
+            .map(|(i, value)| (Value::Int(i as i64 + 1), value.clone()))
+            .collect();
+
+        let mut names: Vec<&String> = self.fields.keys().collect();
+        names.sort();
+        for name in names {
+            entries.push((Value::String(name.clone()), self.fields[name].clone()));
+        }
+        entries
+    }
+
+    /// Length of the array part up to its first nil (the "border"), so
+    /// holes left by assigning past the end are not counted
     pub fn len(&self) -> usize {
-        self.array.len()
+        self.array.iter().position(|value| matches!(value, Value::Nil)).unwrap_or(self.array.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of non-nil entries, in the array part and fields alike
+    pub fn count(&self) -> usize {
+        let present = |value: &&Value| !matches!(value, Value::Nil);
+        self.array.iter().filter(present).count() + self.fields.values().filter(present).count()
+    }
+}
+
+/// Tables compare structurally: two tables are equal when their array
+/// parts and named fields are equal element by element. Metatables do not
+/// participate, so a table with a metatable can equal a plain table.
+impl PartialEq for TableValue {
+    fn eq(&self, other: &Self) -> bool {
+        tables_equal(self, other, false, &mut Vec::new())
     }
 }
 
@@ -73,17 +147,30 @@ impl TableValue {
 #[derive(Debug, Clone)]
 pub struct FunctionValue {
     pub name: String,
-    pub params: Vec<String>,
-    pub body: Vec<crate::parser::Stmt>,
+    pub params: Vec<crate::lexer::Symbol>,
+    pub body: Arc<Vec<crate::parser::Stmt>>,
     pub is_async: bool,
+    /// Where the function was defined, shown in backtraces
+    pub location: crate::error::SourceLocation,
 }
 
 /// Native function value (built-in functions)
 #[derive(Clone)]
 pub struct NativeFunctionValue {
-    pub name: String,
+    pub name: &'static str,
+    /// Exact argument count, or the minimum count when `variadic`
     pub arity: usize,
-    pub func: fn(&[Value]) -> Result<Value, String>,
+    pub variadic: bool,
+    pub func: NativeFn,
+}
+
+/// Implementation of a native function
+#[derive(Clone, Copy)]
+pub enum NativeFn {
+    /// Depends only on its arguments
+    Pure(fn(&[Value]) -> Result<Value, String>),
+    /// Needs access to interpreter state (output stream, etc.)
+    Interpreter(fn(&mut Interpreter, &[Value]) -> Result<Value, String>),
 }
 
 impl fmt::Debug for NativeFunctionValue {
@@ -93,6 +180,43 @@ impl fmt::Debug for NativeFunctionValue {
 }
 
 impl Value {
+    /// Wrap a table in a fresh shared reference
+    pub fn table(table: TableValue) -> Self {
+        Value::Table(Arc::new(Mutex::new(table)))
+    }
+
+    /// Copy tables (including those inside tuples) recursively into fresh,
+    /// independent tables; other values are cloned as-is. Shared and cyclic references are reproduced in the copy.
+    pub fn deep_copy(&self) -> Value {
+        copy_value(self, &mut HashMap::new())
+    }
+
+    /// Render the value with every nested table written out, one entry per
+    /// line and indented by depth, e.g. `{ name = "lux", tags = { ... } }`
+    /// spread over several lines
+    ///
+    /// `Display` stays cheap and prints `{...}` for tables with fields; this
+    /// backs the `dump` builtin instead. It's [`Value::inspect`] except that
+    /// a string on its own is written as is, the way `print` shows it.
+    pub fn display(&self) -> String {
+        match self {
+            Value::String(s) => s.clone(),
+            other => other.inspect(),
+        }
+    }
+
+    /// The debugging representation behind the `inspect` builtin
+    ///
+    /// Strings are quoted and functions are shown as `<fn name>`. Tables with
+    /// only an array part are written as `[ ... ]` and other tables as
+    /// `{ ... }`, with nested tables indented on their own lines. A table met
+    /// again while rendering its own contents is shown as `<cycle>`.
+    pub fn inspect(&self) -> String {
+        let mut out = String::new();
+        render_value(self, 0, &mut Vec::new(), &mut out);
+        out
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Nil => false,
@@ -112,57 +236,331 @@ impl Value {
             Value::Function(_) => "function",
             Value::NativeFunction(_) => "function",
             Value::Pointer(_) => "pointer",
+            Value::Tuple(_) => "tuple",
+            Value::WeakRef(_) => "weakref",
         }
     }
 }
 
+/// A table met again while printing its own contents is shown as `<cycle>`
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Int(n) => write!(f, "{}", n),
-            Value::Float(fl) => write!(f, "{}", fl),
-            Value::String(s) => write!(f, "{}", s),
-            Value::Bool(b) => write!(f, "{}", b),
-            Value::Nil => write!(f, "nil"),
-            Value::Table(t) => {
-                if t.array.is_empty() && t.fields.is_empty() {
-                    write!(f, "{{}}")
-                } else if t.fields.is_empty() {
-                    write!(f, "[")?;
-                    for (i, v) in t.array.iter().enumerate() {
-                        if i > 0 {
-                            write!(f, ", ")?;
-                        }
-                        write!(f, "{}", v)?;
+        write_value(self, f, &mut Vec::new())
+    }
+}
+
+/// Write `value` as `print` shows it; `visiting` holds the tables whose
+/// contents are being written
+fn write_value(value: &Value, f: &mut fmt::Formatter<'_>, visiting: &mut Vec<usize>) -> fmt::Result {
+    match value {
+        Value::Int(n) => write!(f, "{}", n),
+        // Whole floats keep a decimal point so `3.0` doesn't print like the int `3`
+        Value::Float(fl) if fl.is_finite() && fl.fract() == 0.0 => write!(f, "{:.1}", fl),
+        Value::Float(fl) => write!(f, "{}", fl),
+        Value::String(s) => write!(f, "{}", s),
+        Value::Bool(b) => write!(f, "{}", b),
+        Value::Nil => write!(f, "nil"),
+        Value::Table(table) => {
+            let key = Arc::as_ptr(table) as usize;
+            if visiting.contains(&key) {
+                return write!(f, "<cycle>");
+            }
+            let t = table.lock().unwrap();
+            if t.array.is_empty() && t.fields.is_empty() {
+                write!(f, "{{}}")
+            } else if t.fields.is_empty() {
+                visiting.push(key);
+                write!(f, "[")?;
+                for (i, v) in t.array.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
                     }
-                    write!(f, "]")
-                } else {
-                    write!(f, "{{...}}")
+                    write_value(v, f, visiting)?;
                 }
+                visiting.pop();
+                write!(f, "]")
+            } else {
+                write!(f, "{{...}}")
             }
-            Value::Function(func) => write!(f, "<fn {}>", func.name),
-            Value::NativeFunction(func) => write!(f, "<native fn {}>", func.name),
-            Value::Pointer(ptr) => {
-                if let Ok(guard) = ptr.lock() {
-                    write!(f, "<pointer to {}>", guard.type_name())
-                } else {
-                    write!(f, "<pointer (locked)>")
+        }
+        Value::Function(func) => write!(f, "<fn {}>", func.name),
+        Value::NativeFunction(func) => write!(f, "<native fn {}>", func.name),
+        Value::Pointer(ptr) => {
+            if let Ok(guard) = ptr.lock() {
+                write!(f, "<pointer to {}>", guard.type_name())
+            } else {
+                write!(f, "<pointer (locked)>")
+            }
+        }
+        Value::Tuple(elements) => {
+            write!(f, "(")?;
+            for (i, v) in elements.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
                 }
+                write_value(v, f, visiting)?;
+            }
+            write!(f, ")")
+        }
+        Value::WeakRef(weak) => {
+            if weak.strong_count() > 0 {
+                write!(f, "<weakref to table>")
+            } else {
+                write!(f, "<weakref (collected)>")
             }
         }
     }
 }
 
+/// Unlike Lux's `==` (see [`Value::equals`]), an int never equals a float
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Value::Int(a), Value::Int(b)) => a == b,
-            (Value::Float(a), Value::Float(b)) => a == b,
-            (Value::String(a), Value::String(b)) => a == b,
-            (Value::Bool(a), Value::Bool(b)) => a == b,
-            (Value::Nil, Value::Nil) => true,
-            _ => false,
+        values_equal(self, other, false, &mut Vec::new())
+    }
+}
+
+impl Value {
+    /// Equality as Lux's `==` sees it: like `PartialEq`, but an int equals
+    /// a float with the same numeric value, also inside tables and tuples
+    pub fn equals(&self, other: &Value) -> bool {
+        values_equal(self, other, true, &mut Vec::new())
+    }
+}
+
+/// Equality that remembers which pairs of tables are already being compared,
+/// so cyclic tables terminate: a pair met again is assumed equal. With
+/// `mixed_numbers`, ints and floats compare by value.
+fn values_equal(a: &Value, b: &Value, mixed_numbers: bool, in_progress: &mut Vec<(usize, usize)>) -> bool {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        (Value::Int(a), Value::Float(b)) | (Value::Float(b), Value::Int(a)) if mixed_numbers => *a as f64 == *b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Nil, Value::Nil) => true,
+        (Value::Table(a), Value::Table(b)) => {
+            if Arc::ptr_eq(a, b) {
+                return true;
+            }
+            let pair = (Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize);
+            if in_progress.contains(&pair) {
+                return true;
+            }
+            // Compare snapshots so no lock is held while recursing into nested tables
+            let left = a.lock().unwrap().clone();
+            let right = b.lock().unwrap().clone();
+            in_progress.push(pair);
+            let equal = tables_equal(&left, &right, mixed_numbers, in_progress);
+            in_progress.pop();
+            equal
+        }
+        (Value::Tuple(a), Value::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| values_equal(x, y, mixed_numbers, in_progress))
+        }
+        (Value::WeakRef(a), Value::WeakRef(b)) => Weak::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+fn tables_equal(a: &TableValue, b: &TableValue, mixed_numbers: bool, in_progress: &mut Vec<(usize, usize)>) -> bool {
+    a.array.len() == b.array.len()
+        && a.fields.len() == b.fields.len()
+        && a.array.iter().zip(&b.array).all(|(x, y)| values_equal(x, y, mixed_numbers, in_progress))
+        && a.fields
+            .iter()
+            .all(|(key, x)| b.fields.get(key).is_some_and(|y| values_equal(x, y, mixed_numbers, in_progress)))
+}
+
+/// Append the [`Value::inspect`] rendering of `value` at nesting `depth`;
+/// `visiting` holds the tables whose contents are being rendered
+fn render_value(value: &Value, depth: usize, visiting: &mut Vec<usize>, out: &mut String) {
+    match value {
+        Value::Table(table) => render_table(table, depth, visiting, out),
+        Value::Tuple(elements) => {
+            out.push('(');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                render_value(element, depth, visiting, out);
+            }
+            out.push(')');
+        }
+        Value::String(s) => out.push_str(&format!("{:?}", s)),
+        Value::Function(func) if func.name == "<anonymous>" => out.push_str("<fn>"),
+        Value::NativeFunction(func) => out.push_str(&format!("<fn {}>", func.name)),
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+fn render_table(table: &TableRef, depth: usize, visiting: &mut Vec<usize>, out: &mut String) {
+    let key = Arc::as_ptr(table) as usize;
+    if visiting.contains(&key) {
+        out.push_str("<cycle>");
+        return;
+    }
+    // Copy the entries out so no lock is held while rendering nested tables
+    let (entries, is_array) = {
+        let table = table.lock().unwrap();
+        (table.entries(), table.fields.is_empty() && !table.array.is_empty())
+    };
+    let (open, close) = if is_array { ('[', ']') } else { ('{', '}') };
+    if entries.is_empty() {
+        out.push(open);
+        out.push(close);
+        return;
+    }
+
+    visiting.push(key);
+    let indent = "  ".repeat(depth + 1);
+    out.push(open);
+    out.push('\n');
+    for (i, (key, value)) in entries.iter().enumerate() {
+        out.push_str(&indent);
+        if let Value::String(name) = key {
+            out.push_str(&render_key(name));
+            out.push_str(" = ");
         }
+        render_value(value, depth + 1, visiting, out);
+        out.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+    }
+    out.push_str(&"  ".repeat(depth));
+    out.push(close);
+    visiting.pop();
+}
+
+/// A field name as written in a table constructor: bare when it's an
+/// identifier, otherwise as a bracketed string
+fn render_key(name: &str) -> String {
+    let mut chars = name.chars();
+    let is_identifier = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_');
+    if is_identifier {
+        name.to_string()
+    } else {
+        format!("[{:?}]", name)
     }
 }
 
+/// Deep-copy a value, descending into tables and tuples
+fn copy_value(value: &Value, copies: &mut HashMap<usize, TableRef>) -> Value {
+    match value {
+        Value::Table(inner) => Value::Table(copy_table(inner, copies)),
+        Value::Tuple(elements) => Value::Tuple(elements.iter().map(|v| copy_value(v, copies)).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Deep-copy a table, reusing the copy of any table already visited
+fn copy_table(table: &TableRef, copies: &mut HashMap<usize, TableRef>) -> TableRef {
+    let key = Arc::as_ptr(table) as usize;
+    if let Some(copy) = copies.get(&key) {
+        return copy.clone();
+    }
+
+    // Register the (still empty) copy first so self-references resolve to it
+    let copy: TableRef = Arc::new(Mutex::new(TableValue::new()));
+    copies.insert(key, copy.clone());
+
+    let source = table.lock().unwrap().clone();
+    let array = source.array.iter().map(|v| copy_value(v, copies)).collect();
+    let fields = source
+        .fields
+        .iter()
+        .map(|(k, v)| (k.clone(), copy_value(v, copies)))
+        .collect();
+    let metatable = source.metatable.as_ref().map(|meta| copy_table(meta, copies));
+
+    *copy.lock().unwrap() = TableValue { fields, array, metatable };
+    copy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn array_table(values: Vec<Value>) -> Value {
+        let mut table = TableValue::new();
+        table.array = values;
+        Value::table(table)
+    }
+
+    #[test]
+    fn test_table_structural_equality() {
+        let a = array_table(vec![Value::Int(1), Value::Int(2)]);
+        let b = array_table(vec![Value::Int(1), Value::Int(2)]);
+        let c = array_table(vec![Value::Int(2), Value::Int(1)]);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, array_table(vec![Value::Int(1)]));
+    }
+
+    #[test]
+    fn test_nested_table_equality() {
+        let mut left = TableValue::new();
+        left.set(Value::String("inner".to_string()), array_table(vec![Value::Int(1)])).unwrap();
+        let mut right = left.clone();
+        assert_eq!(Value::table(left.clone()), Value::table(right.clone()));
+
+        right.set(Value::String("inner".to_string()), array_table(vec![Value::Int(2)])).unwrap();
+        assert_ne!(Value::table(left), Value::table(right));
+    }
+
+    #[test]
+    fn test_cyclic_tables_compare_without_hanging() {
+        let cyclic = || {
+            let table = Value::table(TableValue::new());
+            if let Value::Table(inner) = &table {
+                inner.lock().unwrap().set(Value::String("self".to_string()), table.clone()).unwrap();
+            }
+            table
+        };
+        assert_eq!(cyclic(), cyclic());
+        assert_eq!(cyclic().deep_copy(), cyclic());
+    }
+
+    #[test]
+    fn test_display_renders_nested_tables() {
+        let mut inner = TableValue::new();
+        inner.set(Value::String("debug".to_string()), Value::Bool(true)).unwrap();
+        inner.set(Value::String("max size".to_string()), Value::Int(10)).unwrap();
+        let mut outer = TableValue::new();
+        outer.array = vec![Value::String("1".to_string()), Value::Int(2)];
+        outer.set(Value::String("name".to_string()), Value::String("lux".to_string())).unwrap();
+        outer.set(Value::String("options".to_string()), Value::table(inner)).unwrap();
+        outer.set(Value::String("empty".to_string()), Value::table(TableValue::new())).unwrap();
+
+        let expected = "{\n  \"1\",\n  2,\n  empty = {},\n  name = \"lux\",\n  options = {\n    debug = true,\n    [\"max size\"] = 10\n  }\n}";
+        assert_eq!(Value::table(outer).display(), expected);
+        // Scalars render as they print
+        assert_eq!(Value::String("lux".to_string()).display(), "lux");
+        assert_eq!(Value::Float(1.5).display(), "1.5");
+    }
+
+    #[test]
+    fn test_print_marks_cycles() {
+        let table = array_table(vec![Value::Int(1)]);
+        if let Value::Table(inner) = &table {
+            inner.lock().unwrap().set(Value::Int(1), table.clone()).unwrap();
+        }
+        assert_eq!(table.to_string(), "[<cycle>]");
+
+        let shared = array_table(vec![Value::Int(7)]);
+        let pair = Value::Tuple(vec![shared.clone(), shared]);
+        assert_eq!(pair.to_string(), "([7], [7])");
+    }
+
+    #[test]
+    fn test_display_marks_cycles() {
+        let table = array_table(vec![Value::Int(1)]);
+        if let Value::Table(inner) = &table {
+            inner.lock().unwrap().set(Value::String("me".to_string()), table.clone()).unwrap();
+        }
+        assert_eq!(table.display(), "{\n  1,\n  me = <cycle>\n}");
+
+        // A table shared by two fields isn't a cycle, so it's written out twice
+        let shared = array_table(vec![Value::Int(7)]);
+        let pair = array_table(vec![shared.clone(), shared]);
+        assert_eq!(pair.display(), "[\n  [\n    7\n  ],\n  [\n    7\n  ]\n]");
+    }
+}
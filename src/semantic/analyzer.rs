@@ -0,0 +1,322 @@
+//! Semantic analyzer implementation
+//!
+//! This module walks a type-checked AST and reports warnings through a
+//! [`Diagnostics`] collector. It never rejects a program.
+
+use crate::error::{Diagnostics, SourceLocation};
+use crate::lexer::Symbol;
+use crate::parser::ast::{Ast, Expr, Stmt, TableKey, Type};
+
+/// A local variable declared in the scope being analyzed
+struct Local {
+    name: String,
+    location: SourceLocation,
+    used: bool,
+}
+
+/// Reports unused variables, unreachable code and other likely mistakes as warnings
+pub struct SemanticAnalyzer<'a> {
+    diagnostics: &'a mut Diagnostics,
+    scopes: Vec<Vec<Local>>,
+}
+
+impl<'a> SemanticAnalyzer<'a> {
+    pub fn new(diagnostics: &'a mut Diagnostics) -> Self {
+        Self {
+            diagnostics,
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Analyze an entire AST
+    ///
+    /// Top-level variables are globals and may be used from other modules,
+    /// so only variables declared inside functions and blocks are reported
+    /// when unused.
+    pub fn analyze(&mut self, ast: &Ast) {
+        self.scopes.push(Vec::new());
+        self.check_block(&ast.statements);
+        self.scopes.pop();
+    }
+
+    /// Check a list of statements, returning whether it always leaves the
+    /// enclosing block (via `return`, `break` or `continue`)
+    ///
+    /// Only the first statement after such an exit is reported, but the rest
+    /// are still analyzed.
+    fn check_block(&mut self, stmts: &[Stmt]) -> bool {
+        let mut exits = false;
+        let mut reported = false;
+        for stmt in stmts {
+            if exits && !reported {
+                // An expression statement is located at its operator (e.g. the
+                // call's parenthesis), so point at the start of the expression
+                let location = match stmt {
+                    Stmt::Expression { expr, .. } => expr.span().start,
+                    other => other.location().clone(),
+                };
+                self.diagnostics.warn("Unreachable code", location);
+                reported = true;
+            }
+            exits |= self.check_stmt(stmt);
+        }
+        exits
+    }
+
+    /// Check `stmts` in a new scope, reporting its unused variables
+    fn check_scoped(&mut self, stmts: &[Stmt]) -> bool {
+        self.push_scope();
+        let exits = self.check_block(stmts);
+        self.pop_scope();
+        exits
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        for local in self.scopes.pop().unwrap_or_default() {
+            if !local.used {
+                self.diagnostics
+                    .warn(format!("Unused variable '{}'", local.name), local.location);
+            }
+        }
+    }
+
+    /// Declare a variable in the current scope. Names starting with `_`
+    /// are treated as deliberately unused.
+    fn declare(&mut self, name: &str, location: &SourceLocation, used: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Local {
+                name: name.to_string(),
+                location: location.clone(),
+                used: used || name.starts_with('_'),
+            });
+        }
+    }
+
+    fn mark_used(&mut self, name: &str) {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(local) = scope.iter_mut().rev().find(|local| local.name == name) {
+                local.used = true;
+                return;
+            }
+        }
+    }
+
+    fn check_function(&mut self, params: &[(Symbol, Type)], body: &[Stmt], location: &SourceLocation) {
+        self.push_scope();
+        for (param, _) in params {
+            // Parameters are part of the function's signature, so they
+            // aren't reported when unused
+            self.declare(param, location, true);
+        }
+        self.check_block(body);
+        self.pop_scope();
+    }
+
+    /// Check a statement, returning whether it always exits the enclosing block
+    fn check_stmt(&mut self, stmt: &Stmt) -> bool {
+        match stmt {
+            Stmt::VarDecl { name, initializer, location, .. } => {
+                if let Some(init) = initializer {
+                    self.check_expr(init);
+                }
+                // Globals can't be reported as unused
+                let is_global = self.scopes.len() == 1;
+                self.declare(name, location, is_global);
+                false
+            }
+
+            Stmt::Destructure { names, initializer, location, .. } => {
+                self.check_expr(initializer);
+                let is_global = self.scopes.len() == 1;
+                for name in names {
+                    self.declare(name, location, is_global);
+                }
+                false
+            }
+
+            Stmt::FunctionDecl { name, params, body, location, .. } => {
+                self.declare(name, location, true);
+                self.check_function(params, body, location);
+                false
+            }
+
+            Stmt::Expression { expr, .. } => {
+                self.check_expr(expr);
+                false
+            }
+
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                self.check_expr(condition);
+                let then_exits = self.check_scoped(then_branch);
+                // Without an else branch the condition may be false, so the
+                // code after the if stays reachable
+                match else_branch {
+                    Some(else_stmts) => self.check_scoped(else_stmts) && then_exits,
+                    None => false,
+                }
+            }
+
+            // A `break` or `continue` inside a loop body only leaves the loop
+            Stmt::While { condition, body, .. } => {
+                self.check_expr(condition);
+                self.check_scoped(body);
+                false
+            }
+
+            Stmt::For { initializer, condition, increment, body, .. } => {
+                self.push_scope();
+                if let Some(init) = initializer {
+                    self.check_stmt(init);
+                }
+                if let Some(cond) = condition {
+                    self.check_expr(cond);
+                }
+                if let Some(inc) = increment {
+                    self.check_expr(inc);
+                }
+                self.check_block(body);
+                self.pop_scope();
+                false
+            }
+
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.check_expr(value);
+                }
+                true
+            }
+
+            Stmt::Break { .. } | Stmt::Continue { .. } => true,
+
+            Stmt::Defer { expr, .. } => {
+                self.check_expr(expr);
+                false
+            }
+
+            Stmt::Block { statements, .. } => self.check_scoped(statements),
+
+            Stmt::EnumDecl { name, location, .. } => {
+                // Like functions, enums are never reported as unused
+                self.declare(name, location, true);
+                false
+            }
+
+            Stmt::Import { .. } | Stmt::TypeAlias { .. } | Stmt::StructDecl { .. } => false,
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal { .. } => {}
+            Expr::Variable { name, .. } => self.mark_used(name),
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } => {
+                self.check_expr(left);
+                self.check_expr(right);
+            }
+            Expr::Unary { operand, .. } => self.check_expr(operand),
+            Expr::Assign { target, value, .. } => {
+                // Assigning to a variable doesn't read it
+                if !matches!(target.as_ref(), Expr::Variable { .. }) {
+                    self.check_expr(target);
+                }
+                self.check_expr(value);
+            }
+            Expr::Call { callee, arguments, .. } => {
+                self.check_expr(callee);
+                for arg in arguments {
+                    self.check_expr(arg);
+                }
+            }
+            Expr::MethodCall { receiver, arguments, .. } => {
+                self.check_expr(receiver);
+                for arg in arguments {
+                    self.check_expr(arg);
+                }
+            }
+            Expr::Table { fields, .. } | Expr::StructInit { fields, .. } => {
+                for (key, value) in fields {
+                    if let TableKey::Expression(key_expr) = key {
+                        self.check_expr(key_expr);
+                    }
+                    self.check_expr(value);
+                }
+            }
+            Expr::Tuple { elements, .. } => {
+                for element in elements {
+                    self.check_expr(element);
+                }
+            }
+            Expr::TableAccess { table, key, .. } => {
+                self.check_expr(table);
+                self.check_expr(key);
+            }
+            Expr::Function { params, body, location, .. } => self.check_function(params, body, location),
+            Expr::DoBlock { body, .. } => {
+                self.check_scoped(body);
+            }
+            Expr::Spawn { call, .. } => self.check_expr(call),
+            Expr::Await { task, .. } => self.check_expr(task),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Parser};
+
+    fn warnings(source: &str) -> Vec<String> {
+        let tokens = Lexer::new(source, None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+        let mut diagnostics = Diagnostics::new();
+        SemanticAnalyzer::new(&mut diagnostics).analyze(&ast);
+        diagnostics.iter().map(|d| d.summary()).collect()
+    }
+
+    #[test]
+    fn test_unused_local_warns() {
+        assert_eq!(
+            warnings("fn f() {\n    local unused := 1\n}"),
+            vec!["Warning: Unused variable 'unused' at 2:5"]
+        );
+    }
+
+    #[test]
+    fn test_used_locals_do_not_warn() {
+        assert!(warnings("local top := 1").is_empty());
+        assert!(warnings("fn f(x) { local y := x\nreturn y }").is_empty());
+        assert!(warnings("fn f() { local n := 1\nlocal g := fn() { return n }\nreturn g }").is_empty());
+        assert!(warnings("fn f() { local _ignored := 1 }").is_empty());
+    }
+
+    #[test]
+    fn test_statement_after_return_is_unreachable() {
+        assert_eq!(
+            warnings("fn f() -> int {\n    return 1\n    print(\"never\")\n    print(\"also never\")\n}"),
+            vec!["Warning: Unreachable code at 3:5"]
+        );
+        assert_eq!(
+            warnings("while true {\n    break\n    print(1)\n}"),
+            vec!["Warning: Unreachable code at 3:5"]
+        );
+        assert_eq!(
+            warnings("fn f(x) -> int {\n    if x { return 1 } else { return 2 }\n    return 3\n}"),
+            vec!["Warning: Unreachable code at 3:5"]
+        );
+    }
+
+    #[test]
+    fn test_statement_after_conditional_return_is_reachable() {
+        assert!(warnings("fn f(x) -> int {\n    if x { return 1 }\n    return 2\n}").is_empty());
+        assert!(warnings("fn f() -> int {\n    while true { break }\n    return 2\n}").is_empty());
+    }
+
+    #[test]
+    fn test_assignment_is_not_a_use() {
+        assert_eq!(warnings("fn f() { local x := 1\nx = 2 }").len(), 1);
+    }
+}
@@ -0,0 +1,8 @@
+//! Semantic analysis module
+//!
+//! This module runs checks that go beyond types, reporting suspicious but
+//! valid code as warnings.
+
+pub mod analyzer;
+
+pub use analyzer::SemanticAnalyzer;
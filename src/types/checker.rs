@@ -2,14 +2,16 @@
 //!
 //! This module implements type checking for Lux.
 
-use std::collections::HashMap;
-use crate::error::{LuxError, LuxResult};
-use crate::parser::ast::{Ast, Stmt, Expr, Type, BinaryOp, UnaryOp, Literal};
+use std::collections::{HashMap, HashSet};
+use crate::error::{LuxError, LuxResult, SourceLocation};
+use crate::lexer::Symbol;
+use crate::module::{search, ImportStack, ModuleCache, SearchPath};
+use crate::parser::ast::{Ast, Stmt, Expr, Type, BinaryOp, UnaryOp, LogicalOp, Literal, TableKey, ImportKind};
 
 /// Type environment for tracking variable types
 #[derive(Debug, Clone)]
 struct TypeEnvironment {
-    scopes: Vec<HashMap<String, Type>>,
+    scopes: Vec<HashMap<Symbol, Type>>,
 }
 
 impl TypeEnvironment {
@@ -29,15 +31,24 @@ impl TypeEnvironment {
         }
     }
 
-    fn define(&mut self, name: String, typ: Type) {
+    /// Pop the innermost scope, returning its bindings
+    fn take_scope(&mut self) -> HashMap<Symbol, Type> {
+        if self.scopes.len() > 1 {
+            self.scopes.pop().unwrap_or_default()
+        } else {
+            HashMap::new()
+        }
+    }
+
+    fn define(&mut self, name: impl Into<Symbol>, typ: Type) {
         if let Some(scope) = self.scopes.last_mut() {
-            scope.insert(name, typ);
+            scope.insert(name.into(), typ);
         }
     }
 
-    fn get(&self, name: &str) -> Option<Type> {
+    fn get(&self, name: Symbol) -> Option<Type> {
         for scope in self.scopes.iter().rev() {
-            if let Some(typ) = scope.get(name) {
+            if let Some(typ) = scope.get(&name) {
                 return Some(typ.clone());
             }
         }
@@ -50,6 +61,28 @@ pub struct TypeChecker {
     env: TypeEnvironment,
     current_function_return_type: Option<Type>,
     loaded_modules: HashMap<String, bool>,
+    /// Parsed modules, shared with the interpreter
+    modules: ModuleCache,
+    /// Modules whose checking is in progress
+    imports: ImportStack,
+    /// Where imported modules are looked up after `current_file_dir`
+    search_path: SearchPath,
+    /// Directory of the file being checked
+    current_file_dir: Option<String>,
+    /// Top-level bindings of modules checked in a scope of their own, by resolved path
+    module_scopes: HashMap<String, HashMap<Symbol, Type>>,
+    /// Declared type aliases, stored fully resolved
+    aliases: HashMap<String, Type>,
+    /// Names of declared structs; their shapes live in `aliases`
+    structs: HashSet<String>,
+    /// Require conditions and logical operands to be bool
+    strict: bool,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TypeChecker {
@@ -61,7 +94,34 @@ impl TypeChecker {
         env.define(
             "print".to_string(),
             Type::Function {
-                params: vec![Type::Nil], // Accept any type (we use Nil as placeholder)
+                params: vec![Type::Any],
+                return_type: Box::new(Type::Nil),
+            },
+        );
+
+        // dump(value) -> nil
+        env.define(
+            "dump".to_string(),
+            Type::Function {
+                params: vec![Type::Any],
+                return_type: Box::new(Type::Nil),
+            },
+        );
+
+        // inspect(value: any) -> string
+        env.define(
+            "inspect".to_string(),
+            Type::Function {
+                params: vec![Type::Any],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // assert(cond, message?) -> nil
+        env.define(
+            "assert".to_string(),
+            Type::Function {
+                params: vec![Type::Any, Type::Variadic(Box::new(Type::Any))],
                 return_type: Box::new(Type::Nil),
             },
         );
@@ -80,7 +140,7 @@ impl TypeChecker {
             "getmetatable".to_string(),
             Type::Function {
                 params: vec![Type::Table],
-                return_type: Box::new(Type::Nil), // Can return table or nil
+                return_type: Box::new(Type::Any), // Can return table or nil
             },
         );
 
@@ -102,6 +162,15 @@ impl TypeChecker {
             },
         );
 
+        // append_file(path: string, content: string) -> nil
+        env.define(
+            "append_file".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::String],
+                return_type: Box::new(Type::Nil),
+            },
+        );
+
         // string_split(text: string, delimiter: string) -> table
         env.define(
             "string_split".to_string(),
@@ -111,6 +180,24 @@ impl TypeChecker {
             },
         );
 
+        // split_lines(text: string) -> table
+        env.define(
+            "split_lines".to_string(),
+            Type::Function {
+                params: vec![Type::String],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // join_lines(lines: table) -> string
+        env.define(
+            "join_lines".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
+                return_type: Box::new(Type::String),
+            },
+        );
+
         // string_contains(text: string, pattern: string) -> bool
         env.define(
             "string_contains".to_string(),
@@ -120,6 +207,294 @@ impl TypeChecker {
             },
         );
 
+        // string_find(text: string, needle: string, start?: int) -> int
+        env.define(
+            "string_find".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::String, Type::Variadic(Box::new(Type::Int))],
+                return_type: Box::new(Type::Int),
+            },
+        );
+
+        // char_at(text: string, index: int) -> string
+        env.define(
+            "char_at".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::Int],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // string_chars(text: string) -> table
+        env.define(
+            "string_chars".to_string(),
+            Type::Function {
+                params: vec![Type::String],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // ord(text: string) -> int
+        env.define(
+            "ord".to_string(),
+            Type::Function {
+                params: vec![Type::String],
+                return_type: Box::new(Type::Int),
+            },
+        );
+
+        // chr(code: int) -> string
+        env.define(
+            "chr".to_string(),
+            Type::Function {
+                params: vec![Type::Int],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // pairs(t: table) -> table
+        env.define(
+            "pairs".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // ipairs(t: table) -> table
+        env.define(
+            "ipairs".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // next(t: table, key: any) -> table | nil
+        env.define(
+            "next".to_string(),
+            Type::Function {
+                params: vec![Type::Table, Type::Any],
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // keys(t: table) -> table
+        env.define(
+            "keys".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // values(t: table) -> table
+        env.define(
+            "values".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // deep_copy(value: any) -> any
+        env.define(
+            "deep_copy".to_string(),
+            Type::Function {
+                params: vec![Type::Any],
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // clamp(x: number, lo: number, hi: number) -> number
+        env.define(
+            "clamp".to_string(),
+            Type::Function {
+                params: vec![Type::Any, Type::Any, Type::Any], // any numbers
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // min_of(t: table) / max_of(t: table) -> number
+        for name in ["min_of", "max_of"] {
+            env.define(
+                name.to_string(),
+                Type::Function {
+                    params: vec![Type::Table],
+                    return_type: Box::new(Type::Any), // int or float
+                },
+            );
+        }
+
+        // is_int(value: any) -> bool, and likewise for the other value types
+        for name in ["is_int", "is_float", "is_string", "is_bool", "is_table", "is_nil", "is_function"] {
+            env.define(
+                name.to_string(),
+                Type::Function {
+                    params: vec![Type::Any],
+                    return_type: Box::new(Type::Bool),
+                },
+            );
+        }
+
+        // weak_ref(table) -> weakref
+        env.define(
+            "weak_ref".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // weak_get(ref: weakref) -> table | nil
+        env.define(
+            "weak_get".to_string(),
+            Type::Function {
+                params: vec![Type::Any],
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // random() -> float
+        env.define(
+            "random".to_string(),
+            Type::Function {
+                params: vec![],
+                return_type: Box::new(Type::Float),
+            },
+        );
+
+        // random_int(min: int, max: int) -> int
+        env.define(
+            "random_int".to_string(),
+            Type::Function {
+                params: vec![Type::Int, Type::Int],
+                return_type: Box::new(Type::Int),
+            },
+        );
+
+        // seed(n: int) -> nil
+        env.define(
+            "seed".to_string(),
+            Type::Function {
+                params: vec![Type::Int],
+                return_type: Box::new(Type::Nil),
+            },
+        );
+
+        // now_millis() -> int
+        env.define(
+            "now_millis".to_string(),
+            Type::Function {
+                params: vec![],
+                return_type: Box::new(Type::Int),
+            },
+        );
+
+        // clock() -> float
+        env.define(
+            "clock".to_string(),
+            Type::Function {
+                params: vec![],
+                return_type: Box::new(Type::Float),
+            },
+        );
+
+        // sleep(ms: int) -> nil
+        env.define(
+            "sleep".to_string(),
+            Type::Function {
+                params: vec![Type::Int],
+                return_type: Box::new(Type::Nil),
+            },
+        );
+
+        // json_parse(text: string) -> any
+        env.define(
+            "json_parse".to_string(),
+            Type::Function {
+                params: vec![Type::String],
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // json_stringify(value: any) -> string
+        env.define(
+            "json_stringify".to_string(),
+            Type::Function {
+                params: vec![Type::Any],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // Bytes are array tables of ints 0-255; the encoders also take a string
+        for name in ["to_hex", "base64_encode"] {
+            env.define(
+                name.to_string(),
+                Type::Function {
+                    params: vec![Type::Any], // string or bytes
+                    return_type: Box::new(Type::String),
+                },
+            );
+        }
+        for name in ["from_hex", "base64_decode"] {
+            env.define(
+                name.to_string(),
+                Type::Function {
+                    params: vec![Type::String],
+                    return_type: Box::new(Type::Table),
+                },
+            );
+        }
+
+        // regex_match(text: string, pattern: string) -> bool
+        env.define(
+            "regex_match".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::String],
+                return_type: Box::new(Type::Bool),
+            },
+        );
+
+        // regex_find(text: string, pattern: string) -> string | nil
+        env.define(
+            "regex_find".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::String],
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // regex_replace(text: string, pattern: string, replacement: string) -> string
+        env.define(
+            "regex_replace".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::String, Type::String],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // get_env(name: string) -> string | nil
+        env.define(
+            "get_env".to_string(),
+            Type::Function {
+                params: vec![Type::String],
+                return_type: Box::new(Type::Any),
+            },
+        );
+
+        // args() -> table
+        env.define(
+            "args".to_string(),
+            Type::Function {
+                params: vec![],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // math module table (math.sin, math.pi, ...); fields are looked up dynamically
+        env.define("math".to_string(), Type::Table);
+
         // string_starts_with(text: string, prefix: string) -> bool
         env.define(
             "string_starts_with".to_string(),
@@ -138,6 +513,27 @@ impl TypeChecker {
             },
         );
 
+        // string_trim_start(text: string) -> string
+        // string_trim_end(text: string) -> string
+        for name in ["string_trim_start", "string_trim_end"] {
+            env.define(
+                name.to_string(),
+                Type::Function {
+                    params: vec![Type::String],
+                    return_type: Box::new(Type::String),
+                },
+            );
+        }
+
+        // string_trim_chars(text: string, chars: string) -> string
+        env.define(
+            "string_trim_chars".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::String],
+                return_type: Box::new(Type::String),
+            },
+        );
+
         // string_length(text: string) -> int
         env.define(
             "string_length".to_string(),
@@ -160,11 +556,65 @@ impl TypeChecker {
         env.define(
             "table_push".to_string(),
             Type::Function {
-                params: vec![Type::Table, Type::Nil], // Nil as placeholder for any type
+                params: vec![Type::Table, Type::Any],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // count(table: table) -> int
+        env.define(
+            "count".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
+                return_type: Box::new(Type::Int),
+            },
+        );
+
+        // table_concat(table: table, separator: string) -> string
+        env.define(
+            "table_concat".to_string(),
+            Type::Function {
+                params: vec![Type::Table, Type::String],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // table_slice(table: table, start: int, end: int) -> table
+        env.define(
+            "table_slice".to_string(),
+            Type::Function {
+                params: vec![Type::Table, Type::Int, Type::Int],
+                return_type: Box::new(Type::Table),
+            },
+        );
+
+        // table_reverse(table: table) -> table
+        env.define(
+            "table_reverse".to_string(),
+            Type::Function {
+                params: vec![Type::Table],
                 return_type: Box::new(Type::Table),
             },
         );
 
+        // table_contains(table: table, value: any) -> bool
+        env.define(
+            "table_contains".to_string(),
+            Type::Function {
+                params: vec![Type::Table, Type::Any],
+                return_type: Box::new(Type::Bool),
+            },
+        );
+
+        // index_of(table: table, value: any) -> int
+        env.define(
+            "index_of".to_string(),
+            Type::Function {
+                params: vec![Type::Table, Type::Any],
+                return_type: Box::new(Type::Int),
+            },
+        );
+
         // parse_lux(source: string) -> table
         env.define(
             "parse_lux".to_string(),
@@ -178,7 +628,7 @@ impl TypeChecker {
         env.define(
             "type_of".to_string(),
             Type::Function {
-                params: vec![Type::Nil], // any type
+                params: vec![Type::Any], // any type
                 return_type: Box::new(Type::String),
             },
         );
@@ -187,16 +637,16 @@ impl TypeChecker {
         env.define(
             "to_string".to_string(),
             Type::Function {
-                params: vec![Type::Nil], // any type
+                params: vec![Type::Any], // any type
                 return_type: Box::new(Type::String),
             },
         );
 
-        // to_int(value: any) -> int
+        // to_int(value: any, base?: int) -> int
         env.define(
             "to_int".to_string(),
             Type::Function {
-                params: vec![Type::Nil], // any type
+                params: vec![Type::Any, Type::Variadic(Box::new(Type::Int))],
                 return_type: Box::new(Type::Int),
             },
         );
@@ -205,7 +655,7 @@ impl TypeChecker {
         env.define(
             "to_float".to_string(),
             Type::Function {
-                params: vec![Type::Nil], // any type
+                params: vec![Type::Any], // any type
                 return_type: Box::new(Type::Float),
             },
         );
@@ -255,6 +705,45 @@ impl TypeChecker {
             },
         );
 
+        // string_repeat(text: string, count: int) -> string
+        env.define(
+            "string_repeat".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::Int],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // pad_left(text: string, width: int, fill: string) -> string
+        // pad_right(text: string, width: int, fill: string) -> string
+        for name in ["pad_left", "pad_right"] {
+            env.define(
+                name.to_string(),
+                Type::Function {
+                    params: vec![Type::String, Type::Int, Type::String],
+                    return_type: Box::new(Type::String),
+                },
+            );
+        }
+
+        // string_format(fmt: string, ...args) -> string
+        env.define(
+            "string_format".to_string(),
+            Type::Function {
+                params: vec![Type::String, Type::Variadic(Box::new(Type::Any))],
+                return_type: Box::new(Type::String),
+            },
+        );
+
+        // format_float(x: number, mode: string, precision: int) -> string
+        env.define(
+            "format_float".to_string(),
+            Type::Function {
+                params: vec![Type::Any, Type::String, Type::Int],
+                return_type: Box::new(Type::String),
+            },
+        );
+
         // sqrt(x: float) -> float
         env.define(
             "sqrt".to_string(),
@@ -277,8 +766,8 @@ impl TypeChecker {
         env.define(
             "abs".to_string(),
             Type::Function {
-                params: vec![Type::Nil], // int or float
-                return_type: Box::new(Type::Nil),
+                params: vec![Type::Any], // int or float
+                return_type: Box::new(Type::Any),
             },
         );
 
@@ -300,12 +789,21 @@ impl TypeChecker {
             },
         );
 
+        // round(x: number, digits?: int) -> int without digits, float with them
+        env.define(
+            "round".to_string(),
+            Type::Function {
+                params: vec![Type::Any, Type::Variadic(Box::new(Type::Any))], // digits are checked at runtime
+                return_type: Box::new(Type::Any),
+            },
+        );
+
         // min(a: number, b: number) -> number
         env.define(
             "min".to_string(),
             Type::Function {
-                params: vec![Type::Nil, Type::Nil], // any numbers
-                return_type: Box::new(Type::Nil),
+                params: vec![Type::Any, Type::Any], // any numbers
+                return_type: Box::new(Type::Any),
             },
         );
 
@@ -313,8 +811,8 @@ impl TypeChecker {
         env.define(
             "max".to_string(),
             Type::Function {
-                params: vec![Type::Nil, Type::Nil], // any numbers
-                return_type: Box::new(Type::Nil),
+                params: vec![Type::Any, Type::Any], // any numbers
+                return_type: Box::new(Type::Any),
             },
         );
 
@@ -322,9 +820,46 @@ impl TypeChecker {
             env,
             current_function_return_type: None,
             loaded_modules: HashMap::new(),
+            modules: ModuleCache::new(),
+            imports: ImportStack::new(),
+            search_path: SearchPath::from_env(),
+            current_file_dir: None,
+            module_scopes: HashMap::new(),
+            aliases: HashMap::new(),
+            structs: HashSet::new(),
+            strict: false,
+        }
+    }
+
+    /// Create a checker that rejects non-bool conditions in `if`, `while`
+    /// and `for`, and non-bool operands of `and`/`or`
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            ..Self::new()
         }
     }
 
+    /// Parse imported modules through `modules`, so work done here is reused
+    /// by later phases that share the cache
+    pub fn with_modules(mut self, modules: ModuleCache) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Also look for imported modules in `dir`, after the directories
+    /// already on the search path
+    pub fn with_search_path(mut self, dir: impl Into<std::path::PathBuf>) -> Self {
+        self.search_path.push(dir);
+        self
+    }
+
+    /// Resolve imports relative to the directory containing `file` first
+    pub fn with_current_file(mut self, file: &str) -> Self {
+        self.current_file_dir = search::file_dir(file);
+        self
+    }
+
     fn import_module(&mut self, path: &str, location: &crate::error::SourceLocation) -> LuxResult<()> {
         // Check if already loaded
         if self.loaded_modules.contains_key(path) {
@@ -334,26 +869,20 @@ impl TypeChecker {
         // Resolve the module path
         let resolved_path = self.resolve_module_path(path, location)?;
 
-        // Read the file
-        let source = std::fs::read_to_string(&resolved_path)
-            .map_err(|e| LuxError::type_error(
-                format!("Failed to read module '{}': {}", path, e),
-                location.clone(),
-            ))?;
-
-        // Parse the module
-        use crate::lexer::Lexer;
-        use crate::parser::Parser;
+        // Read and parse the module, unless it is already cached
+        let ast = self.modules.load(&resolved_path, |e| {
+            LuxError::type_error(format!("Failed to read module '{}': {}", path, e), location.clone())
+        })?;
 
-        let mut lexer = Lexer::new(&source, Some(&resolved_path));
-        let tokens = lexer.tokenize()?;
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse()?;
+        // A module that is still being checked further up is importing itself
+        self.imports.enter(path).map_err(|cycle| {
+            LuxError::type_error(format!("Circular import: {}", cycle), location.clone())
+        })?;
 
         // Type-check the module in the current environment
-        for stmt in &ast.statements {
-            self.check_stmt(stmt)?;
-        }
+        let checked = ast.statements.iter().try_for_each(|stmt| self.check_stmt(stmt));
+        self.imports.exit();
+        checked?;
 
         // Mark as loaded
         self.loaded_modules.insert(path.to_string(), true);
@@ -361,57 +890,112 @@ impl TypeChecker {
         Ok(())
     }
 
-    fn resolve_module_path(&self, path: &str, location: &crate::error::SourceLocation) -> LuxResult<String> {
-        use std::path::Path;
-
-        // Try different locations:
-        // 1. In lib/ directory
-        let lib_path = Path::new("lib").join(format!("{}.lux", path));
-        if lib_path.exists() {
-            return Ok(lib_path.to_string_lossy().to_string());
+    /// Check a module in a scope of its own and return the types of its
+    /// top-level bindings
+    fn module_exports(&mut self, path: &str, location: &SourceLocation) -> LuxResult<HashMap<Symbol, Type>> {
+        let resolved_path = self.resolve_module_path(path, location)?;
+        if let Some(exports) = self.module_scopes.get(&resolved_path) {
+            return Ok(exports.clone());
         }
 
-        // 2. In tools/ directory
-        let tools_path = Path::new("tools").join(format!("{}.lux", path));
-        if tools_path.exists() {
-            return Ok(tools_path.to_string_lossy().to_string());
-        }
+        let ast = self.modules.load(&resolved_path, |e| {
+            LuxError::type_error(format!("Failed to read module '{}': {}", path, e), location.clone())
+        })?;
+        self.imports.enter(path).map_err(|cycle| {
+            LuxError::type_error(format!("Circular import: {}", cycle), location.clone())
+        })?;
+
+        self.env.push_scope();
+        let checked = ast.statements.iter().try_for_each(|stmt| self.check_stmt(stmt));
+        let exports = self.env.take_scope();
+        self.imports.exit();
+        checked?;
+
+        self.module_scopes.insert(resolved_path, exports.clone());
+        Ok(exports)
+    }
 
-        // 3. As absolute or relative path with .lux extension
-        let direct_path_str = format!("{}.lux", path);
-        let direct_path = Path::new(&direct_path_str);
-        if direct_path.exists() {
-            return Ok(direct_path.to_string_lossy().to_string());
+    fn resolve_module_path(&self, path: &str, location: &SourceLocation) -> LuxResult<String> {
+        let current_dir = self.current_file_dir.as_deref().map(std::path::Path::new);
+        match self.search_path.resolve(path, current_dir) {
+            Some(resolved) => Ok(resolved.to_string_lossy().to_string()),
+            None => Err(LuxError::type_error(
+                format!("Module '{}' not found", path),
+                location.clone(),
+            )),
         }
-
-        Err(LuxError::type_error(
-            format!("Module '{}' not found", path),
-            location.clone(),
-        ))
     }
 
     /// Type check an entire AST
     pub fn check(&mut self, ast: &Ast) -> LuxResult<()> {
+        self.hoist_functions(&ast.statements);
         for stmt in &ast.statements {
             self.check_stmt(stmt)?;
         }
         Ok(())
     }
 
+    /// Register the signatures of top-level functions before any body is
+    /// checked, so functions can be called above their declaration
+    fn hoist_functions(&mut self, statements: &[Stmt]) {
+        for stmt in statements {
+            if let Stmt::FunctionDecl { name, params, return_type, location, .. } = stmt {
+                // A signature that can't be resolved yet (e.g. it names a type
+                // alias declared further down) is left to the in-order pass,
+                // which reports any real error
+                let (Ok(params), Ok(return_type)) =
+                    (self.resolve_params(params, location), self.resolve_optional(return_type, location))
+                else {
+                    continue;
+                };
+                let func_type = Type::Function {
+                    params: params.into_iter().map(|(_, t)| t).collect(),
+                    return_type: Box::new(return_type.unwrap_or(Type::Any)),
+                };
+                self.env.define(*name, func_type);
+            }
+        }
+    }
+
     /// Check a statement
     fn check_stmt(&mut self, stmt: &Stmt) -> LuxResult<()> {
         match stmt {
-            Stmt::Import { path, location } => {
+            Stmt::Import { path, kind, location } => {
                 // Load and type-check the imported module
-                self.import_module(path, location)?;
+                match kind {
+                    ImportKind::All => self.import_module(path, location)?,
+                    // The alias is a table with one field per top-level binding
+                    ImportKind::Alias(name) => {
+                        let mut fields: Vec<(String, Type)> = self
+                            .module_exports(path, location)?
+                            .into_iter()
+                            .map(|(name, typ)| (name.to_string(), typ))
+                            .collect();
+                        fields.sort_by(|(a, _), (b, _)| a.cmp(b));
+                        self.env.define(name.clone(), Type::Shape(fields));
+                    }
+                    ImportKind::Names(names) => {
+                        let exports = self.module_exports(path, location)?;
+                        for name in names {
+                            let Some(typ) = exports.get(&Symbol::from(name)) else {
+                                return Err(LuxError::type_error(
+                                    format!("Module '{}' has no '{}'", path, name),
+                                    location.clone(),
+                                ));
+                            };
+                            self.env.define(name.clone(), typ.clone());
+                        }
+                    }
+                }
                 Ok(())
             }
 
             Stmt::VarDecl { name, type_annotation, initializer, location, .. } => {
-                let init_type = if let Some(init) = initializer {
-                    Some(self.check_expr(init)?)
-                } else {
-                    None
+                let type_annotation = &self.resolve_optional(type_annotation, location)?;
+                let init_type = match (initializer, type_annotation) {
+                    (Some(init), Some(annotated)) => Some(self.check_expr_expecting(init, annotated)?),
+                    (Some(init), None) => Some(self.check_expr(init)?),
+                    (None, _) => None,
                 };
 
                 let var_type = match (type_annotation, init_type) {
@@ -420,7 +1004,7 @@ impl TypeChecker {
                         if !self.types_compatible(annotated, &init) {
                             return Err(LuxError::type_error(
                                 format!(
-                                    "Type mismatch: variable '{}' declared as {:?} but initialized with {:?}",
+                                    "Type mismatch: variable '{}' declared as {} but initialized with {}",
                                     name, annotated, init
                                 ),
                                 location.clone(),
@@ -444,24 +1028,44 @@ impl TypeChecker {
                     }
                 };
 
-                self.env.define(name.clone(), var_type);
+                self.env.define(*name, var_type);
+                Ok(())
+            }
+
+            Stmt::Destructure { names, initializer, location, .. } => {
+                let element_types = match self.check_expr(initializer)? {
+                    Type::Tuple(elements) if elements.len() == names.len() => elements,
+                    Type::Any => vec![Type::Any; names.len()],
+                    other => {
+                        return Err(LuxError::type_error(
+                            format!("Cannot destructure {} into {} variables", other, names.len()),
+                            location.clone(),
+                        ))
+                    }
+                };
+                for (name, element_type) in names.iter().zip(element_types) {
+                    self.env.define(*name, element_type);
+                }
                 Ok(())
             }
 
             Stmt::FunctionDecl { name, params, return_type, body, location, .. } => {
+                let params = &self.resolve_params(params, location)?;
+                let return_type = &self.resolve_optional(return_type, location)?;
+
                 // Define function type in environment
                 let func_type = Type::Function {
                     params: params.iter().map(|(_, t)| t.clone()).collect(),
-                    return_type: Box::new(return_type.clone().unwrap_or(Type::Nil)),
+                    return_type: Box::new(return_type.clone().unwrap_or(Type::Any)),
                 };
-                self.env.define(name.clone(), func_type);
+                self.env.define(*name, func_type);
 
                 // Check function body in new scope
                 self.env.push_scope();
 
                 // Define parameters
                 for (param_name, param_type) in params {
-                    self.env.define(param_name.clone(), param_type.clone());
+                    self.env.define(*param_name, param_type.clone());
                 }
 
                 // Set current function return type
@@ -469,7 +1073,7 @@ impl TypeChecker {
                 self.current_function_return_type = return_type.clone();
 
                 // Check body
-                for stmt in body {
+                for stmt in body.iter() {
                     self.check_stmt(stmt)?;
                 }
 
@@ -485,12 +1089,24 @@ impl TypeChecker {
                 Ok(())
             }
 
-            Stmt::If { condition, then_branch, else_branch, location } => {
-                let cond_type = self.check_expr(condition)?;
-                // Condition can be any type (truthy/falsy semantics)
+            Stmt::If { condition, then_branch, else_branch, .. } => {
+                // Condition can be any type (truthy/falsy semantics) unless strict
+                self.check_condition(condition, "Condition")?;
+
+                // A nil check on an optional variable narrows it in each branch
+                let narrowed = Self::nil_check(condition).and_then(|(name, is_not_nil)| {
+                    match self.env.get(name) {
+                        Some(Type::Optional(inner)) if is_not_nil => Some((name, *inner, Type::Nil)),
+                        Some(Type::Optional(inner)) => Some((name, Type::Nil, *inner)),
+                        _ => None,
+                    }
+                });
 
                 // Check then branch
                 self.env.push_scope();
+                if let Some((name, then_type, _)) = &narrowed {
+                    self.env.define(*name, then_type.clone());
+                }
                 for stmt in then_branch {
                     self.check_stmt(stmt)?;
                 }
@@ -499,6 +1115,9 @@ impl TypeChecker {
                 // Check else branch
                 if let Some(else_stmts) = else_branch {
                     self.env.push_scope();
+                    if let Some((name, _, else_type)) = narrowed {
+                        self.env.define(name, else_type);
+                    }
                     for stmt in else_stmts {
                         self.check_stmt(stmt)?;
                     }
@@ -509,7 +1128,7 @@ impl TypeChecker {
             }
 
             Stmt::While { condition, body, .. } => {
-                self.check_expr(condition)?;
+                self.check_condition(condition, "Condition")?;
 
                 self.env.push_scope();
                 for stmt in body {
@@ -528,7 +1147,7 @@ impl TypeChecker {
                 }
 
                 if let Some(cond) = condition {
-                    self.check_expr(cond)?;
+                    self.check_condition(cond, "Condition")?;
                 }
 
                 if let Some(inc) = increment {
@@ -545,17 +1164,20 @@ impl TypeChecker {
 
             Stmt::Return { value, location } => {
                 let return_type = if let Some(val) = value {
-                    self.check_expr(val)?
+                    match self.current_function_return_type.clone() {
+                        Some(expected) => self.check_expr_expecting(val, &expected)?,
+                        None => self.check_expr(val)?,
+                    }
                 } else {
                     Type::Nil
                 };
 
                 if let Some(expected) = &self.current_function_return_type {
-                    // Allow Nil (unknown type) to match any expected return type
-                    if !matches!(return_type, Type::Nil) && !self.types_compatible(expected, &return_type) {
+                    // A bare `return` leaves the function early without a value
+                    if value.is_some() && !self.types_compatible(expected, &return_type) {
                         return Err(LuxError::type_error(
                             format!(
-                                "Return type mismatch: expected {:?}, got {:?}",
+                                "Return type mismatch: expected {}, got {}",
                                 expected, return_type
                             ),
                             location.clone(),
@@ -563,19 +1185,122 @@ impl TypeChecker {
                     }
                 }
 
-                Ok(())
-            }
+                Ok(())
+            }
+
+            Stmt::Break { .. } | Stmt::Continue { .. } => Ok(()),
+
+            Stmt::Defer { expr, .. } => {
+                self.check_expr(expr)?;
+                Ok(())
+            }
+
+            Stmt::Block { statements, .. } => {
+                self.env.push_scope();
+                for stmt in statements {
+                    self.check_stmt(stmt)?;
+                }
+                self.env.pop_scope();
+                Ok(())
+            }
+
+            Stmt::TypeAlias { name, target, location } => {
+                if Self::mentions_type(target, name) {
+                    return Err(LuxError::type_error(
+                        format!("Type alias '{}' cannot refer to itself", name),
+                        location.clone(),
+                    ));
+                }
+                let resolved = self.resolve_type(target, location)?;
+                self.aliases.insert(name.clone(), resolved);
+                Ok(())
+            }
+
+            Stmt::StructDecl { name, fields, location } => {
+                let fields = self.resolve_params(fields, location)?;
+                self.structs.insert(name.clone());
+                self.aliases.insert(name.clone(), Type::Shape(fields));
+                Ok(())
+            }
+
+            Stmt::EnumDecl { name, members, .. } => {
+                // The enum name is both a type and a namespace holding its members
+                let member_type = Type::Enum(name.clone());
+                self.aliases.insert(name.clone(), member_type.clone());
+                let namespace = members
+                    .iter()
+                    .map(|(member, _)| (member.clone(), member_type.clone()))
+                    .collect();
+                self.env.define(name.clone(), Type::Shape(namespace));
+                Ok(())
+            }
+        }
+    }
+
+    /// Replace alias names in a type with the types they stand for
+    fn resolve_type(&self, typ: &Type, location: &SourceLocation) -> LuxResult<Type> {
+        Ok(match typ {
+            Type::Named(name) => match self.aliases.get(name) {
+                Some(resolved) => resolved.clone(),
+                None => {
+                    return Err(LuxError::type_error(
+                        format!("Unknown type '{}'", name),
+                        location.clone(),
+                    ))
+                }
+            },
+            Type::Function { params, return_type } => Type::Function {
+                params: params
+                    .iter()
+                    .map(|p| self.resolve_type(p, location))
+                    .collect::<LuxResult<_>>()?,
+                return_type: Box::new(self.resolve_type(return_type, location)?),
+            },
+            Type::Pointer(inner) => Type::Pointer(Box::new(self.resolve_type(inner, location)?)),
+            Type::Array(element) => Type::Array(Box::new(self.resolve_type(element, location)?)),
+            Type::Map { key, value } => Type::Map {
+                key: Box::new(self.resolve_type(key, location)?),
+                value: Box::new(self.resolve_type(value, location)?),
+            },
+            Type::Shape(fields) => Type::Shape(self.resolve_params(fields, location)?),
+            Type::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|e| self.resolve_type(e, location))
+                    .collect::<LuxResult<_>>()?,
+            ),
+            // Types that already admit nil don't need the wrapper
+            Type::Optional(inner) => match self.resolve_type(inner, location)? {
+                resolved @ (Type::Any | Type::Nil | Type::Optional(_)) => resolved,
+                resolved => Type::Optional(Box::new(resolved)),
+            },
+            other => other.clone(),
+        })
+    }
+
+    fn resolve_optional(&self, typ: &Option<Type>, location: &SourceLocation) -> LuxResult<Option<Type>> {
+        typ.as_ref().map(|t| self.resolve_type(t, location)).transpose()
+    }
 
-            Stmt::Break { .. } | Stmt::Continue { .. } => Ok(()),
+    fn resolve_params<N: Clone>(&self, params: &[(N, Type)], location: &SourceLocation) -> LuxResult<Vec<(N, Type)>> {
+        params
+            .iter()
+            .map(|(name, t)| Ok((name.clone(), self.resolve_type(t, location)?)))
+            .collect()
+    }
 
-            Stmt::Block { statements, .. } => {
-                self.env.push_scope();
-                for stmt in statements {
-                    self.check_stmt(stmt)?;
-                }
-                self.env.pop_scope();
-                Ok(())
+    /// Whether `typ` refers to the named type anywhere inside it
+    fn mentions_type(typ: &Type, name: &str) -> bool {
+        match typ {
+            Type::Named(other) => other == name,
+            Type::Function { params, return_type } => {
+                params.iter().any(|p| Self::mentions_type(p, name)) || Self::mentions_type(return_type, name)
             }
+            Type::Pointer(inner) | Type::Array(inner) | Type::Optional(inner) => Self::mentions_type(inner, name),
+            Type::Map { key, value } => Self::mentions_type(key, name) || Self::mentions_type(value, name),
+            Type::Shape(fields) => fields.iter().any(|(_, t)| Self::mentions_type(t, name)),
+            Type::Tuple(elements) => elements.iter().any(|t| Self::mentions_type(t, name)),
+            _ => false,
         }
     }
 
@@ -592,8 +1317,8 @@ impl TypeChecker {
                 })
             }
 
-            Expr::Variable { name, location } => {
-                self.env.get(name).ok_or_else(|| {
+            Expr::Variable { name, location, .. } => {
+                self.env.get(*name).ok_or_else(|| {
                     LuxError::type_error(
                         format!("Undefined variable '{}'", name),
                         location.clone(),
@@ -601,61 +1326,81 @@ impl TypeChecker {
                 })
             }
 
-            Expr::Binary { left, operator, right, location } => {
+            Expr::Binary { left, operator, right, location, .. } => {
                 let left_type = self.check_expr(left)?;
                 let right_type = self.check_expr(right)?;
+                let span = expr.span();
+
+                if let Some(chained) = Self::chained_comparison(left, operator, right) {
+                    return Err(LuxError::type_error(
+                        format!("Comparisons cannot be chained; write `{}` instead", chained),
+                        location.clone(),
+                    ).with_span(span.clone()));
+                }
 
-                // If either operand is Nil (unknown type from table access), be lenient
-                if matches!(left_type, Type::Nil) || matches!(right_type, Type::Nil) {
+                // If either operand is Any (e.g. from table access), be lenient
+                if matches!(left_type, Type::Any) || matches!(right_type, Type::Any) {
                     // Unknown type - allow operation and infer result type
                     return Ok(match operator {
                         BinaryOp::Equal | BinaryOp::NotEqual |
                         BinaryOp::Less | BinaryOp::LessEqual |
                         BinaryOp::Greater | BinaryOp::GreaterEqual => Type::Bool,
-                        _ => Type::Nil, // Unknown result type
+                        _ => Type::Any, // Unknown result type
                     });
                 }
 
                 match operator {
                     BinaryOp::Add => {
                         // Add works for int + int, float + float, string + string
-                        if self.types_compatible(&left_type, &right_type) {
+                        if let Some(result) = Self::mixed_numeric_type(&left_type, &right_type) {
+                            Ok(result)
+                        } else if self.types_compatible(&left_type, &right_type) {
                             match left_type {
                                 Type::Int | Type::Float | Type::String => Ok(left_type),
                                 _ => Err(LuxError::type_error(
-                                    format!("Cannot add {:?} and {:?}", left_type, right_type),
+                                    format!("Cannot add {} and {}", left_type, right_type),
                                     location.clone(),
-                                )),
+                                ).with_span(span.clone())),
                             }
                         } else {
                             Err(LuxError::type_error(
-                                format!("Type mismatch: cannot add {:?} and {:?}", left_type, right_type),
+                                format!("Type mismatch: cannot add {} and {}", left_type, right_type),
                                 location.clone(),
-                            ))
+                            ).with_span(span.clone()))
                         }
                     }
 
-                    BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::Modulo => {
+                    BinaryOp::Subtract
+                    | BinaryOp::Multiply
+                    | BinaryOp::Divide
+                    | BinaryOp::FloorDivide
+                    | BinaryOp::Modulo
+                    | BinaryOp::Power => {
                         // Arithmetic operations work for int and float
                         if !matches!(left_type, Type::Int | Type::Float) {
                             return Err(LuxError::type_error(
-                                format!("Cannot apply {:?} to {:?}", operator, left_type),
+                                format!("Cannot apply {:?} to {}", operator, left_type),
                                 location.clone(),
-                            ));
+                            ).with_span(span.clone()));
                         }
                         if !matches!(right_type, Type::Int | Type::Float) {
                             return Err(LuxError::type_error(
-                                format!("Cannot apply {:?} to {:?}", operator, right_type),
+                                format!("Cannot apply {:?} to {}", operator, right_type),
                                 location.clone(),
-                            ));
+                            ).with_span(span.clone()));
                         }
-                        if self.types_compatible(&left_type, &right_type) {
+                        if matches!(operator, BinaryOp::Divide | BinaryOp::Power) {
+                            // `/` and `^` always produce floats; `~/` keeps ints
+                            Ok(Type::Float)
+                        } else if let Some(result) = Self::mixed_numeric_type(&left_type, &right_type) {
+                            Ok(result)
+                        } else if self.types_compatible(&left_type, &right_type) {
                             Ok(left_type)
                         } else {
                             Err(LuxError::type_error(
-                                format!("Type mismatch: {:?} and {:?}", left_type, right_type),
+                                format!("Type mismatch: {} and {}", left_type, right_type),
                                 location.clone(),
-                            ))
+                            ).with_span(span.clone()))
                         }
                     }
 
@@ -668,31 +1413,31 @@ impl TypeChecker {
                         // Ordering comparisons work for int and float
                         if !matches!(left_type, Type::Int | Type::Float) {
                             return Err(LuxError::type_error(
-                                format!("Cannot compare {:?}", left_type),
+                                format!("Cannot compare {}", left_type),
                                 location.clone(),
-                            ));
+                            ).with_span(span.clone()));
                         }
                         if !matches!(right_type, Type::Int | Type::Float) {
                             return Err(LuxError::type_error(
-                                format!("Cannot compare {:?}", right_type),
+                                format!("Cannot compare {}", right_type),
                                 location.clone(),
-                            ));
+                            ).with_span(span.clone()));
                         }
                         Ok(Type::Bool)
                     }
                 }
             }
 
-            Expr::Unary { operator, operand, location } => {
+            Expr::Unary { operator, operand, location, .. } => {
                 let operand_type = self.check_expr(operand)?;
 
                 match operator {
                     UnaryOp::Negate => {
-                        if matches!(operand_type, Type::Int | Type::Float) {
+                        if matches!(operand_type, Type::Int | Type::Float | Type::Any) {
                             Ok(operand_type)
                         } else {
                             Err(LuxError::type_error(
-                                format!("Cannot negate {:?}", operand_type),
+                                format!("Cannot negate {}", operand_type),
                                 location.clone(),
                             ))
                         }
@@ -702,12 +1447,14 @@ impl TypeChecker {
                         Ok(Type::Bool)
                     }
                     UnaryOp::Length => {
-                        // Length works on strings and tables
-                        if matches!(operand_type, Type::String | Type::Table) {
+                        // Length works on strings, tables and tuples
+                        if matches!(operand_type, Type::String | Type::Any | Type::Tuple(_))
+                            || Self::is_table_type(&operand_type)
+                        {
                             Ok(Type::Int)
                         } else {
                             Err(LuxError::type_error(
-                                format!("Cannot get length of {:?}", operand_type),
+                                format!("Cannot get length of {}", operand_type),
                                 location.clone(),
                             ))
                         }
@@ -720,9 +1467,11 @@ impl TypeChecker {
                         // * operator dereferences a pointer
                         if let Type::Pointer(inner_type) = operand_type {
                             Ok(*inner_type)
+                        } else if operand_type == Type::Any {
+                            Ok(Type::Any)
                         } else {
                             Err(LuxError::type_error(
-                                format!("Cannot dereference non-pointer type {:?}", operand_type),
+                                format!("Cannot dereference non-pointer type {}", operand_type),
                                 location.clone(),
                             ))
                         }
@@ -731,31 +1480,35 @@ impl TypeChecker {
             }
 
             Expr::Logical { left, operator, right, .. } => {
-                self.check_expr(left)?;
-                self.check_expr(right)?;
-                // Logical operators work on any type (truthy/falsy)
-                // Return type is bool
-                Ok(Type::Bool)
+                // Logical operators work on any type (truthy/falsy) unless strict
+                let left_type = self.check_condition(left, "Logical operand")?;
+                let right_type = self.check_condition(right, "Logical operand")?;
+                // Like the runtime, `and`/`or` evaluate to one of their operands
+                Ok(match operator {
+                    LogicalOp::And => right_type,
+                    LogicalOp::Or if left_type == Type::Nil => right_type,
+                    LogicalOp::Or if left_type == right_type => left_type,
+                    LogicalOp::Or => Type::Any,
+                })
             }
 
-            Expr::Assign { target, value, location } => {
+            Expr::Assign { target, value, location, .. } => {
                 let value_type = self.check_expr(value)?;
 
                 match target.as_ref() {
                     Expr::Variable { name, .. } => {
                         // Simple variable assignment
-                        let var_type = self.env.get(name).ok_or_else(|| {
+                        let var_type = self.env.get(*name).ok_or_else(|| {
                             LuxError::type_error(
                                 format!("Undefined variable '{}'", name),
                                 location.clone(),
                             )
                         })?;
 
-                        // Allow Nil (unknown type) to be assigned to any variable
-                        if !matches!(value_type, Type::Nil) && !self.types_compatible(&var_type, &value_type) {
+                        if !self.types_compatible(&var_type, &value_type) {
                             return Err(LuxError::type_error(
                                 format!(
-                                    "Type mismatch: cannot assign {:?} to variable of type {:?}",
+                                    "Type mismatch: cannot assign {} to variable of type {}",
                                     value_type, var_type
                                 ),
                                 location.clone(),
@@ -764,22 +1517,64 @@ impl TypeChecker {
 
                         Ok(value_type)
                     }
-                    Expr::TableAccess { table, .. } => {
+                    Expr::TableAccess { table, key, .. } => {
                         // Table element assignment: table[key] = value
                         // Check that the table expression is valid
                         let table_type = self.check_expr(table)?;
 
                         // For now, just verify it's a table type
-                        if !matches!(table_type, Type::Table | Type::Nil) {
+                        if !matches!(table_type, Type::Any) && !Self::is_table_type(&table_type) {
                             return Err(LuxError::type_error(
-                                format!("Cannot index non-table type {:?}", table_type),
+                                format!("Cannot index non-table type {}", table_type),
                                 location.clone(),
                             ));
                         }
 
+                        // Typed arrays, maps and shapes only accept their element type
+                        let element_type = match &table_type {
+                            Type::Array(element_type) => Some(element_type.as_ref()),
+                            Type::Map { value, .. } => Some(value.as_ref()),
+                            Type::Shape(fields) => match Self::shape_field(fields, key) {
+                                Some(Some(field_type)) => Some(field_type),
+                                Some(None) => return Err(Self::unknown_field_error(&table_type, key, location)),
+                                None => None,
+                            },
+                            _ => None,
+                        };
+                        if let Some(element_type) = element_type {
+                            if !self.types_compatible(element_type, &value_type) {
+                                return Err(LuxError::type_error(
+                                    format!("Cannot store {} in a {}", value_type, table_type),
+                                    location.clone(),
+                                ));
+                            }
+                        }
+
                         // Table assignments are dynamically typed, so we accept any value
                         Ok(value_type)
                     }
+                    Expr::Unary { operator: UnaryOp::Dereference, operand, .. } => {
+                        // *p = value must store the pointee's type
+                        match self.check_expr(operand)? {
+                            Type::Pointer(pointee) => {
+                                if !self.types_compatible(&pointee, &value_type) {
+                                    return Err(LuxError::type_error(
+                                        format!(
+                                            "Type mismatch: cannot assign {} through a pointer to {}",
+                                            value_type, pointee
+                                        ),
+                                        location.clone(),
+                                    ));
+                                }
+                                Ok(value_type)
+                            }
+                            Type::Any => Ok(value_type),
+                            other => Err(LuxError::type_error(
+                                format!("Cannot dereference non-pointer type {}", other),
+                                location.clone(),
+                            )),
+                        }
+                    }
                     _ => {
                         Err(LuxError::type_error(
                             "Invalid assignment target".to_string(),
@@ -789,105 +1584,206 @@ impl TypeChecker {
                 }
             }
 
-            Expr::Call { callee, arguments, location } => {
+            Expr::Call { callee, arguments, location, .. } => {
                 let func_type = self.check_expr(callee)?;
 
                 match func_type {
                     Type::Function { params, return_type } => {
-                        // Check argument count (but be lenient for built-ins that use Nil as "any")
-                        // If params has a single Nil, it means "accepts any number of any type" (built-in)
-                        let is_builtin = params.len() == 1 && params[0] == Type::Nil;
+                        // A trailing variadic parameter accepts any number of extra arguments
+                        let (fixed, rest) = match params.split_last() {
+                            Some((Type::Variadic(rest), fixed)) => (fixed, Some(rest)),
+                            _ => (params.as_slice(), None),
+                        };
 
-                        if !is_builtin && arguments.len() != params.len() {
+                        if rest.is_none() && arguments.len() != fixed.len() {
                             return Err(LuxError::type_error(
                                 format!(
                                     "Function expects {} arguments, got {}",
-                                    params.len(),
+                                    fixed.len(),
+                                    arguments.len()
+                                ),
+                                location.clone(),
+                            ));
+                        }
+                        if arguments.len() < fixed.len() {
+                            return Err(LuxError::type_error(
+                                format!(
+                                    "Function expects at least {} arguments, got {}",
+                                    fixed.len(),
                                     arguments.len()
                                 ),
                                 location.clone(),
                             ));
                         }
 
-                        // Check argument types (skip for built-ins)
-                        if !is_builtin {
-                            for (i, (arg, expected_type)) in arguments.iter().zip(params.iter()).enumerate() {
-                                let arg_type = self.check_expr(arg)?;
-                                // Allow Nil (unknown type) to match any expected type
-                                // Also allow expected_type of Nil to accept any arg_type (for variadic/any params)
-                                if !matches!(arg_type, Type::Nil)
-                                    && !matches!(expected_type, Type::Nil)
-                                    && !self.types_compatible(expected_type, &arg_type) {
-                                    return Err(LuxError::type_error(
-                                        format!(
-                                            "Argument {} type mismatch: expected {:?}, got {:?}",
-                                            i + 1,
-                                            expected_type,
-                                            arg_type
-                                        ),
-                                        location.clone(),
-                                    ));
-                                }
-                            }
-                        } else {
-                            // For built-ins, just check that arguments are valid expressions
-                            for arg in arguments {
-                                self.check_expr(arg)?;
-                            }
+                        let mut expected = fixed.to_vec();
+                        if let Some(rest) = rest {
+                            expected.resize(arguments.len(), (**rest).clone());
+                        }
+                        self.check_arguments(&expected, arguments, 1, location)?;
+
+                        Ok(*return_type)
+                    }
+                    Type::Int | Type::Float | Type::String | Type::Bool => Err(LuxError::type_error(
+                        format!("Cannot call a value of type {}", func_type),
+                        location.clone(),
+                    )),
+                    _ => {
+                        // Callees whose type isn't known precisely (nil, any, tables
+                        // with fields of any type) are checked at runtime
+                        for arg in arguments {
+                            self.check_expr(arg)?;
                         }
+                        Ok(Type::Any)
+                    }
+                }
+            }
+
+            Expr::MethodCall { receiver, method, arguments, location, .. } => {
+                let receiver_type = self.check_expr(receiver)?;
+                let method_type = match &receiver_type {
+                    Type::Shape(fields) => match fields.iter().find(|(field, _)| field == method) {
+                        Some((_, field_type)) => field_type.clone(),
+                        None => return Err(Self::no_field_error(&receiver_type, method, location)),
+                    },
+                    Type::Table | Type::Any => Type::Any,
+                    _ => {
+                        return Err(LuxError::type_error(
+                            format!("Cannot call method '{}' on {}", method, receiver_type),
+                            location.clone(),
+                        ))
+                    }
+                };
 
+                match method_type {
+                    Type::Function { params, return_type } => {
+                        // The receiver is passed as the first argument
+                        if params.len() != arguments.len() + 1 {
+                            return Err(LuxError::type_error(
+                                format!(
+                                    "Method '{}' expects {} arguments besides the receiver, got {}",
+                                    method,
+                                    params.len().saturating_sub(1),
+                                    arguments.len()
+                                ),
+                                location.clone(),
+                            ));
+                        }
+                        if !self.types_compatible(&params[0], &receiver_type) {
+                            return Err(LuxError::type_error(
+                                format!("Receiver type mismatch: expected {}, got {}", params[0], receiver_type),
+                                location.clone(),
+                            ));
+                        }
+                        self.check_arguments(&params[1..], arguments, 2, location)?;
                         Ok(*return_type)
                     }
                     _ => {
-                        // For now, allow calling non-function types (built-ins, etc.)
-                        // Return unknown type as Nil
-                        Ok(Type::Nil)
+                        for arg in arguments {
+                            self.check_expr(arg)?;
+                        }
+                        Ok(Type::Any)
                     }
                 }
             }
 
             Expr::Table { fields, .. } => {
                 // Check all field values
-                for (key, value) in fields {
+                for (_, value) in fields {
                     self.check_expr(value)?;
                 }
                 Ok(Type::Table)
             }
 
-            Expr::TableAccess { table, key, location } => {
+            Expr::Tuple { elements, .. } => Ok(Type::Tuple(
+                elements.iter().map(|e| self.check_expr(e)).collect::<LuxResult<_>>()?,
+            )),
+
+            Expr::TableAccess { table, key, location, .. } => {
                 let table_type = self.check_expr(table)?;
-                self.check_expr(key)?;
+                let key_type = self.check_expr(key)?;
 
-                // Allow indexing on Table or Nil (unknown type)
-                if !matches!(table_type, Type::Table | Type::Nil) {
-                    return Err(LuxError::type_error(
-                        format!("Cannot index {:?}", table_type),
+                match table_type {
+                    // Typed arrays are indexed by int and yield their element type
+                    Type::Array(element_type) => {
+                        if !matches!(key_type, Type::Int | Type::Any) {
+                            return Err(LuxError::type_error(
+                                format!("Array index must be int, got {}", key_type),
+                                location.clone(),
+                            ));
+                        }
+                        Ok(*element_type)
+                    }
+                    // Shapes yield the declared type of a named field
+                    Type::Shape(ref fields) => match Self::shape_field(fields, key) {
+                        Some(Some(field_type)) => Ok(field_type.clone()),
+                        Some(None) => Err(Self::unknown_field_error(&table_type, key, location)),
+                        // Computed keys can't be resolved statically
+                        None => Ok(Type::Any),
+                    },
+                    // Maps are indexed by their key type and yield their value type
+                    Type::Map { key, value } => {
+                        if !self.types_compatible(&key, &key_type) {
+                            return Err(LuxError::type_error(
+                                format!("Map key must be {}, got {}", key, key_type),
+                                location.clone(),
+                            ));
+                        }
+                        Ok(*value)
+                    }
+                    // Tuples yield the element type when the index is a literal
+                    Type::Tuple(ref elements) => {
+                        if !matches!(key_type, Type::Int | Type::Any) {
+                            return Err(LuxError::type_error(
+                                format!("Tuple index must be int, got {}", key_type),
+                                location.clone(),
+                            ));
+                        }
+                        let Expr::Literal { value: Literal::Integer(index), .. } = key.as_ref() else {
+                            return Ok(Type::Any);
+                        };
+                        let position = if *index < 0 { elements.len() as i64 + index } else { index - 1 };
+                        usize::try_from(position)
+                            .ok()
+                            .and_then(|i| elements.get(i))
+                            .cloned()
+                            .ok_or_else(|| {
+                                LuxError::type_error(
+                                    format!("Tuple index {} out of range for {}", index, table_type),
+                                    location.clone(),
+                                )
+                            })
+                    }
+                    // Table indexing can return any type
+                    Type::Table | Type::Any => Ok(Type::Any),
+                    _ => Err(LuxError::type_error(
+                        format!("Cannot index {}", table_type),
                         location.clone(),
-                    ));
+                    )),
                 }
-
-                // Table indexing can return any type
-                Ok(Type::Nil)
             }
 
-            Expr::Function { params, return_type, body, .. } => {
+            Expr::Function { params, return_type, body, location, .. } => {
+                let params = &self.resolve_params(params, location)?;
+                let return_type = &self.resolve_optional(return_type, location)?;
+
                 // Function expression type
                 let func_type = Type::Function {
                     params: params.iter().map(|(_, t)| t.clone()).collect(),
-                    return_type: Box::new(return_type.clone().unwrap_or(Type::Nil)),
+                    return_type: Box::new(return_type.clone().unwrap_or(Type::Any)),
                 };
 
                 // Check function body
                 self.env.push_scope();
 
                 for (param_name, param_type) in params {
-                    self.env.define(param_name.clone(), param_type.clone());
+                    self.env.define(*param_name, param_type.clone());
                 }
 
                 let prev_return_type = self.current_function_return_type.clone();
                 self.current_function_return_type = return_type.clone();
 
-                for stmt in body {
+                for stmt in body.iter() {
                     self.check_stmt(stmt)?;
                 }
 
@@ -897,43 +1793,346 @@ impl TypeChecker {
                 Ok(func_type)
             }
 
-            Expr::Spawn { call, location } => {
+            Expr::StructInit { name, fields, location, .. } => {
+                let shape_fields = match self.aliases.get(name) {
+                    Some(Type::Shape(shape_fields)) if self.structs.contains(name) => shape_fields.clone(),
+                    _ => {
+                        return Err(LuxError::type_error(
+                            format!("Unknown struct '{}'", name),
+                            location.clone(),
+                        ))
+                    }
+                };
+                self.check_shape_literal(name, &shape_fields, fields, location)?;
+                Ok(Type::Shape(shape_fields))
+            }
+
+            Expr::DoBlock { body, .. } => {
+                self.env.push_scope();
+                let result = self.check_do_block(body);
+                self.env.pop_scope();
+                result
+            }
+
+            Expr::Spawn { call, .. } => {
                 // Spawn expects a function call
                 self.check_expr(call)?;
                 // Returns task ID (int)
                 Ok(Type::Int)
             }
 
-            Expr::Await { task, location } => {
+            Expr::Await { task, location, .. } => {
                 let task_type = self.check_expr(task)?;
                 // Await accepts either a single task ID (int) or a table of task IDs
-                if !matches!(task_type, Type::Int | Type::Table | Type::Nil) {
+                if !matches!(task_type, Type::Int | Type::Any) && !Self::is_table_type(&task_type) {
                     return Err(LuxError::type_error(
-                        format!("await expects task ID (int) or table of task IDs, got {:?}", task_type),
+                        format!("await expects task ID (int) or table of task IDs, got {}", task_type),
                         location.clone(),
                     ));
                 }
                 // Await can return any type (we don't know the task's return type)
                 // If awaiting a table, it returns a table of results
                 // If awaiting a single task, it returns the task's result
+                Ok(Type::Any)
+            }
+        }
+    }
+
+    /// Check an expression used for its truthiness; in strict mode it must be bool
+    fn check_condition(&mut self, expr: &Expr, what: &str) -> LuxResult<Type> {
+        let typ = self.check_expr(expr)?;
+        if self.strict && !self.types_compatible(&Type::Bool, &typ) {
+            return Err(LuxError::type_error(
+                format!("{} must be bool in strict mode, got {}", what, typ),
+                expr.location().clone(),
+            ));
+        }
+        Ok(typ)
+    }
+
+    /// Check the statements of a do block; its type is the type of the
+    /// final expression statement, or nil when there is none
+    fn check_do_block(&mut self, body: &[Stmt]) -> LuxResult<Type> {
+        let Some((last, rest)) = body.split_last() else {
+            return Ok(Type::Nil);
+        };
+        for stmt in rest {
+            self.check_stmt(stmt)?;
+        }
+        match last {
+            Stmt::Expression { expr, .. } => self.check_expr(expr),
+            other => {
+                self.check_stmt(other)?;
                 Ok(Type::Nil)
             }
         }
     }
 
+    /// Check call arguments against parameter types; `first` is the
+    /// position of the first argument in error messages
+    fn check_arguments(&mut self, params: &[Type], arguments: &[Expr], first: usize, location: &SourceLocation) -> LuxResult<()> {
+        for (i, (arg, expected_type)) in arguments.iter().zip(params).enumerate() {
+            let arg_type = self.check_expr_expecting(arg, expected_type)?;
+            if !self.types_compatible(expected_type, &arg_type) {
+                return Err(LuxError::type_error(
+                    format!(
+                        "Argument {} type mismatch: expected {}, got {}",
+                        i + first,
+                        expected_type,
+                        arg_type
+                    ),
+                    location.clone(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check an expression whose expected type is known from context
+    ///
+    /// Table literals are checked element by element against a typed
+    /// array or map; everything else falls back to [`Self::check_expr`].
+    fn check_expr_expecting(&mut self, expr: &Expr, expected: &Type) -> LuxResult<Type> {
+        if let (Expr::Table { fields, .. }, Type::Map { key: key_type, value: value_type }) = (expr, expected) {
+            for (key, value) in fields {
+                let actual_key_type = match key {
+                    TableKey::Identifier(_) => Type::String,
+                    TableKey::Expression(key_expr) => self.check_expr(key_expr)?,
+                };
+                if !self.types_compatible(key_type, &actual_key_type) {
+                    return Err(LuxError::type_error(
+                        format!("Map key type mismatch: expected {}, got {}", key_type, actual_key_type),
+                        value.location().clone(),
+                    ));
+                }
+                let actual_value_type = self.check_expr_expecting(value, value_type)?;
+                if !self.types_compatible(value_type, &actual_value_type) {
+                    return Err(LuxError::type_error(
+                        format!("Map value type mismatch: expected {}, got {}", value_type, actual_value_type),
+                        value.location().clone(),
+                    ));
+                }
+            }
+            return Ok(expected.clone());
+        }
+        if let (Expr::Table { fields, location, .. }, Type::Array(element_type)) = (expr, expected) {
+            for (key, value) in fields {
+                if matches!(key, TableKey::Identifier(_)) {
+                    return Err(LuxError::type_error(
+                        format!("Array literal of {} cannot have named fields", element_type),
+                        location.clone(),
+                    ));
+                }
+                let value_type = self.check_expr_expecting(value, element_type)?;
+                if !self.types_compatible(element_type, &value_type) {
+                    return Err(LuxError::type_error(
+                        format!("Array element type mismatch: expected {}, got {}", element_type, value_type),
+                        value.location().clone(),
+                    ));
+                }
+            }
+            return Ok(expected.clone());
+        }
+        if let (Expr::Table { fields, location, .. }, Type::Shape(shape_fields)) = (expr, expected) {
+            self.check_shape_literal(&format!("{}", expected), shape_fields, fields, location)?;
+            return Ok(expected.clone());
+        }
+        self.check_expr(expr)
+    }
+
+    /// Check that a table literal has exactly the fields of a shape, with
+    /// compatible types; `owner` names the shape in error messages
+    fn check_shape_literal(
+        &mut self,
+        owner: &str,
+        shape_fields: &[(String, Type)],
+        fields: &[(TableKey, Expr)],
+        location: &SourceLocation,
+    ) -> LuxResult<()> {
+        for (key, value) in fields {
+            let name = match key {
+                TableKey::Identifier(name) => name,
+                TableKey::Expression(_) => {
+                    return Err(LuxError::type_error(
+                        format!("{} only has named fields", owner),
+                        value.location().clone(),
+                    ));
+                }
+            };
+            let field_type = shape_fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, typ)| typ)
+                .ok_or_else(|| {
+                    LuxError::type_error(format!("{} has no field '{}'", owner, name), value.location().clone())
+                })?;
+            let value_type = self.check_expr_expecting(value, field_type)?;
+            if !self.types_compatible(field_type, &value_type) {
+                return Err(LuxError::type_error(
+                    format!("Field '{}' type mismatch: expected {}, got {}", name, field_type, value_type),
+                    value.location().clone(),
+                ));
+            }
+        }
+        for (name, _) in shape_fields {
+            if !fields.iter().any(|(key, _)| matches!(key, TableKey::Identifier(field) if field == name)) {
+                return Err(LuxError::type_error(format!("Missing field '{}'", name), location.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up the field named by a constant string key in a shape
+    ///
+    /// Returns `None` when the key isn't a string literal, and `Some(None)`
+    /// when it names a field the shape doesn't declare.
+    fn shape_field<'a>(fields: &'a [(String, Type)], key: &Expr) -> Option<Option<&'a Type>> {
+        match key {
+            Expr::Literal { value: Literal::String(name), .. } => {
+                Some(fields.iter().find(|(field, _)| field == name).map(|(_, typ)| typ))
+            }
+            _ => None,
+        }
+    }
+
+    /// For `a < b < c`, which parses as `(a < b) < c` and so compares a bool,
+    /// the comparison that was probably meant: `a < b and b < c`
+    fn chained_comparison(left: &Expr, operator: &BinaryOp, right: &Expr) -> Option<String> {
+        let ordering = |op: &BinaryOp| match op {
+            BinaryOp::Less => Some("<"),
+            BinaryOp::LessEqual => Some("<="),
+            BinaryOp::Greater => Some(">"),
+            BinaryOp::GreaterEqual => Some(">="),
+            _ => None,
+        };
+        let Expr::Binary { left: first, operator: inner, right: middle, .. } = left else {
+            return None;
+        };
+        let (inner, outer) = (ordering(inner)?, ordering(operator)?);
+        // Only simple operands are spelled out; anything else is elided
+        let text = |expr: &Expr| match expr {
+            Expr::Variable { name, .. } => name.to_string(),
+            Expr::Literal { value: Literal::Integer(n), .. } => n.to_string(),
+            Expr::Literal { value: Literal::Float(f), .. } => format!("{:?}", f),
+            _ => "...".to_string(),
+        };
+        let middle = text(middle);
+        Some(format!("{} {} {} and {} {} {}", text(first), inner, middle, middle, outer, text(right)))
+    }
+
+    fn unknown_field_error(shape: &Type, key: &Expr, location: &crate::error::SourceLocation) -> LuxError {
+        let name = match key {
+            Expr::Literal { value: Literal::String(name), .. } => name.as_str(),
+            _ => "?",
+        };
+        Self::no_field_error(shape, name, location)
+    }
+
+    /// The error for a field that `owner` doesn't have; an enum's
+    /// namespace reports a missing member by the enum's name
+    fn no_field_error(owner: &Type, name: &str, location: &SourceLocation) -> LuxError {
+        let enum_name = match owner {
+            Type::Shape(members) => match members.first() {
+                Some((_, Type::Enum(enum_name))) => members
+                    .iter()
+                    .all(|(_, typ)| matches!(typ, Type::Enum(other) if other == enum_name))
+                    .then_some(enum_name),
+                _ => None,
+            },
+            _ => None,
+        };
+        let message = match enum_name {
+            Some(enum_name) => format!("enum '{}' has no member '{}'", enum_name, name),
+            None => format!("{} has no field '{}'", owner, name),
+        };
+        LuxError::type_error(message, location.clone())
+    }
+
+    /// The variable tested by a direct nil comparison (`x != nil`, `nil == x`, ...),
+    /// and whether the condition holds when it is not nil
+    fn nil_check(condition: &Expr) -> Option<(Symbol, bool)> {
+        let Expr::Binary { left, operator, right, .. } = condition else {
+            return None;
+        };
+        let is_not_nil = match operator {
+            BinaryOp::NotEqual => true,
+            BinaryOp::Equal => false,
+            _ => return None,
+        };
+        match (left.as_ref(), right.as_ref()) {
+            (Expr::Variable { name, .. }, Expr::Literal { value: Literal::Nil, .. })
+            | (Expr::Literal { value: Literal::Nil, .. }, Expr::Variable { name, .. }) => Some((*name, is_not_nil)),
+            _ => None,
+        }
+    }
+
+    /// Whether values of this type are tables at runtime
+    fn is_table_type(typ: &Type) -> bool {
+        matches!(typ, Type::Table | Type::Array(_) | Type::Map { .. } | Type::Shape(_))
+    }
+
+    /// Result type of arithmetic on an int and a float (the int is promoted)
+    fn mixed_numeric_type(left: &Type, right: &Type) -> Option<Type> {
+        match (left, right) {
+            (Type::Int, Type::Float) | (Type::Float, Type::Int) => Some(Type::Float),
+            _ => None,
+        }
+    }
+
     /// Check if two types are compatible
     fn types_compatible(&self, expected: &Type, actual: &Type) -> bool {
         match (expected, actual) {
+            (Type::Any, _) | (_, Type::Any) => true,
             (Type::Int, Type::Int) => true,
             (Type::Float, Type::Float) => true,
             (Type::String, Type::String) => true,
             (Type::Bool, Type::Bool) => true,
             (Type::Nil, Type::Nil) => true,
             (Type::Table, Type::Table) => true,
-            (Type::Function { .. }, Type::Function { .. }) => {
-                // For now, accept any function type
-                // TODO: Check parameter and return types
-                true
+            // Enum members are ints, but an arbitrary int is not a member
+            (Type::Enum(expected_enum), Type::Enum(actual_enum)) => expected_enum == actual_enum,
+            (Type::Int, Type::Enum(_)) => true,
+            // A typed array can be used wherever a plain table is expected
+            (Type::Table, Type::Array(_) | Type::Map { .. } | Type::Shape(_)) => true,
+            // A shape is satisfied by any shape that has all of its fields
+            (Type::Shape(expected_fields), Type::Shape(actual_fields)) => {
+                expected_fields.iter().all(|(name, expected_type)| {
+                    actual_fields
+                        .iter()
+                        .any(|(actual_name, actual_type)| actual_name == name && self.types_compatible(expected_type, actual_type))
+                })
+            }
+            (Type::Array(expected_element), Type::Array(actual_element)) => {
+                self.types_compatible(expected_element, actual_element)
+            }
+            (
+                Type::Map { key: expected_key, value: expected_value },
+                Type::Map { key: actual_key, value: actual_value },
+            ) => {
+                self.types_compatible(expected_key, actual_key)
+                    && self.types_compatible(expected_value, actual_value)
+            }
+            (
+                Type::Function { params: expected_params, return_type: expected_return },
+                Type::Function { params: actual_params, return_type: actual_return },
+            ) => {
+                expected_params.len() == actual_params.len()
+                    && expected_params.iter().zip(actual_params).all(|(e, a)| self.types_compatible(e, a))
+                    && self.types_compatible(expected_return, actual_return)
+            }
+            // An optional accepts nil, another optional of a compatible type,
+            // or a plain value of its inner type; the reverse needs a nil check
+            (Type::Optional(_), Type::Nil) => true,
+            (Type::Optional(expected_inner), Type::Optional(actual_inner)) => {
+                self.types_compatible(expected_inner, actual_inner)
+            }
+            (Type::Optional(expected_inner), actual) => self.types_compatible(expected_inner, actual),
+            (Type::Variadic(expected_inner), Type::Variadic(actual_inner)) => {
+                self.types_compatible(expected_inner, actual_inner)
+            }
+            (Type::Tuple(expected_elements), Type::Tuple(actual_elements)) => {
+                expected_elements.len() == actual_elements.len()
+                    && expected_elements.iter().zip(actual_elements).all(|(e, a)| self.types_compatible(e, a))
             }
             (Type::Pointer(expected_inner), Type::Pointer(actual_inner)) => {
                 // Pointers are compatible if their inner types are compatible
@@ -944,3 +2143,297 @@ impl TypeChecker {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lexer, Parser};
+
+    fn check(source: &str) -> LuxResult<()> {
+        let tokens = Lexer::new(source, None).with_newlines().tokenize()?;
+        let ast = Parser::new(tokens).parse()?;
+        TypeChecker::new().check(&ast)
+    }
+
+    #[test]
+    fn test_typed_array_literal() {
+        assert!(check("local xs: []int = {1, 2, 3}").is_ok());
+        assert!(check("local xs: []int = {}").is_ok());
+        assert!(check("local grid: [][]int = {{1}, {2, 3}}").is_ok());
+
+        let err = check("local xs: []int = {1, \"two\"}").unwrap_err();
+        assert!(err.to_string().contains("Array element type mismatch: expected int, got string"));
+    }
+
+    #[test]
+    fn test_typed_array_access() {
+        assert!(check("local xs: []int = {1, 2}\nlocal n: int = xs[1]").is_ok());
+        assert!(check("local xs: []int = {1, 2}\nlocal s: string = xs[1]").is_err());
+        assert!(check("local xs: []int = {1, 2}\nxs[1] = \"three\"").is_err());
+        // Arrays are still tables for builtins that take any table
+        assert!(check("local xs: []int = {1, 2}\nlocal n := table_length(xs) + #xs").is_ok());
+    }
+
+    #[test]
+    fn test_function_type_compatibility() {
+        let apply = "fn apply(f: fn(int) -> int, x: int) -> int { return f(x) }\n";
+        assert!(check(&format!("{}fn double(n: int) -> int {{ return n * 2 }}\napply(double, 4)", apply)).is_ok());
+        assert!(check(&format!("{}apply(fn(n: int) -> int {{ return n + 1 }}, 4)", apply)).is_ok());
+
+        let err = check(&format!("{}fn shout(s: string) -> int {{ return 1 }}\napply(shout, 4)", apply)).unwrap_err();
+        assert_eq!(err.message(), "Argument 1 type mismatch: expected fn(int) -> int, got fn(string) -> int");
+        assert!(check(&format!("{}fn pair(a: int, b: int) -> int {{ return a }}\napply(pair, 4)", apply)).is_err());
+        assert!(check(&format!("{}fn name(n: int) -> string {{ return \"x\" }}\napply(name, 4)", apply)).is_err());
+    }
+
+    #[test]
+    fn test_builtin_argument_counts() {
+        let err = check("substring(\"a\", 1)").unwrap_err();
+        assert!(err.to_string().contains("Function expects 3 arguments, got 2"), "{}", err);
+        // Builtins taking a single value of any type are not variadic
+        assert!(check("print(1, 2)").is_err());
+        assert!(check("type_of()").is_err());
+
+        // Variadic builtins check their fixed and extra arguments
+        assert!(check("string_format(\"%d and %d\", 1, 2)").is_ok());
+        let err = check("string_format()").unwrap_err();
+        assert!(err.to_string().contains("Function expects at least 1 arguments, got 0"), "{}", err);
+        assert!(check("string_find(\"abc\", \"b\")").is_ok());
+        assert!(check("string_find(\"abc\", \"b\", 2)").is_ok());
+        assert!(check("string_find(\"abc\", \"b\", \"2\")").is_err());
+    }
+
+    #[test]
+    fn test_calling_a_non_function() {
+        let err = check("local x := 5\nx()").unwrap_err();
+        assert!(err.to_string().contains("Cannot call a value of type int"), "{}", err);
+        assert!(check("local s := \"text\"\ns(1)").is_err());
+        assert!(check("local done := true\ndone()").is_err());
+
+        // The checker can't tell what an any-typed value holds
+        assert!(check("local handler := json_parse(\"1\")\nhandler()").is_ok());
+        assert!(check("local t := {}\nt.run(1, 2)").is_ok());
+    }
+
+    #[test]
+    fn test_shape_field_access() {
+        let setup = "local p: {name: string, age: int} = {name = \"ann\", age = 31}\n";
+        assert!(check(&format!("{}local n: int = p.age + 1", setup)).is_ok());
+        assert!(check(&format!("{}local n: int = p.name", setup)).is_err());
+        assert!(check(&format!("{}p.age = 32", setup)).is_ok());
+        assert!(check(&format!("{}p.age = \"old\"", setup)).is_err());
+
+        let err = check(&format!("{}local e := p.email", setup)).unwrap_err();
+        assert_eq!(err.message(), "{name: string, age: int} has no field 'email'");
+        let err = check(&format!("{}p.greet()", setup)).unwrap_err();
+        assert_eq!(err.message(), "{name: string, age: int} has no field 'greet'");
+        assert!(check(&format!("{}p.email = \"x\"", setup)).is_err());
+    }
+
+    #[test]
+    fn test_shape_literal() {
+        assert!(check("local p: {x: int} = {x = 1, y = 2}").is_err());
+        let err = check("local p: {x: int, y: int} = {x = 1}").unwrap_err();
+        assert!(err.to_string().contains("Missing field 'y'"));
+    }
+
+    #[test]
+    fn test_typed_map_literal() {
+        assert!(check("local ages: map[string]int = {ann = 31, [\"bob\"] = 42}").is_ok());
+        assert!(check("local names: map[int]string = {\"zero\", [5] = \"five\"}").is_ok());
+
+        let err = check("local ages: map[string]int = {ann = \"old\"}").unwrap_err();
+        assert!(err.to_string().contains("Map value type mismatch: expected int, got string"));
+        let err = check("local ages: map[string]int = {[1] = 2}").unwrap_err();
+        assert!(err.to_string().contains("Map key type mismatch: expected string, got int"));
+    }
+
+    #[test]
+    fn test_typed_map_access() {
+        let setup = "local ages: map[string]int = {ann = 31}\n";
+        assert!(check(&format!("{}local n: int = ages[\"ann\"] + ages.ann", setup)).is_ok());
+        assert!(check(&format!("{}local n := ages[1]", setup)).is_err());
+        assert!(check(&format!("{}ages.bob = true", setup)).is_err());
+    }
+
+    #[test]
+    fn test_type_alias() {
+        assert!(check("type Id = int\nlocal x: Id = 5").is_ok());
+        assert!(check("type Id = int\ntype UserId = Id\nlocal ids: []UserId = {1, 2}").is_ok());
+        assert!(check("type Point = {x: int, y: int}\nfn origin() -> Point { return {x = 0, y = 0} }").is_ok());
+
+        let err = check("type Id = int\nlocal x: Id = \"five\"").unwrap_err();
+        assert!(err.to_string().contains("declared as int but initialized with string"));
+        let err = check("local x: Missing = 5").unwrap_err();
+        assert!(err.to_string().contains("Unknown type 'Missing'"));
+    }
+
+    #[test]
+    fn test_type_alias_cycle_rejected() {
+        let err = check("type A = A").unwrap_err();
+        assert!(err.to_string().contains("Type alias 'A' cannot refer to itself"));
+        assert!(check("type List = []List").is_err());
+    }
+
+    #[test]
+    fn test_any_accepts_every_type() {
+        assert!(check("local x: any = 5\nx = \"five\"\nx = nil").is_ok());
+        assert!(check("fn show(v: any) -> string { return to_string(v) }\nshow(1)\nshow(\"s\")").is_ok());
+        assert!(check("local t := {1}\nlocal n: int = t[1] + 1").is_ok());
+    }
+
+    #[test]
+    fn test_do_block_type_is_final_expression() {
+        assert!(check("local x: int = do { local a := 2; a * 21 }").is_ok());
+        let err = check("local s: string = do { local a := 2; a * 21 }").unwrap_err();
+        assert!(err.to_string().contains("declared as string but initialized with int"));
+        assert!(check("local a := do { local hidden := 1 }\nhidden").is_err());
+    }
+
+    #[test]
+    fn test_strict_mode_requires_bool_conditions() {
+        let check_strict = |source: &str| {
+            let tokens = Lexer::new(source, None).tokenize()?;
+            let ast = Parser::new(tokens).parse()?;
+            TypeChecker::strict().check(&ast)
+        };
+        let err = check_strict("while 1 {}").unwrap_err();
+        assert!(err.to_string().contains("Condition must be bool in strict mode, got int"));
+        assert!(check_strict("local x := 0\nwhile x < 10 { x = x + 1 }").is_ok());
+        assert!(check_strict("if \"yes\" {}").is_err());
+        assert!(check_strict("local ok := 1 and true").is_err());
+        assert!(check_strict("local ok := 1 < 2 and true").is_ok());
+
+        // The default checker keeps Lua-style truthiness
+        assert!(check("while 1 { break }\nif \"yes\" {}\nlocal ok := 1 and true").is_ok());
+    }
+
+    #[test]
+    fn test_logical_operators_type_as_their_operands() {
+        assert!(check("fn f(a: int) -> int { local x := a or 0\n return x + 1 }").is_ok());
+        assert!(check("local x: int = nil or 0").is_ok());
+        assert!(check("local s: string = true and \"yes\"").is_ok());
+
+        let err = check("local count: int = 1 or 0\nlocal s: string = count or 0").unwrap_err();
+        assert!(err.to_string().contains("declared as string but initialized with int"));
+        // Operands of different types give a value of either type
+        assert!(check("local x: int = 1 or \"one\"").is_ok());
+    }
+
+    #[test]
+    fn test_division_types() {
+        assert!(check("local x: float = 7 / 2").is_ok());
+        assert!(check("local x: int = 7 ~/ 2").is_ok());
+        assert!(check("local x: float = 7.0 ~/ 2").is_ok());
+        let err = check("local x: int = 7 / 2").unwrap_err();
+        assert!(err.to_string().contains("declared as int but initialized with float"));
+    }
+
+    #[test]
+    fn test_top_level_functions_are_hoisted() {
+        assert!(check("fn a() -> int { return b() + 1 }\nfn b() -> int { return 41 }").is_ok());
+        assert!(check("local x: int = later()\nfn later() -> int { return 1 }").is_ok());
+
+        // The hoisted signature is used to check calls made above the declaration
+        let err = check("local s: string = later()\nfn later() -> int { return 1 }").unwrap_err();
+        assert!(err.to_string().contains("declared as string but initialized with int"));
+    }
+
+    #[test]
+    fn test_enum_members() {
+        let source = "enum Color { Red, Green, Blue }\n";
+        assert!(check(&format!("{}local c: Color = Color.Green\nlocal n: int = c", source)).is_ok());
+        let err = check(&format!("{}local c := Color.Purple", source)).unwrap_err();
+        assert_eq!(err.message(), "enum 'Color' has no member 'Purple'");
+        let err = check(&format!("{}Color.Purple()", source)).unwrap_err();
+        assert_eq!(err.message(), "enum 'Color' has no member 'Purple'");
+        assert!(check(&format!("{}local c: Color = 1", source)).is_err());
+        assert!(check(&format!("{}enum Size {{ Small }}\nlocal c: Color = Size.Small", source)).is_err());
+    }
+
+    #[test]
+    fn test_struct_constructor() {
+        let source = "struct Point { x: int, y: int }\n";
+        assert!(check(&format!("{}local p: Point = Point {{ x = 1, y = 2 }}\nlocal sum: int = p.x + p.y", source)).is_ok());
+
+        let err = check(&format!("{}local p := Point {{ x = 1 }}", source)).unwrap_err();
+        assert!(err.to_string().contains("Missing field 'y'"));
+        let err = check(&format!("{}local p := Point {{ x = 1, y = \"two\" }}", source)).unwrap_err();
+        assert!(err.to_string().contains("Field 'y' type mismatch: expected int, got string"));
+        let err = check(&format!("{}local p := Point {{ x = 1, y = 2, z = 3 }}", source)).unwrap_err();
+        assert!(err.to_string().contains("Point has no field 'z'"));
+    }
+
+    #[test]
+    fn test_tuple_element_types() {
+        assert!(check("local pair: (int, string) = (1, \"a\")\nlocal n: int = pair[1]\nlocal s: string = pair[-1]").is_ok());
+        assert!(check("local a, b = (1, \"x\")\nlocal s: string = b\nlocal n: int = #(a, b)").is_ok());
+
+        let err = check("local pair: (int, string) = (\"a\", 1)").unwrap_err();
+        assert!(err.to_string().contains("declared as (int, string) but initialized with (string, int)"));
+        let err = check("local pair := (1, \"a\")\nlocal s: string = pair[1]").unwrap_err();
+        assert!(err.to_string().contains("declared as string but initialized with int"));
+        let err = check("local pair := (1, \"a\")\npair[3]").unwrap_err();
+        assert!(err.to_string().contains("Tuple index 3 out of range"));
+        let err = check("local a, b, c = (1, 2)").unwrap_err();
+        assert!(err.to_string().contains("Cannot destructure"));
+    }
+
+    #[test]
+    fn test_optional_types() {
+        assert!(check("local x: int? = nil\nx = 5\nx = nil").is_ok());
+        assert!(check("fn find(n: int) -> int? { if n > 0 { return n }\nreturn nil }").is_ok());
+        let err = check("local x: int = nil").unwrap_err();
+        assert!(err.to_string().contains("declared as int but initialized with nil"));
+
+        // An optional must be checked for nil before it is used as its inner type
+        let err = check("local x: int? = 1\nlocal y: int = x").unwrap_err();
+        assert!(err.to_string().contains("declared as int but initialized with int?"));
+        assert!(check("local x: int? = 1\nlocal y := x + 1").is_err());
+        assert!(check("local x: int? = 1\nif x != nil { local y: int = x + 1 }").is_ok());
+        assert!(check("local x: int? = 1\nif nil != x { local y: int = x }").is_ok());
+    }
+
+    #[test]
+    fn test_nil_checks_narrow_each_branch() {
+        let source = "local x: int? = 1\n";
+        assert!(check(&format!("{}if x == nil {{ local n: nil = x }} else {{ local y: int = x * 2 }}", source)).is_ok());
+        assert!(check(&format!("{}if x != nil {{ local y: int = x }} else {{ local n: nil = x }}", source)).is_ok());
+
+        // The refinement only applies inside the guarded branch
+        assert!(check(&format!("{}if x == nil {{ local y: int = x }}", source)).is_err());
+        assert!(check(&format!("{}if x != nil {{ local y: int = x }} else {{ local y: int = x }}", source)).is_err());
+        assert!(check(&format!("{}if x != nil {{ local y: int = x }}\nlocal z: int = x", source)).is_err());
+        // Other conditions don't narrow
+        assert!(check(&format!("{}if x != nil and true {{ local y: int = x }}", source)).is_err());
+    }
+
+    #[test]
+    fn test_assign_through_pointer() {
+        assert!(check("local x := 1\nlocal p := &x\n*p = 5\nlocal y: int = *p").is_ok());
+        let err = check("local x := 1\nlocal p := &x\n*p = \"five\"").unwrap_err();
+        assert!(err.to_string().contains("cannot assign string through a pointer to int"));
+        let err = check("local x := 1\n*x = 2").unwrap_err();
+        assert!(err.to_string().contains("Cannot dereference non-pointer type int"));
+    }
+
+    #[test]
+    fn test_chained_comparison_suggests_and() {
+        let err = check("local x := 5\nlocal ok := 1 < x < 10").unwrap_err();
+        assert!(err.to_string().contains("Comparisons cannot be chained; write `1 < x and x < 10` instead"), "{}", err);
+        let err = check("local x := 5.0\nif 0.5 <= x > 2 { print(x) }").unwrap_err();
+        assert!(err.to_string().contains("write `0.5 <= x and x > 2` instead"), "{}", err);
+
+        assert!(check("local x := 5\nlocal ok := 1 < x and x < 10").is_ok());
+        assert!(check("local x := 5\nlocal same := (1 < x) == true").is_ok());
+    }
+
+    #[test]
+    fn test_nil_is_not_universal() {
+        let err = check("local x: int = nil").unwrap_err();
+        assert!(err.to_string().contains("declared as int but initialized with nil"));
+        assert!(check("local x: int = 5\nx = nil").is_err());
+        assert!(check("fn f() -> int { return nil }").is_err());
+        assert!(check("fn f(n: int) -> int { return n }\nf(nil)").is_err());
+        assert!(check("local n := nil + 1").is_err());
+    }
+}
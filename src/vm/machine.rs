@@ -0,0 +1,252 @@
+//! Bytecode virtual machine
+//!
+//! This module implements a stack machine that runs a compiled [`Program`].
+//! Operators and builtins are shared with the tree-walking interpreter, so
+//! both backends produce the same results and error messages.
+
+use std::collections::HashMap;
+use std::io::Write;
+use crate::compiler::{Function, OpCode, Program};
+use crate::error::{LuxError, LuxResult, SourceLocation};
+use crate::runtime::interpreter::DEFAULT_MAX_DEPTH;
+use crate::runtime::{Interpreter, Value};
+
+/// An active function call
+struct Frame<'p> {
+    function: &'p Function,
+    ip: usize,
+    /// Stack index of the function's first local (its first argument)
+    base: usize,
+}
+
+/// Bytecode virtual machine
+pub struct Vm {
+    /// Provides builtins and operator semantics
+    host: Interpreter,
+    globals: HashMap<String, Value>,
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        Self {
+            host: Interpreter::new(),
+            globals: HashMap::new(),
+        }
+    }
+
+    /// Send program output (e.g. from `print`) to `output` instead of stdout
+    pub fn with_output(mut self, output: Box<dyn Write + Send>) -> Self {
+        self.host = self.host.with_output(output);
+        self
+    }
+
+    /// Run a program and return its result
+    ///
+    /// As with [`Interpreter::eval`], the result is the value of a top-level
+    /// `return` or of the final expression statement, or else nil.
+    pub fn run(&mut self, program: &Program) -> LuxResult<Value> {
+        let mut stack: Vec<Value> = Vec::new();
+        let mut frames = vec![Frame { function: &program.main, ip: 0, base: 0 }];
+
+        loop {
+            let frame = frames.last_mut().expect("the main frame returns before it is popped");
+            let (function, base, ip) = (frame.function, frame.base, frame.ip);
+            frame.ip += 1;
+            let chunk = &function.chunk;
+            let location = &chunk.locations[ip];
+
+            match &chunk.code[ip] {
+                OpCode::Constant(index) => stack.push(chunk.constants[*index].clone()),
+                OpCode::Nil => stack.push(Value::Nil),
+                OpCode::True => stack.push(Value::Bool(true)),
+                OpCode::False => stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    stack.pop();
+                }
+
+                OpCode::GetLocal(slot) => stack.push(stack[base + slot].clone()),
+                OpCode::SetLocal(slot) => stack[base + slot] = Self::peek(&stack).clone(),
+                OpCode::DefineGlobal(name) => {
+                    let value = Self::pop(&mut stack);
+                    self.globals.insert(Self::name(chunk, *name).to_string(), value);
+                }
+                OpCode::GetGlobal(name) => {
+                    let value = self.global(Self::name(chunk, *name), location)?;
+                    stack.push(value);
+                }
+                OpCode::SetGlobal(name) => {
+                    let name = Self::name(chunk, *name);
+                    if !self.globals.contains_key(name) {
+                        return Err(Self::undefined(name, location));
+                    }
+                    self.globals.insert(name.to_string(), Self::peek(&stack).clone());
+                }
+
+                OpCode::Binary(op) => {
+                    let right = Self::pop(&mut stack);
+                    let left = Self::pop(&mut stack);
+                    stack.push(self.host.eval_binary(left, op, right, location)?);
+                }
+                OpCode::Unary(op) => {
+                    let operand = Self::pop(&mut stack);
+                    stack.push(self.host.eval_unary(op, operand, location)?);
+                }
+
+                OpCode::Jump(target) => Self::jump(&mut frames, *target),
+                OpCode::JumpIfFalse(target) => {
+                    if !Self::peek(&stack).is_truthy() {
+                        Self::jump(&mut frames, *target);
+                    }
+                }
+                OpCode::JumpIfTrue(target) => {
+                    if Self::peek(&stack).is_truthy() {
+                        Self::jump(&mut frames, *target);
+                    }
+                }
+
+                OpCode::Call { function, argc } => {
+                    let callee = &program.functions[*function];
+                    if *argc != callee.arity {
+                        return Err(LuxError::runtime_error(
+                            format!("Expected {} arguments but got {}", callee.arity, argc),
+                            Some(location.clone()),
+                        ));
+                    }
+                    // The main frame doesn't count as a call
+                    if frames.len() > DEFAULT_MAX_DEPTH {
+                        return Err(LuxError::runtime_error(
+                            format!("stack overflow (more than {} nested calls)", DEFAULT_MAX_DEPTH),
+                            Some(location.clone()),
+                        ));
+                    }
+                    // The arguments already on the stack become the callee's first locals
+                    frames.push(Frame { function: callee, ip: 0, base: stack.len() - argc });
+                }
+                OpCode::CallGlobal { name, argc } => {
+                    let callee = self.global(Self::name(chunk, *name), location)?;
+                    let args = stack.split_off(stack.len() - argc);
+                    stack.push(self.host.call_function(callee, args, location)?);
+                }
+
+                OpCode::Return => {
+                    let result = Self::pop(&mut stack);
+                    let finished = frames.pop().expect("a frame is running");
+                    if frames.is_empty() {
+                        return Ok(result);
+                    }
+                    stack.truncate(finished.base);
+                    stack.push(result);
+                }
+            }
+        }
+    }
+
+    /// Look up a global, falling back to the builtins
+    fn global(&self, name: &str, location: &SourceLocation) -> LuxResult<Value> {
+        self.globals
+            .get(name)
+            .cloned()
+            .or_else(|| self.host.global(name))
+            .ok_or_else(|| Self::undefined(name, location))
+    }
+
+    fn undefined(name: &str, location: &SourceLocation) -> LuxError {
+        LuxError::runtime_error(format!("Undefined variable '{}'", name), Some(location.clone()))
+    }
+
+    fn name(chunk: &crate::compiler::Chunk, index: usize) -> &str {
+        match &chunk.constants[index] {
+            Value::String(name) => name,
+            other => unreachable!("variable name constant is a {}", other.type_name()),
+        }
+    }
+
+    fn jump(frames: &mut [Frame], target: usize) {
+        if let Some(frame) = frames.last_mut() {
+            frame.ip = target;
+        }
+    }
+
+    // The compiler only emits instructions whose operands are on the stack
+    fn pop(stack: &mut Vec<Value>) -> Value {
+        stack.pop().expect("bytecode popped an empty stack")
+    }
+
+    fn peek(stack: &[Value]) -> &Value {
+        stack.last().expect("bytecode read an empty stack")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::compile;
+    use crate::runtime::testing::SharedBuffer;
+    use crate::{Lexer, Parser};
+
+    /// Run `source` on both backends, returning each one's result and output
+    fn run_both(source: &str) -> ((LuxResult<Value>, String), (LuxResult<Value>, String)) {
+        let tokens = Lexer::new(source, None).tokenize().unwrap();
+        let ast = Parser::new(tokens).parse().unwrap();
+
+        let interpreter_output = SharedBuffer::default();
+        let interpreted = Interpreter::new()
+            .with_output(Box::new(interpreter_output.clone()))
+            .eval(&ast);
+
+        let vm_output = SharedBuffer::default();
+        let program = compile(&ast).unwrap();
+        let executed = Vm::new().with_output(Box::new(vm_output.clone())).run(&program);
+
+        ((interpreted, interpreter_output.contents()), (executed, vm_output.contents()))
+    }
+
+    #[test]
+    fn test_vm_matches_interpreter() {
+        let programs = [
+            // Arithmetic and strings
+            "print(1 + 2 * 3)\nprint(7 / 2)\nprint(-7 ~/ 2)\nprint(7 % 3)\nprint(\"lu\" + \"x\")\n2.5 * 4",
+            // Globals, locals, shadowing and assignment
+            "local x := 1\n{ local x := 10\nx = x + 1\nprint(x) }\nx = x + 1\nprint(x)\nx",
+            // if/else and short-circuit logic
+            "local n := 7\nif n > 5 { print(\"big\") } else { print(\"small\") }\n\
+             print(nil or \"default\")\nprint(n > 1 and n < 10)\nprint(not n)",
+            // while and for with break and continue
+            "local i := 0\nlocal total := 0\nwhile true { i = i + 1\nif i > 10 { break }\n\
+             if i % 2 == 0 { continue }\nlocal odd := i\ntotal = total + odd }\nprint(total)\n\
+             for local j := 0; j < 5; j = j + 1 { if j == 3 { continue }\nprint(j) }",
+            // Recursion, hoisting, early returns and builtins
+            "print(fib(15))\nfn fib(n: int) -> int { if n < 2 { return n }\nreturn fib(n - 1) + fib(n - 2) }\n\
+             fn first_over(limit: int) -> int { local k := 0\nwhile true { k = k + 1\nif k * k > limit { return k } } }\n\
+             print(first_over(50))\nprint(type_of(sqrt(16)))\nreturn fib(10)",
+        ];
+        for source in programs {
+            let ((interpreted, interpreter_output), (executed, vm_output)) = run_both(source);
+            assert_eq!(vm_output, interpreter_output, "output differs for:\n{}", source);
+            assert_eq!(executed.unwrap(), interpreted.unwrap(), "result differs for:\n{}", source);
+        }
+    }
+
+    #[test]
+    fn test_vm_runtime_errors_match_interpreter() {
+        for source in ["local x := 0\nprint(1 ~/ x)", "missing + 1", "fn f(n: int) { f(\"a\", n) }\nf(1)"] {
+            let ((interpreted, _), (executed, _)) = run_both(source);
+            let (interpreted, executed) = (interpreted.unwrap_err(), executed.unwrap_err());
+            assert_eq!(executed.message(), interpreted.message(), "error differs for:\n{}", source);
+        }
+    }
+
+    #[test]
+    fn test_vm_reports_stack_overflow() {
+        let tokens = Lexer::new("fn f(n: int) -> int { return f(n + 1) }\nf(0)", None).tokenize().unwrap();
+        let program = compile(&Parser::new(tokens).parse().unwrap()).unwrap();
+        let err = Vm::new().run(&program).unwrap_err();
+        assert_eq!(err.message(), format!("stack overflow (more than {} nested calls)", DEFAULT_MAX_DEPTH));
+    }
+}
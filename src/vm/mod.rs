@@ -0,0 +1,7 @@
+//! Virtual machine module
+//!
+//! This module executes bytecode produced by the `compiler` module.
+
+pub mod machine;
+
+pub use machine::Vm;